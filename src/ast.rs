@@ -1,22 +1,58 @@
 pub mod nodes {
-    use crate::scope::values::{Value, ValueKind};
+    use crate::{diagnostic::diagnostic::Span, lint::lint::Level};
 
     #[derive(Debug, Clone)]
     pub enum Node {
         Binary(BinaryNode),
+        Compare(CompareNode),
+        Logical(LogicalNode),
         Unary(UnaryNode),
         Literal(LiteralNode),
-        Ignore,
+        Identifier(IdentifierNode),
+        List(ListNode),
+        Matrix(MatrixNode),
+        Index(IndexNode),
+        Slice(SliceNode),
+        Interpolation(InterpolationNode),
+        Call(CallNode),
+        Method(MethodNode),
+        Tuple(TupleNode),
+        Map(MapNode),
+        Variant(VariantNode),
+        Assign(AssignNode),
+        Destructure(DestructureNode),
+        Out(OutNode),
+        If(IfNode),
+        Match(MatchNode),
+        For(ForNode),
+        Func(FuncNode),
+        Enum(EnumNode),
+        Lambda(FuncNode),
+        Return(ReturnNode),
+        Raise(RaiseNode),
+        Try(TryNode),
+        Include(IncludeNode),
     }
 
+    // The left hand side of a destructuring assignment or a for loop
+    // e.g. `x`, `x, y` or `(x, (y, z))`
     #[derive(Debug, Clone)]
+    pub enum Pattern {
+        Name(String),
+        Tuple(Vec<Pattern>),
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
     pub enum UnaryOp {
         //increment,
         //decrement,
         Negative,
+        Not,
+        // `&value`, the field of an enum variant
+        Field,
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, PartialEq)]
     pub enum BinaryOp {
         Plus,
         Minus,
@@ -24,11 +60,29 @@ pub mod nodes {
         Divide,
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum CompareOp {
+        Equal,
+        NotEqual,
+        Less,
+        LessEqual,
+        More,
+        MoreEqual,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum LogicalOp {
+        And,
+        Or,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
     pub enum Literal {
         Interger(i32),
         Float(f64),
         String(String),
+        Bool(bool),
+        Nil,
     }
 
     #[derive(Debug, Clone)]
@@ -36,138 +90,1716 @@ pub mod nodes {
         pub left: Box<Node>,
         pub right: Box<Node>,
         pub op: BinaryOp,
+        pub line: usize,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct CompareNode {
+        pub left: Box<Node>,
+        pub right: Box<Node>,
+        pub op: CompareOp,
+        pub line: usize,
+    }
+
+    // `and` / `or`, kept apart from BinaryNode because the right side may never run
+    #[derive(Debug, Clone)]
+    pub struct LogicalNode {
+        pub left: Box<Node>,
+        pub right: Box<Node>,
+        pub op: LogicalOp,
+        pub line: usize,
     }
 
     #[derive(Debug, Clone)]
     pub struct LiteralNode {
         pub literal: Literal
     }
-    
+
     #[derive(Debug, Clone)]
     pub struct UnaryNode {
         pub right: Box<Node>,
         pub op: UnaryOp,
+        pub line: usize,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct IdentifierNode {
+        pub name: String,
+        pub span: Span,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ListNode {
+        pub items: Vec<Node>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct TupleNode {
+        pub items: Vec<Node>,
+    }
+
+    // `{key: value, ...}`, keys are checked when the map is built
+    #[derive(Debug, Clone)]
+    pub struct MapNode {
+        pub entries: Vec<(Node, Node)>,
+        pub line: usize,
+    }
+
+    // `Enum:Variant` or `Enum:Variant(field)`
+    #[derive(Debug, Clone)]
+    pub struct VariantNode {
+        pub enum_name: String,
+        pub name: String,
+        pub field: Option<Box<Node>>,
+        pub line: usize,
+    }
+
+    // Each row of a `matrix ... end` block, rows are checked for shape at runtime
+    #[derive(Debug, Clone)]
+    pub struct MatrixNode {
+        pub rows: Vec<Vec<Node>>,
+        pub line: usize,
+    }
+
+    // `target[i]` or `target[i, j]`
+    #[derive(Debug, Clone)]
+    pub struct IndexNode {
+        pub target: Box<Node>,
+        pub indices: Vec<Node>,
+        pub line: usize,
+    }
+
+    // `target[start:end]`, either end may be left out
+    #[derive(Debug, Clone)]
+    pub struct SliceNode {
+        pub target: Box<Node>,
+        pub start: Option<Box<Node>>,
+        pub end: Option<Box<Node>>,
+        pub line: usize,
+    }
+
+    /*
+        A format specifier after the `:` in `"{value:>8.2}"`
+
+        align       `<` left, `>` right or `^` centre
+        width       the minimum number of characters
+        precision   digits after the decimal point (numbers only)
+    */
+    // The largest width or precision a format spec can ask for
+    pub const MAX_FORMAT: usize = 1000;
+
+    #[derive(Debug, Clone, Default)]
+    pub struct FormatSpec {
+        pub align: Option<char>,
+        pub width: usize,
+        pub precision: Option<usize>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum Part {
+        Text(String),
+        Value(Node, FormatSpec),
+    }
+
+    // A string literal with `{expression}` parts in it
+    #[derive(Debug, Clone)]
+    pub struct InterpolationNode {
+        pub parts: Vec<Part>,
+        pub line: usize,
+    }
+
+    // `callee(args)`, the callee is usually a name but can be any expression
+    // `args_span` runs from the `(` to the `)`
+    #[derive(Debug, Clone)]
+    pub struct CallNode {
+        pub callee: Box<Node>,
+        pub args: Vec<ArgumentNode>,
+        pub line: usize,
+        pub args_span: Span,
+    }
+
+    // A call argument, either positional `5` or labelled `width as 5`
+    #[derive(Debug, Clone)]
+    pub struct ArgumentNode {
+        pub label: Option<String>,
+        pub value: Node,
+    }
+
+    // `target.name(args)`
+    #[derive(Debug, Clone)]
+    pub struct MethodNode {
+        pub target: Box<Node>,
+        pub name: String,
+        pub args: Vec<Node>,
+        pub line: usize,
+        pub args_span: Span,
+    }
+
+    // `name = value`, or `const name = value` for a name that shouldn't be shadowed
+    #[derive(Debug, Clone)]
+    pub struct AssignNode {
+        pub name: String,
+        pub value: Box<Node>,
+        pub constant: bool,
+        pub span: Span,
+    }
+
+    // `x, y = point`
+    #[derive(Debug, Clone)]
+    pub struct DestructureNode {
+        pub pattern: Pattern,
+        pub value: Box<Node>,
+        pub line: usize,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct OutNode {
+        pub value: Box<Node>,
+        pub line: usize,
+    }
+
+    /*
+        A parameter of a function:
+
+        width               positional or `width as 5`
+        p #perimeter        `perimeter as 5` from the caller, `p` inside the function
+        width = 1           may be left out by the caller
+    */
+    #[derive(Debug, Clone)]
+    pub struct ParamNode {
+        pub name: String,
+        pub label: Option<String>,
+        pub default: Option<Node>,
+        pub span: Span,
+    }
+
+    impl ParamNode {
+        // Returns the label callers use for this parameter
+        pub fn external(&self) -> &str {
+            self.label.as_deref().unwrap_or(&self.name)
+        }
+    }
+
+    // `func name <- params ... end`, anonymous functions are named `lambda` and marked `anonymous`
+    #[derive(Debug, Clone)]
+    pub struct FuncNode {
+        pub name: String,
+        pub anonymous: bool,
+        pub params: Vec<ParamNode>,
+        pub body: Vec<Node>,
+        pub line: usize,
+    }
+
+    // `enum Name ... end`, each variant says whether it has a field
+    #[derive(Debug, Clone)]
+    pub struct EnumNode {
+        pub name: String,
+        pub variants: Vec<(String, bool)>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ReturnNode {
+        pub value: Option<Box<Node>>,
+        pub line: usize,
+    }
+
+    // `raise value`, the value is a Str message or an Error that was caught
+    #[derive(Debug, Clone)]
+    pub struct RaiseNode {
+        pub value: Box<Node>,
+        pub line: usize,
+    }
+
+    /*
+        try
+            ...
+        catch err
+            ...
+        end
+
+        The name after `catch` may be left out if the error isn't needed.
+    */
+    #[derive(Debug, Clone)]
+    pub struct TryNode {
+        pub body: Vec<Node>,
+        pub binding: Option<IdentifierNode>,
+        pub handler: Vec<Node>,
+        pub line: usize,
+    }
+
+    /*
+        include plotting, stats end
+
+        Each name is a file next to the one including it (`plotting.dy`), run in place
+        of the include. The parser only reads the names, `body` is filled in by the loader
+        with the statements of every file that hadn't been included already.
+    */
+    #[derive(Debug, Clone)]
+    pub struct IncludeNode {
+        pub modules: Vec<IdentifierNode>,
+        pub body: Vec<Node>,
+        pub line: usize,
+    }
+
+    // `if cond ... elif cond ... else ... end`
+    // Each branch is a condition and its body, checked in order
+    #[derive(Debug, Clone)]
+    pub struct IfNode {
+        pub branches: Vec<(Node, Vec<Node>)>,
+        pub otherwise: Option<Vec<Node>>,
+        pub line: usize,
+    }
+
+    // What a `case` of a match compares against
+    #[derive(Debug, Clone)]
+    pub enum CasePattern {
+        // `Option:Some(x)` binds the field to `x`
+        Variant { enum_name: String, name: String, binding: Option<String> },
+        // Any other expression is compared with `==`
+        Value(Node),
+    }
+
+    /*
+        match value
+        case Option:Some(x)
+            ...
+        case nil
+            ...
+        else
+            ...
+        end
+    */
+    #[derive(Debug, Clone)]
+    pub struct MatchNode {
+        pub value: Box<Node>,
+        pub cases: Vec<(CasePattern, Vec<Node>)>,
+        pub otherwise: Option<Vec<Node>>,
+        pub line: usize,
+    }
+
+    /*
+        `@allow(unused_variable, float_equality)` on the line before a statement sets the
+        level of those lints for the statement and everything nested in it, `first` and `last`
+        being the lines the statement covers.
+    */
+    #[derive(Debug, Clone)]
+    pub struct Annotation {
+        pub level: Level,
+        pub lints: Vec<(String, Span)>,
+        pub first: usize,
+        pub last: usize,
+    }
+
+    // `for pattern in iterable ... end`
+    #[derive(Debug, Clone)]
+    pub struct ForNode {
+        pub pattern: Pattern,
+        pub iterable: Box<Node>,
+        pub body: Vec<Node>,
+        pub line: usize,
     }
 }
 
 pub mod ast {
-    use crate::{ast::nodes::{Literal, LiteralNode}, tokens::tokens::{Token, TokenKind}};
+    use crate::{
+        codes::codes,
+        diagnostic::diagnostic::Span,
+        error::errors::Errors,
+        lexer::lexer::{KEYWORDS, Lexer},
+        lint::lint::Level,
+        source::source::SourceMap,
+        suggest::suggest,
+        tokens::tokens::{Token, TokenKind},
+    };
 
-    use super::nodes::{BinaryNode, BinaryOp, Node};
+    use super::nodes::*;
 
+    /*
+        Ast is a recursive descent parser over the lexer's tokens.
+        Each method below parses one level of precedence (lowest first)
+        and returns the node it built, or the first SyntaxError it hit.
+
+        A block records the error of a statement that fails and skips to the next one,
+        so every mistake in the file is reported rather than only the first.
+    */
     pub struct Ast {
         idx: usize,
+        functions: usize,
+        // How many blocks deep the statement being parsed is, the top of the file being 1
+        depth: usize,
         pub tokens: Vec<Token>,
         pub nodes: Vec<Node>,
+        pub annotations: Vec<Annotation>,
+        pub errors: Vec<Errors>,
     }
 
     impl Ast {
-        fn match_token(&mut self, token: &Token, idx: usize) -> Node {
+        // Create a new AST struct with the tokens and empty node vec
+        pub fn new(tokens: Vec<Token>) -> Self {
+            Self {
+                idx: 0_usize,
+                functions: 0_usize,
+                depth: 0_usize,
+                tokens,
+                nodes: Vec::new(),
+                annotations: Vec::new(),
+                errors: Vec::new(),
+            }
+        }
+
+        // Returns the current token without consuming it
+        fn peek(&self) -> &Token {
+            &self.tokens[self.idx.min(self.tokens.len() - 1)]
+        }
+
+        // Returns the kind of the token after the current one
+        fn peek_next(&self) -> &TokenKind {
+            &self.tokens[(self.idx + 1).min(self.tokens.len() - 1)].kind
+        }
+
+        // Returns the token that was consumed last
+        fn previous(&self) -> &Token {
+            &self.tokens[self.idx.saturating_sub(1)]
+        }
+
+        // Consumes the current token and returns it
+        fn next(&mut self) -> Token {
+            let token = self.peek().clone();
+            if token.kind != TokenKind::EndOfFile {
+                self.idx += 1;
+            }
+            token
+        }
+
+        // Consumes the current token if it is of the kind given
+        fn check(&mut self, kind: TokenKind) -> bool {
+            if self.peek().kind == kind {
+                self.next();
+                true
+            } else {
+                false
+            }
+        }
+
+        // Consumes a token of the kind given or returns a SyntaxError
+        fn expect(&mut self, kind: TokenKind, what: &str) -> Result<Token, Errors> {
+            if self.peek().kind == kind {
+                Ok(self.next())
+            } else {
+                Err(self.unexpected(what))
+            }
+        }
+
+        // Builds a SyntaxError for the current token
+        fn unexpected(&self, what: &str) -> Errors {
+            let token = self.peek();
+            Errors::SyntaxError(token.span(), format!("expected {what} but found `{}`", token.lex))
+        }
+
+        fn skip_newlines(&mut self) {
+            while self.peek().kind == TokenKind::Newline || self.peek().kind == TokenKind::Semicolon {
+                self.next();
+            }
+        }
+
+        // Take the tokens from AST.Tokens and construct the AST
+        // Returns every error in the file, in the order they were found
+        pub fn build(&mut self) -> Result<&Vec<Node>, Vec<Errors>> {
+            match self.block(&[]) {
+                Ok(nodes) => self.nodes = nodes,
+                Err(error) => self.errors.push(error),
+            }
+
+            if self.errors.is_empty() {
+                Ok(&self.nodes)
+            } else {
+                Err(std::mem::take(&mut self.errors))
+            }
+        }
+
+        // Parses statements until the end of the file or one of the terminators given
+        // The terminator itself is left for the caller to consume
+        fn block(&mut self, terminators: &[TokenKind]) -> Result<Vec<Node>, Errors> {
+            self.depth += 1;
+            let nodes = self.statements(terminators);
+            self.depth -= 1;
+            nodes
+        }
+
+        fn statements(&mut self, terminators: &[TokenKind]) -> Result<Vec<Node>, Errors> {
+            let mut nodes = Vec::new();
+            self.skip_newlines();
+
+            while !terminators.contains(&self.peek().kind) {
+                if self.peek().kind == TokenKind::EndOfFile {
+                    if terminators.is_empty() {
+                        break;
+                    }
+                    return Err(self.unexpected("`end`"));
+                }
+
+                let start = self.idx;
+                match self.block_statement() {
+                    Ok(node) => nodes.push(node),
+
+                    // Running into the end of the file inside a block can't be recovered from,
+                    // the error goes up through every open block so each can say where it started
+                    Err(error) if self.peek().kind == TokenKind::EndOfFile && !terminators.is_empty() => return Err(error),
+                    Err(error) => {
+                        self.errors.push(error);
+                        self.synchronize(start, terminators);
+                    },
+                }
+            }
+
+            Ok(nodes)
+        }
+
+        // Parses one statement of a block, along with its annotations and whatever ends it
+        fn block_statement(&mut self) -> Result<Node, Errors> {
+            let annotations = self.annotations()?;
+            let start = self.peek().clone();
+            let first = start.line;
+
+            // Only the blocks that are open get to use these
+            if let Some(message) = Self::stray(&start.kind) {
+                return Err(Errors::SyntaxError(start.span(), message.to_string()).code(codes::UNBALANCED_BLOCK));
+            }
+
+            let node = self.statement()?;
+
+            let last = self.previous().line;
+            self.annotations.extend(annotations.into_iter().map(|a| Annotation { first, last, ..a }));
+
+            // Every statement ends at a newline, a semicolon or the end of the file
+            match self.peek().kind {
+                TokenKind::Newline | TokenKind::Semicolon => self.skip_newlines(),
+                TokenKind::EndOfFile => {},
+                _ => return Err(Self::misspelled(&start, self.unexpected("the end of the statement"))),
+            }
+
+            Ok(node)
+        }
+
+        // Returns what is wrong with a keyword that ends or divides a block, found where no block wants it
+        fn stray(kind: &TokenKind) -> Option<&'static str> {
+            match kind {
+                TokenKind::End => Some("this `end` doesn't close anything, every block before it is already closed"),
+                TokenKind::Elif => Some("`elif` can only follow the body of an `if`"),
+                TokenKind::Else => Some("`else` can only follow the body of an `if` or a `match`"),
+                TokenKind::Case => Some("`case` can only be used inside a `match`"),
+                TokenKind::Catch => Some("`catch` can only follow the body of a `try`"),
+                _ => None,
+            }
+        }
+
+        /*
+            Skips the rest of a statement that failed to parse, starting from its first token.
+            Any block the statement opened is skipped up to its matching `end`, then the
+            skipping stops at the end of the line (or before a terminator of the block it is in).
+
+            Brackets may span lines, so a newline inside them only ends the statement if the
+            next line starts like a statement would (the bracket was probably never closed).
+        */
+        fn synchronize(&mut self, start: usize, terminators: &[TokenKind]) {
+            if self.idx == start {
+                self.next();
+            }
+
+            let mut depth = 0_usize;
+            let mut brackets = 0_usize;
+            for i in start..self.idx {
+                Self::count(&self.tokens, i, &mut depth, &mut brackets);
+            }
+
+            loop {
+                let kind = self.peek().kind.clone();
+                match kind {
+                    TokenKind::EndOfFile => return,
+                    TokenKind::Newline | TokenKind::Semicolon if depth == 0 => {
+                        self.skip_newlines();
+                        if brackets == 0 || Self::starts_statement(&self.peek().kind) {
+                            return;
+                        }
+                        continue;
+                    },
+                    _ if depth == 0 && terminators.contains(&kind) => return,
+                    _ => Self::count(&self.tokens, self.idx, &mut depth, &mut brackets),
+                }
+                self.next();
+            }
+        }
+
+        // Keeps count of the blocks and brackets open while skipping over the token at `i`
+        // The formatter indents by the same count
+        pub fn count(tokens: &[Token], i: usize, depth: &mut usize, brackets: &mut usize) {
+            match tokens[i].kind {
+                TokenKind::If | TokenKind::For | TokenKind::Match | TokenKind::Enum | TokenKind::Matrix | TokenKind::Try
+                    | TokenKind::Include => *depth += 1,
+
+                // `func <- x -> x * 2` has no body to close
+                TokenKind::Func => {
+                    let short = tokens[i..].iter()
+                        .take_while(|t| !matches!(t.kind, TokenKind::Newline | TokenKind::EndOfFile))
+                        .any(|t| t.kind == TokenKind::RArrow);
+                    if !short {
+                        *depth += 1;
+                    }
+                },
+                TokenKind::End => *depth = depth.saturating_sub(1),
+                TokenKind::LPar | TokenKind::LBrac | TokenKind::LCurl => *brackets += 1,
+                TokenKind::RPar | TokenKind::RBrac | TokenKind::RCurl => *brackets = brackets.saturating_sub(1),
+                _ => {},
+            }
+        }
+
+        // Returns whether a block or bracket is still open at the end of the tokens,
+        // so the REPL knows to keep reading lines before running them
+        pub fn unclosed(&self) -> bool {
+            let (mut depth, mut brackets) = (0, 0);
+            for i in 0..self.tokens.len() {
+                Self::count(&self.tokens, i, &mut depth, &mut brackets);
+            }
+            depth > 0 || brackets > 0
+        }
+
+        // Returns whether a token can only be the start of a statement (or the end of a block)
+        fn starts_statement(kind: &TokenKind) -> bool {
+            matches!(
+                kind,
+                TokenKind::Out | TokenKind::If | TokenKind::For | TokenKind::Match | TokenKind::Enum
+                    | TokenKind::Return | TokenKind::Const | TokenKind::At | TokenKind::Try | TokenKind::Raise | TokenKind::Include
+                    | TokenKind::End | TokenKind::Elif | TokenKind::Else | TokenKind::Case | TokenKind::Catch,
+            )
+        }
+
+        // A statement that starts with a name close to a keyword and then goes wrong,
+        // e.g. `elsif x > 1` or `retrun x`, probably meant the keyword
+        fn misspelled(start: &Token, error: Errors) -> Errors {
+            let TokenKind::Identifier(name) = &start.kind else {
+                return error;
+            };
+
+            match suggest::closest(name, KEYWORDS.iter().copied()) {
+                Some(keyword) => error
+                    .label(start.span(), &format!("`{name}` isn't a keyword"))
+                    .fix(start.span(), keyword, &format!("did you mean `{keyword}`?")),
+                None => error,
+            }
+        }
+
+        // Parses any `@level(lint, ...)` lines before a statement
+        // The lines they cover are filled in once the statement has been parsed
+        fn annotations(&mut self) -> Result<Vec<Annotation>, Errors> {
+            let mut annotations = Vec::new();
+
+            while self.check(TokenKind::At) {
+                let token = self.peek().clone();
+                let level = match &token.kind {
+                    TokenKind::Identifier(name) => Level::from_name(name).ok_or_else(|| Errors::SyntaxError(token.span(), format!(
+                        "unknown lint level `{name}`, expected `allow`, `warn` or `deny`",
+                    )))?,
+                    _ => return Err(self.unexpected("`allow`, `warn` or `deny` after `@`")),
+                };
+                self.next();
+
+                self.expect(TokenKind::LPar, "`(`")?;
+                let mut lints = Vec::new();
+                loop {
+                    let token = self.peek().clone();
+                    match &token.kind {
+                        TokenKind::Identifier(name) => lints.push((name.clone(), token.span())),
+                        _ => return Err(self.unexpected("the name of a lint")),
+                    }
+                    self.next();
+                    if !self.check(TokenKind::Comma) {
+                        break;
+                    }
+                }
+                self.expect(TokenKind::RPar, "`)`")?;
+
+                // The annotation has to be followed by the statement it applies to
+                if !matches!(self.peek().kind, TokenKind::Newline | TokenKind::Semicolon) {
+                    return Err(self.unexpected("a new line after the annotation"));
+                }
+                self.skip_newlines();
+                if matches!(self.peek().kind, TokenKind::End | TokenKind::Else | TokenKind::Elif | TokenKind::Case | TokenKind::Catch | TokenKind::EndOfFile) {
+                    return Err(self.unexpected("a statement after the annotation"));
+                }
+
+                annotations.push(Annotation { level, lints, first: 0, last: 0 });
+            }
+
+            Ok(annotations)
+        }
+
+        // Parses a single name or a parenthesised group of patterns
+        fn pattern_item(&mut self) -> Result<Pattern, Errors> {
+            match self.peek().kind.clone() {
+                TokenKind::Identifier(name) => {
+                    self.next();
+                    Ok(Pattern::Name(name))
+                },
+                TokenKind::LPar => {
+                    self.next();
+                    let pattern = self.pattern()?;
+                    self.expect(TokenKind::RPar, "`)`")?;
+                    Ok(pattern)
+                },
+                _ => Err(self.unexpected("a name or `(`")),
+            }
+        }
+
+        // Parses `a`, `a, b` or `(a, b), c`
+        // A single item on its own is not wrapped in a tuple
+        fn pattern(&mut self) -> Result<Pattern, Errors> {
+            let mut items = vec![self.pattern_item()?];
+            while self.check(TokenKind::Comma) {
+                items.push(self.pattern_item()?);
+            }
+
+            if items.len() == 1 {
+                Ok(items.pop().unwrap())
+            } else {
+                Ok(Pattern::Tuple(items))
+            }
+        }
+
+        // Parses `x, y = point` or `(q, w), e = t`
+        fn destructure(&mut self) -> Result<Node, Errors> {
+            let line = self.peek().line;
+            let pattern = self.pattern()?;
+            self.expect(TokenKind::Equal, "`=`")?;
+            let value = Box::new(self.expression()?);
+            Ok(Node::Destructure(DestructureNode { pattern, value, line }))
+        }
+
+        // Looks ahead to tell `(q, w), e = t` apart from an expression that starts with `(`
+        // A pattern is only names, commas and balanced parentheses, followed by `=`
+        fn starts_pattern(&self) -> bool {
+            let mut depth = 0usize;
+            for token in &self.tokens[self.idx..] {
+                match token.kind {
+                    TokenKind::LPar => depth += 1,
+                    TokenKind::RPar if depth > 0 => depth -= 1,
+                    TokenKind::Identifier(_) | TokenKind::Comma => {},
+                    TokenKind::Equal => return depth == 0,
+                    _ => return false,
+                }
+            }
+            false
+        }
+
+        /*
+            Functions look like:
+
+            func area <- height, width = 1
+                return height * width
+            end
+
+            A function with no parameters leaves out the `<-`.
+            Leaving out the name makes an anonymous function, which is an expression:
+
+            double = func <- x -> x * 2
+            xs.each(func <- x
+                out x
+            end)
+        */
+        fn function(&mut self) -> Result<Node, Errors> {
+            let line = self.next().line;
+            let name = match self.peek().kind.clone() {
+                TokenKind::Identifier(name) => {
+                    self.next();
+                    Some(name)
+                },
+                _ => None,
+            };
+
+            let params = self.parameters(line, name.as_deref().unwrap_or("lambda"))?;
+
+            self.functions += 1;
+            let short = self.check(TokenKind::RArrow);
+            let body = if short {
+                // `-> expression` is short for a body that only returns the expression
+                self.expression().map(|value| vec![Node::Return(ReturnNode { value: Some(Box::new(value)), line })])
+            } else {
+                self.block(&[TokenKind::End])
+            };
+            self.functions -= 1;
+            let body = body?;
+
+            if !short {
+                self.next();
+            }
+
+            Ok(match name {
+                Some(name) => Node::Func(FuncNode { name, anonymous: false, params, body, line }),
+                None => Node::Lambda(FuncNode { name: "lambda".to_string(), anonymous: true, params, body, line }),
+            })
+        }
+
+        // Parses the parameters after `<-` (if there are any)
+        fn parameters(&mut self, line: usize, name: &str) -> Result<Vec<ParamNode>, Errors> {
+            let mut params: Vec<ParamNode> = Vec::new();
+            if self.check(TokenKind::LArrow) {
+                loop {
+                    params.push(self.parameter()?);
+
+                    // Parameters without a default can't follow ones with a default
+                    let last = &params[params.len() - 1];
+                    if last.default.is_none() && params.iter().any(|p| p.default.is_some()) {
+                        return Err(Errors::SyntaxError(line.into(), format!(
+                            "parameter `{}` needs a default value because an earlier parameter has one",
+                            last.name,
+                        )));
+                    }
+
+                    if !self.check(TokenKind::Comma) {
+                        break;
+                    }
+                }
+            }
+
+            // Each label may only be used once
+            for (i, param) in params.iter().enumerate() {
+                if params[..i].iter().any(|p| p.external() == param.external()) {
+                    return Err(Errors::SyntaxError(line.into(), format!(
+                        "`{}` is used as a parameter label more than once in `{name}`",
+                        param.external(),
+                    )));
+                }
+            }
+
+            Ok(params)
+        }
+
+        // Parses `name`, `name #label` and either followed by `= default`
+        fn parameter(&mut self) -> Result<ParamNode, Errors> {
+            let span = self.peek().span();
+            let name = match self.peek().kind.clone() {
+                TokenKind::Identifier(name) => {
+                    self.next();
+                    name
+                },
+                _ => return Err(self.unexpected("a parameter name")),
+            };
+
+            let mut label = None;
+            if self.check(TokenKind::Hash) {
+                match self.peek().kind.clone() {
+                    TokenKind::Identifier(l) => {
+                        self.next();
+                        label = Some(l);
+                    },
+                    _ => return Err(self.unexpected("a label after `#`")),
+                }
+            }
+
+            let mut default = None;
+            if self.check(TokenKind::Equal) {
+                default = Some(self.expression()?);
+            }
+
+            Ok(ParamNode { name, label, default, span })
+        }
+
+        /*
+            If statements look like:
+
+            if x > 5
+                out "X is greater than 5"
+            elif x == 5
+                out "X is 5"
+            else
+                out "X is less than 5"
+            end
+        */
+        fn if_statement(&mut self) -> Result<Node, Errors> {
+            let line = self.peek().line;
+            let mut branches = Vec::new();
+            let mut otherwise = None;
+
+            // The `if` and each `elif` have a condition
+            loop {
+                self.next();
+                let condition = self.expression()?;
+                let body = self.block(&[TokenKind::Elif, TokenKind::Else, TokenKind::End])?;
+                branches.push((condition, body));
+
+                if self.peek().kind != TokenKind::Elif {
+                    break;
+                }
+            }
+
+            if self.check(TokenKind::Else) {
+                otherwise = Some(self.block(&[TokenKind::End])?);
+            }
+            self.expect(TokenKind::End, "`end`")?;
+
+            Ok(Node::If(IfNode { branches, otherwise, line }))
+        }
+
+        /*
+            Enums list one variant per line, a variant with a field names it in brackets:
+
+            enum Shape
+                Circle(radius)
+                Empty
+            end
+
+            Variants are written `Shape:Circle(2)` and `Shape:Empty`.
+        */
+        fn enum_declaration(&mut self) -> Result<Node, Errors> {
+            self.next();
+            let name = match self.peek().kind.clone() {
+                TokenKind::Identifier(name) if name.starts_with(char::is_uppercase) => name,
+                _ => return Err(self.unexpected("a capitalised name for the enum")),
+            };
+            self.next();
+
+            let mut variants: Vec<(String, bool)> = Vec::new();
+            self.skip_newlines();
+            while !self.check(TokenKind::End) {
+                let variant = match self.peek().kind.clone() {
+                    TokenKind::Identifier(v) if v.starts_with(char::is_uppercase) => v,
+                    _ => return Err(self.unexpected("a capitalised variant name or `end`")),
+                };
+                let variant_span = self.next().span();
+
+                let field = self.check(TokenKind::LPar);
+                if field {
+                    if !matches!(self.peek().kind, TokenKind::Identifier(_)) {
+                        return Err(self.unexpected("a name for the field"));
+                    }
+                    self.next();
+                    self.expect(TokenKind::RPar, "`)`")?;
+                }
+
+                if variants.iter().any(|(v, _)| *v == variant) {
+                    return Err(Errors::SyntaxError(variant_span, format!("`{name}` has more than one variant called `{variant}`")));
+                }
+                variants.push((variant, field));
+
+                match self.peek().kind {
+                    TokenKind::Newline | TokenKind::Semicolon => self.skip_newlines(),
+                    TokenKind::End => {},
+                    _ => return Err(self.unexpected("a new line after the variant")),
+                }
+            }
+
+            Ok(Node::Enum(EnumNode { name, variants }))
+        }
+
+        // Parses `include name, name end`, the names may also be on lines of their own
+        fn include(&mut self) -> Result<Node, Errors> {
+            let keyword = self.next();
+            if self.depth > 1 {
+                return Err(Errors::SyntaxError(keyword.span(), "`include` can only be used at the top level of a file".to_string()));
+            }
+
+            let mut modules: Vec<IdentifierNode> = Vec::new();
+            loop {
+                while self.check(TokenKind::Comma) || self.check(TokenKind::Newline) || self.check(TokenKind::Semicolon) {}
+                if self.check(TokenKind::End) {
+                    break;
+                }
+
+                let name = match self.peek().kind.clone() {
+                    TokenKind::Identifier(name) => name,
+                    _ if modules.is_empty() => return Err(self.unexpected("the name of a file to include")),
+                    _ => return Err(self.unexpected("another name or `end`")),
+                };
+                modules.push(IdentifierNode { name, span: self.next().span() });
+            }
+
+            if modules.is_empty() {
+                return Err(Errors::SyntaxError(keyword.span(), "`include` needs at least one file, e.g. `include plotting end`".to_string()));
+            }
+            Ok(Node::Include(IncludeNode { modules, body: Vec::new(), line: keyword.line }))
+        }
+
+        /*
+            Match statements run the first case that fits the value:
+
+            match scores.max()
+            case nil
+                out "no scores yet"
+            else
+                out "best: {best}"
+            end
+
+            `case Option:Some(x)` fits any `Some` and binds its field to `x`,
+            any other case is an expression compared to the value with `==`.
+        */
+        fn match_statement(&mut self) -> Result<Node, Errors> {
+            let line = self.next().line;
+            let value = Box::new(self.expression()?);
+            let mut cases = Vec::new();
+            let mut otherwise = None;
+
+            self.skip_newlines();
+            while self.check(TokenKind::Case) {
+                let pattern = self.case_pattern()?;
+                let body = self.block(&[TokenKind::Case, TokenKind::Else, TokenKind::End])?;
+                cases.push((pattern, body));
+            }
+
+            if self.check(TokenKind::Else) {
+                otherwise = Some(self.block(&[TokenKind::End])?);
+            }
+            if cases.is_empty() && otherwise.is_none() {
+                return Err(self.unexpected("`case`"));
+            }
+            self.expect(TokenKind::End, "`case`, `else` or `end`")?;
+
+            Ok(Node::Match(MatchNode { value, cases, otherwise, line }))
+        }
+
+        /*
+            Try statements run the `catch` body if anything in the `try` body fails:
+
+            try
+                data = load(path)
+            catch err
+                out "couldn't load {path}: {err.message()}"
+            end
+        */
+        fn try_statement(&mut self) -> Result<Node, Errors> {
+            let line = self.next().line;
+            let body = self.block(&[TokenKind::Catch, TokenKind::End])?;
+            self.expect(TokenKind::Catch, "`catch`")?;
+
+            let binding = match self.peek().kind.clone() {
+                TokenKind::Identifier(name) => Some(IdentifierNode { name, span: self.next().span() }),
+                _ => None,
+            };
+            let handler = self.block(&[TokenKind::End])?;
+            self.next();
+
+            Ok(Node::Try(TryNode { body, binding, handler, line }))
+        }
+
+        // Parses what follows `case`
+        fn case_pattern(&mut self) -> Result<CasePattern, Errors> {
+            let node = self.expression()?;
+
+            // A variant with a name in place of its field binds the field
+            Ok(match node {
+                Node::Variant(v) => {
+                    let binding = match v.field.as_deref() {
+                        None => None,
+                        Some(Node::Identifier(id)) => Some(id.name.clone()),
+                        Some(_) => return Err(Errors::SyntaxError(v.line.into(), format!(
+                            "expected a name to bind the field of `{}:{}` to, e.g. `case {}:{}(x)`",
+                            v.enum_name, v.name, v.enum_name, v.name,
+                        ))),
+                    };
+                    CasePattern::Variant { enum_name: v.enum_name, name: v.name, binding }
+                },
+                other => CasePattern::Value(other),
+            })
+        }
+
+        /*
+            For loops look like:
+
+            for (x, y) in points
+                out x
+            end
+        */
+        fn for_loop(&mut self) -> Result<Node, Errors> {
+            let line = self.next().line;
+            let pattern = self.pattern()?;
+            self.expect(TokenKind::In, "`in`")?;
+            let iterable = Box::new(self.expression()?);
+
+            let body = self.block(&[TokenKind::End])?;
+            self.next();
+
+            Ok(Node::For(ForNode { pattern, iterable, body, line }))
+        }
+
+        // Parses a block statement, pointing an error at the end of the file back at the keyword that opened it
+        fn opened(&mut self, keyword: &str, parse: fn(&mut Self) -> Result<Node, Errors>) -> Result<Node, Errors> {
+            let opener = self.peek().span();
+            parse(self).map_err(|error| match self.peek().kind {
+                TokenKind::EndOfFile => error
+                    .label(opener, &format!("this `{keyword}` is never closed with `end`"))
+                    .code(codes::UNCLOSED_BLOCK),
+                _ => error,
+            })
+        }
+
+        fn statement(&mut self) -> Result<Node, Errors> {
+            match self.peek().kind.clone() {
+                TokenKind::Out => {
+                    let line = self.next().line;
+                    let value = Box::new(self.expression()?);
+                    Ok(Node::Out(OutNode { value, line }))
+                },
+                TokenKind::Const => {
+                    self.next();
+                    let target = self.peek().clone();
+                    let name = match target.kind.clone() {
+                        TokenKind::Identifier(name) => name,
+                        _ => return Err(self.unexpected("a name after `const`")),
+                    };
+                    self.next();
+                    self.expect(TokenKind::Equal, "`=`")?;
+                    let value = Box::new(self.expression()?);
+                    Ok(Node::Assign(AssignNode { name, value, constant: true, span: target.span() }))
+                },
+                TokenKind::For => self.opened("for", Self::for_loop),
+                TokenKind::If => self.opened("if", Self::if_statement),
+                TokenKind::Match => self.opened("match", Self::match_statement),
+                TokenKind::Enum => self.opened("enum", Self::enum_declaration),
+                TokenKind::Try => self.opened("try", Self::try_statement),
+                TokenKind::Include => self.opened("include", Self::include),
+                TokenKind::Raise => {
+                    let line = self.next().line;
+                    let value = Box::new(self.expression()?);
+                    Ok(Node::Raise(RaiseNode { value, line }))
+                },
+                TokenKind::Func if matches!(self.peek_next(), TokenKind::Identifier(_)) => self.opened("func", Self::function),
+                TokenKind::Return => {
+                    let line = self.next().line;
+                    if self.functions == 0 {
+                        return Err(Errors::SyntaxError(line.into(), "`return` can only be used inside a `func`".to_string()));
+                    }
+
+                    // A bare `return` gives back nothing
+                    let value = match self.peek().kind {
+                        TokenKind::Newline | TokenKind::Semicolon | TokenKind::End | TokenKind::EndOfFile => None,
+                        _ => Some(Box::new(self.expression()?)),
+                    };
+                    Ok(Node::Return(ReturnNode { value, line }))
+                },
+                TokenKind::LPar if self.starts_pattern() => self.destructure(),
+                TokenKind::Identifier(name) => {
+                    let op = match self.peek_next() {
+                        TokenKind::Equal => None,
+                        TokenKind::PlusEqual => Some(BinaryOp::Plus),
+                        TokenKind::MinusEqual => Some(BinaryOp::Minus),
+                        TokenKind::Comma => return self.destructure(),
+                        _ => return self.expression(),
+                    };
+
+                    let target = self.next();
+                    let line = target.line;
+                    self.next();
+                    let mut value = self.expression()?;
+
+                    // `x += y` is the same as `x = x + y`
+                    if let Some(op) = op {
+                        let left = Box::new(Node::Identifier(IdentifierNode { name: name.clone(), span: target.span() }));
+                        value = Node::Binary(BinaryNode { left, right: Box::new(value), op, line });
+                    }
+
+                    Ok(Node::Assign(AssignNode { name, value: Box::new(value), constant: false, span: target.span() }))
+                },
+                _ => self.expression(),
+            }
+        }
+
+        pub fn expression(&mut self) -> Result<Node, Errors> {
+            self.or()
+        }
+
+        fn or(&mut self) -> Result<Node, Errors> {
+            let mut left = self.and()?;
+
+            while self.peek().kind == TokenKind::Or {
+                let line = self.next().line;
+                let right = self.and()?;
+                left = Node::Logical(LogicalNode { left: Box::new(left), right: Box::new(right), op: LogicalOp::Or, line });
+            }
+
+            Ok(left)
+        }
+
+        fn and(&mut self) -> Result<Node, Errors> {
+            let mut left = self.not()?;
+
+            while self.peek().kind == TokenKind::And {
+                let line = self.next().line;
+                let right = self.not()?;
+                left = Node::Logical(LogicalNode { left: Box::new(left), right: Box::new(right), op: LogicalOp::And, line });
+            }
+
+            Ok(left)
+        }
+
+        // `not` binds looser than comparisons so `not x == 5` reads as `not (x == 5)`
+        fn not(&mut self) -> Result<Node, Errors> {
+            if self.peek().kind == TokenKind::Bang {
+                let line = self.next().line;
+                let right = Box::new(self.not()?);
+                return Ok(Node::Unary(UnaryNode { right, op: UnaryOp::Not, line }));
+            }
+
+            self.comparison()
+        }
+
+        // Comparisons don't chain, `1 < x < 5` is a SyntaxError
+        fn comparison(&mut self) -> Result<Node, Errors> {
+            let left = self.additive()?;
+
+            let op = match self.peek().kind {
+                TokenKind::EqualEqual => CompareOp::Equal,
+                TokenKind::BangEqual => CompareOp::NotEqual,
+                TokenKind::LessThan => CompareOp::Less,
+                TokenKind::LessEqual => CompareOp::LessEqual,
+                TokenKind::MoreThan => CompareOp::More,
+                TokenKind::MoreEqual => CompareOp::MoreEqual,
+                _ => return Ok(left),
+            };
+            let line = self.next().line;
+            let right = self.additive()?;
+
+            if matches!(self.peek().kind, TokenKind::EqualEqual | TokenKind::BangEqual | TokenKind::LessThan
+                | TokenKind::LessEqual | TokenKind::MoreThan | TokenKind::MoreEqual)
+            {
+                return Err(Errors::SyntaxError(line.into(), "comparisons can't be chained, join them with `and` instead".to_string()));
+            }
+
+            Ok(Node::Compare(CompareNode { left: Box::new(left), right: Box::new(right), op, line }))
+        }
+
+        fn additive(&mut self) -> Result<Node, Errors> {
+            let mut left = self.multiplicative()?;
+
+            loop {
+                let op = match self.peek().kind {
+                    TokenKind::Plus => BinaryOp::Plus,
+                    TokenKind::Minus => BinaryOp::Minus,
+                    _ => return Ok(left),
+                };
+                let line = self.next().line;
+                let right = self.multiplicative()?;
+                left = Node::Binary(BinaryNode { left: Box::new(left), right: Box::new(right), op, line });
+            }
+        }
+
+        fn multiplicative(&mut self) -> Result<Node, Errors> {
+            let mut left = self.unary()?;
+
+            loop {
+                let op = match self.peek().kind {
+                    TokenKind::Star => BinaryOp::Multiply,
+                    TokenKind::Slash => BinaryOp::Divide,
+                    _ => return Ok(left),
+                };
+                let line = self.next().line;
+                let right = self.unary()?;
+                left = Node::Binary(BinaryNode { left: Box::new(left), right: Box::new(right), op, line });
+            }
+        }
+
+        fn unary(&mut self) -> Result<Node, Errors> {
+            let op = match self.peek().kind {
+                TokenKind::Minus => UnaryOp::Negative,
+                TokenKind::Ampersand => UnaryOp::Field,
+                _ => return self.postfix(),
+            };
+
+            let line = self.next().line;
+            let right = Box::new(self.unary()?);
+            Ok(Node::Unary(UnaryNode { right, op, line }))
+        }
+
+        // Enums and variants are capitalised, which keeps `Option:Some` apart from slices like `s[a:b]`
+        fn is_variant(&self, name: &str) -> bool {
+            let next = &self.tokens[(self.idx + 1).min(self.tokens.len() - 1)].kind;
+            name.starts_with(char::is_uppercase)
+                && self.peek().kind == TokenKind::Colon
+                && matches!(next, TokenKind::Identifier(v) if v.starts_with(char::is_uppercase))
+        }
+
+        // Parses the rest of `Enum:Variant` or `Enum:Variant(field)`
+        fn variant(&mut self, enum_name: String, line: usize) -> Result<Node, Errors> {
+            self.next();
+            let name = match self.next().kind {
+                TokenKind::Identifier(name) => name,
+                _ => unreachable!(),
+            };
+
+            let mut field = None;
+            if self.check(TokenKind::LPar) {
+                let mut args = self.arguments(TokenKind::RPar, "`)`")?;
+                if args.len() != 1 {
+                    return Err(Errors::SyntaxError(line.into(), format!(
+                        "a variant has a single field, but `{enum_name}:{name}` was given {}",
+                        args.len(),
+                    )));
+                }
+                field = Some(Box::new(args.remove(0)));
+            }
+
+            Ok(Node::Variant(VariantNode { enum_name, name, field, line }))
+        }
+
+        // Parses indexing, method calls and function calls after a primary expression
+        fn postfix(&mut self) -> Result<Node, Errors> {
+            let mut node = self.primary()?;
+
+            loop {
+                match self.peek().kind {
+                    TokenKind::LBrac => {
+                        let line = self.next().line;
+                        node = self.index(node, line)?;
+                    },
+                    TokenKind::Dot => {
+                        let line = self.next().line;
+                        let name = match self.next().kind {
+                            TokenKind::Identifier(name) => name,
+                            _ => return Err(Errors::SyntaxError(line.into(), "expected a method name after `.`".to_string())),
+                        };
+                        let open = self.expect(TokenKind::LPar, "`(`")?.span();
+                        let args = self.arguments(TokenKind::RPar, "`)`")?;
+                        let args_span = open.to(self.previous().span());
+                        node = Node::Method(MethodNode { target: Box::new(node), name, args, line, args_span });
+                    },
+                    TokenKind::LPar => {
+                        let open = self.next();
+                        let args = self.call_arguments()?;
+                        let args_span = open.span().to(self.previous().span());
+                        node = Node::Call(CallNode { callee: Box::new(node), args, line: open.line, args_span });
+                    },
+                    _ => return Ok(node),
+                }
+            }
+        }
+
+        // Parses what follows the `[` of `target[i]`, `target[i, j]` or `target[start:end]`
+        fn index(&mut self, target: Node, line: usize) -> Result<Node, Errors> {
+            let target = Box::new(target);
+            self.skip_newlines();
+
+            let start = match self.peek().kind {
+                TokenKind::Colon => None,
+                _ => Some(Box::new(self.expression()?)),
+            };
+            self.skip_newlines();
+
+            // A colon makes it a slice
+            if self.check(TokenKind::Colon) {
+                self.skip_newlines();
+                let end = match self.peek().kind {
+                    TokenKind::RBrac => None,
+                    _ => Some(Box::new(self.expression()?)),
+                };
+                self.skip_newlines();
+                self.expect(TokenKind::RBrac, "`]`")?;
+                return Ok(Node::Slice(SliceNode { target, start, end, line }));
+            }
+
+            let mut indices = vec![*start.unwrap()];
+            if self.check(TokenKind::Comma) {
+                indices.extend(self.arguments(TokenKind::RBrac, "`]`")?);
+            } else {
+                self.expect(TokenKind::RBrac, "`]`")?;
+            }
+
+            Ok(Node::Index(IndexNode { target, indices, line }))
+        }
+
+        /*
+            Splits a string literal into text and `{expression:spec}` parts.
+            `{{` and `}}` are literal braces. A string without any parts
+            stays a plain literal.
+        */
+        fn string(&mut self, text: String, span: Span) -> Result<Node, Errors> {
+            let line = span.line;
+            if !text.contains('{') && !text.contains('}') {
+                return Ok(Node::Literal(LiteralNode { literal: Literal::String(text) }));
+            }
+
+            let mut parts = Vec::new();
+            let mut buffer = String::new();
+            let mut chars = text.chars().peekable();
+
+            while let Some(c) = chars.next() {
+                match c {
+                    '{' if chars.peek() == Some(&'{') => {
+                        chars.next();
+                        buffer.push('{');
+                    },
+                    '}' if chars.peek() == Some(&'}') => {
+                        chars.next();
+                        buffer.push('}');
+                    },
+                    '}' => return Err(Errors::SyntaxError(span, "unmatched `}` in string, write `}}` for a literal brace".to_string())),
+                    '{' => {
+                        // Take everything up to the matching `}`
+                        let mut inner = String::new();
+                        let mut depth = 0;
+                        let mut spec = None;
+                        'inner: loop {
+                            match chars.next() {
+                                None => return Err(Errors::SyntaxError(span, "unclosed `{` in string, write `{{` for a literal brace".to_string())),
+                                Some('}') if depth == 0 => break 'inner,
+                                Some(':') if depth == 0 => {
+                                    spec = Some(inner.len());
+                                    inner.push(':');
+                                },
+                                Some(c @ ('(' | '[' | '{')) => {
+                                    depth += 1;
+                                    inner.push(c);
+                                    continue 'inner;
+                                },
+                                Some(c @ (')' | ']' | '}')) => {
+                                    depth -= 1;
+                                    inner.push(c);
+                                    continue 'inner;
+                                },
+                                Some(c) => inner.push(c),
+                            }
+                        }
+
+                        // Only the last top level colon starts the spec
+                        let (source, spec) = match spec {
+                            Some(at) => {
+                                // Errors point at the spec itself when the literal is written as is, without escapes
+                                let end = text.chars().count() - chars.clone().count() - 1;
+                                let start = end - inner[at..].chars().count();
+                                let spec_span = if span.line == span.end_line && span.end_col - span.col == text.chars().count() + 2 {
+                                    Span::new(line, span.col + 1 + start, line, span.col + 1 + end)
+                                } else {
+                                    span
+                                };
+                                (&inner[..at], Self::format_spec(&inner[at..], spec_span)?)
+                            },
+                            None => (&inner[..], FormatSpec::default()),
+                        };
+
+                        if !buffer.is_empty() {
+                            parts.push(Part::Text(std::mem::take(&mut buffer)));
+                        }
+                        parts.push(Part::Value(Self::interpolated(source, span)?, spec));
+                    },
+                    c => buffer.push(c),
+                }
+            }
+
+            if !buffer.is_empty() {
+                parts.push(Part::Text(buffer));
+            }
+            Ok(Node::Interpolation(InterpolationNode { parts, line }))
+        }
+
+        // Lexes and parses the expression inside `{...}` of a string
+        // Everything inside is reported against the span of the whole string
+        fn interpolated(source: &str, span: Span) -> Result<Node, Errors> {
+            if source.trim().is_empty() {
+                return Err(Errors::SyntaxError(span, "empty `{}` in string, write `{{}}` for literal braces".to_string()));
+            }
+
+            let source = SourceMap::new(source.to_string());
+            let mut lexer = Lexer::new(&source);
+            let (tokens, errors) = lexer.scan();
+            if let Some(error) = errors.first() {
+                return Err(Errors::SyntaxError(span, error.diagnostic().message));
+            }
+
+            let tokens = tokens.iter().cloned().map(|mut token| {
+                token.line = span.line;
+                token.col = span.col;
+                token.len = span.end_col - span.col;
+                token
+            }).collect();
+
+            // The whole of the braces must be one expression
+            let mut ast = Ast::new(tokens);
+            let node = ast.expression()?;
+            if ast.peek().kind != TokenKind::EndOfFile {
+                return Err(ast.unexpected("`}`"));
+            }
+            Ok(node)
+        }
+
+        // Parses a format spec such as `.2`, `>8` or `^10.3`
+        fn format_spec(spec: &str, span: Span) -> Result<FormatSpec, Errors> {
+            let invalid = || Errors::SyntaxError(span, format!(
+                "`{spec}` is not a valid format, expected something like `:.2`, `:>8` or `:^10.3`",
+            ));
+            let mut rest = &spec[1..];
+            let mut format = FormatSpec::default();
+
+            if let Some(c @ ('<' | '>' | '^')) = rest.chars().next() {
+                format.align = Some(c);
+                rest = &rest[1..];
+            }
+
+            let (width, precision) = match rest.split_once('.') {
+                Some((width, precision)) => (width, Some(precision)),
+                None => (rest, None),
+            };
+
+            // Anything past MAX_FORMAT is a typo, and would make strings too large to allocate
+            let bounded = |digits: &str, what: &str| -> Result<usize, Errors> {
+                let n: usize = digits.parse().map_err(|_| invalid())?;
+                if n > MAX_FORMAT {
+                    return Err(Errors::SyntaxError(span, format!("`{spec}` has a {what} of {n}, it can be at most {MAX_FORMAT}")));
+                }
+                Ok(n)
+            };
+
+            if !width.is_empty() {
+                format.width = bounded(width, "width")?;
+            }
+            if let Some(precision) = precision {
+                format.precision = Some(bounded(precision, "precision")?);
+            }
+
+            Ok(format)
+        }
+
+        // Parses a comma separated list of expressions up to the closing token
+        // Newlines are allowed anywhere inside the brackets
+        fn arguments(&mut self, close: TokenKind, what: &str) -> Result<Vec<Node>, Errors> {
+            let mut args = Vec::new();
+
+            loop {
+                self.skip_newlines();
+                if self.check(close.clone()) {
+                    return Ok(args);
+                }
+
+                args.push(self.expression()?);
+                self.skip_newlines();
+
+                if !self.check(TokenKind::Comma) {
+                    self.expect(close, what)?;
+                    return Ok(args);
+                }
+            }
+        }
+
+        // Parses the arguments of a function call up to the closing `)`
+        // Each argument may be labelled with `label as value`
+        fn call_arguments(&mut self) -> Result<Vec<ArgumentNode>, Errors> {
+            let mut args = Vec::new();
+
+            loop {
+                self.skip_newlines();
+                if self.check(TokenKind::RPar) {
+                    return Ok(args);
+                }
+
+                let label = match (self.peek().kind.clone(), self.peek_next()) {
+                    (TokenKind::Identifier(label), TokenKind::As) => {
+                        self.next();
+                        self.next();
+                        Some(label)
+                    },
+                    _ => None,
+                };
+                args.push(ArgumentNode { label, value: self.expression()? });
+                self.skip_newlines();
+
+                if !self.check(TokenKind::Comma) {
+                    self.expect(TokenKind::RPar, "`)`")?;
+                    return Ok(args);
+                }
+            }
+        }
+
+        fn primary(&mut self) -> Result<Node, Errors> {
+            let token = self.next();
+            let span = token.span();
+
             match token.kind {
                 // Float and integer literals
                 TokenKind::NumberLiteral(lexeme) => {
                     // Check if token is a float
-                    if lexeme.contains(".") {
-                        let parsed = lexeme.parse::<f64>();
-                        match parsed {
-                            Ok(_) => {}
-                            Err(_) => {
-                                panic!("Error parsing number literal lexeme to float64");
-                                // TODO: Error handle here
-                            }
+                    let literal = if lexeme.contains('.') {
+                        match lexeme.parse::<f64>() {
+                            Ok(f) => Literal::Float(f),
+                            Err(_) => return Err(Errors::SyntaxError(token.line.into(), format!("`{lexeme}` is not a valid number"))),
                         }
-
-                        let literal = Literal::Float(parsed.unwrap());
-                        let literal_node = LiteralNode { literal };
-                        
-                        Node::Literal(literal_node)
                     } else {
                         // If token is an integer
-                        let parsed = lexeme.parse::<i32>();
-                        match parsed {
-                            Ok(_) => {},
-                            Err(_) => {
-                                panic!("Error parsing number literal lexeme to signed int32")
-                                // TOOD: Error handle here
-                            }
+                        match lexeme.parse::<i32>() {
+                            Ok(i) => Literal::Interger(i),
+                            Err(_) => return Err(Errors::SyntaxError(token.line.into(), format!("`{lexeme}` is too large for an Int"))),
                         }
+                    };
 
-                        let literal = Literal::Interger(parsed.unwrap());
-                        let literal_node = LiteralNode { literal };
-                        
-                        Node::Literal(literal_node)
-                    }
+                    Ok(Node::Literal(LiteralNode { literal }))
                 },
-                TokenKind::Plus => {
-                    // Get the left node
-                    let left: Box<Node>;
-                    if let Some(last_node) = self.nodes.last() {
-                        let n = last_node.clone();
-                        left = Box::new(n);
-                    } else {
-                        panic!("Error determining last token in nodes!")
-                        // TODO: Error handle here
+                TokenKind::True => Ok(Node::Literal(LiteralNode { literal: Literal::Bool(true) })),
+                TokenKind::False => Ok(Node::Literal(LiteralNode { literal: Literal::Bool(false) })),
+                TokenKind::StringLiteral(s) => self.string(s, span),
+                TokenKind::Identifier(name) if self.is_variant(&name) => self.variant(name, token.line),
+                TokenKind::Identifier(name) => {
+                    Ok(Node::Identifier(IdentifierNode { name, span }))
+                },
+                TokenKind::Nil => Ok(Node::Literal(LiteralNode { literal: Literal::Nil })),
+                TokenKind::LPar => {
+                    // `()` is the empty tuple
+                    self.skip_newlines();
+                    if self.check(TokenKind::RPar) {
+                        return Ok(Node::Tuple(TupleNode { items: Vec::new() }));
                     }
 
-                    // Get the right node
-                    let right: Box<Node>;
-                    let next_token = self.tokens[self.idx + 1].clone();
-                    let right_node = self.match_token(&next_token, self.idx);
-                    let n = right_node.clone();
-                    right = Box::new(n);
+                    let node = self.expression()?;
+                    self.skip_newlines();
+                    if self.check(TokenKind::RPar) {
+                        return Ok(node);
+                    }
 
-                    // Construct the binary node
-                    let op = BinaryOp::Plus;
-                    let binary = BinaryNode { left, right, op, };
-                    
-                    Node::Binary(binary)
+                    // A comma makes it a tuple, `(5,)` is a tuple of one
+                    self.expect(TokenKind::Comma, "`,` or `)`")?;
+                    let mut items = vec![node];
+                    items.extend(self.arguments(TokenKind::RPar, "`)`")?);
+                    Ok(Node::Tuple(TupleNode { items }))
+                },
+                TokenKind::LBrac => {
+                    let items = self.arguments(TokenKind::RBrac, "`]`")?;
+                    Ok(Node::List(ListNode { items }))
+                },
+                TokenKind::LCurl => self.map(token.line),
+                TokenKind::Matrix => self.matrix(span),
+                TokenKind::Func => {
+                    self.idx -= 1;
+                    self.function()
+                },
+                _ => {
+                    // Step back so the error points at the offending token
+                    if token.kind != TokenKind::EndOfFile {
+                        self.idx -= 1;
+                    }
+                    Err(self.unexpected("an expression"))
                 },
-                _ => Node::Ignore
             }
         }
 
-        // Create a new AST struct with the tokens and empty node vec
-        pub fn new(tokens: Vec<Token>) -> Self {
-            Self {
-                idx: 0 as usize,
-                tokens,
-                nodes: Vec::new(),
+        /*
+            Map literals look like:
+
+            settings = {
+                theme: "default",
+                "title": "A Graph",
+                (0, 1): 5
             }
-        }
 
-        // Take the tokens from AST.Tokens and construct the AST
-        pub fn build(&mut self) {
-            // Iterate over every token
-            let len = self.tokens.len();
+            A bare name before the colon is a Str key, anything else is evaluated.
+            Entries are separated by commas, newlines or both.
+        */
+        fn map(&mut self, line: usize) -> Result<Node, Errors> {
+            let mut entries = Vec::new();
+
+            loop {
+                self.skip_newlines();
+                if self.check(TokenKind::RCurl) {
+                    break;
+                }
+
+                let key = match (self.peek().kind.clone(), self.peek_next()) {
+                    (TokenKind::Identifier(name), TokenKind::Colon) => {
+                        self.next();
+                        Node::Literal(LiteralNode { literal: Literal::String(name) })
+                    },
+                    _ => self.expression()?,
+                };
+                self.expect(TokenKind::Colon, "`:` after the map key")?;
+                self.skip_newlines();
+                let value = self.expression()?;
+                entries.push((key, value));
+
+                // Entries end with a comma, a newline or the closing brace
+                match self.peek().kind {
+                    TokenKind::Comma | TokenKind::Newline => {
+                        self.next();
+                    },
+                    TokenKind::RCurl => {},
+                    _ => return Err(self.unexpected("`,` or `}`")),
+                }
+            }
 
-            let mut nodes = Vec::<Node>::new();
+            Ok(Node::Map(MapNode { entries, line }))
+        }
 
-            'tokens: loop {
-                while self.idx <= len {
-                    let t = &self.tokens[self.idx];
-                    let node = self.match_token(t, self.idx);
+        /*
+            Matrix blocks look like:
 
-                    // Push node to nodes
-                    nodes.push(node);
+            matrix
+                [5, 10, 0]
+                [6, 20, 0]
+            end
+        */
+        fn matrix(&mut self, opener: Span) -> Result<Node, Errors> {
+            let line = opener.line;
+            let mut rows = Vec::new();
 
-                    // Continue to next token
-                    self.idx += 1;
-                    continue 'tokens;
+            loop {
+                self.skip_newlines();
+                match self.peek().kind {
+                    TokenKind::End => {
+                        self.next();
+                        break;
+                    },
+                    TokenKind::LBrac => {
+                        self.next();
+                        rows.push(self.arguments(TokenKind::RBrac, "`]`")?);
+                    },
+                    TokenKind::EndOfFile => {
+                        return Err(self.unexpected("`end`")
+                            .label(opener, "this `matrix` is never closed with `end`")
+                            .code(codes::UNCLOSED_BLOCK));
+                    },
+                    _ => return Err(self.unexpected("a row like `[1, 2, 3]` or `end`")),
                 }
+            }
+
+            Ok(Node::Matrix(MatrixNode { rows, line }))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::Ast;
+        use crate::{codes::codes, error::errors::Errors, lexer::lexer::Lexer, source::source::SourceMap};
 
-                println!("Reached the end of the tokens list");
-                break 'tokens;
+        fn build(text: &str) -> Result<(), Vec<Errors>> {
+            let source = SourceMap::new(text.to_string());
+            let mut lexer = Lexer::new(&source);
+            let (tokens, _) = lexer.scan();
+            Ast::new(tokens.clone()).build().map(|_| ())
+        }
+
+        // The line and code of every syntax error in the text
+        fn errors(text: &str) -> Vec<(usize, &'static str)> {
+            match build(text) {
+                Ok(_) => Vec::new(),
+                Err(errors) => errors.iter().map(|e| (e.diagnostic().span.line, e.error_code())).collect(),
             }
         }
 
-        fn binary(left: Node, right: Node, op: BinaryOp) -> Node {
-            let node = BinaryNode {
-                left: Box::new(left),
-                right: Box::new(right),
-                op,
-            };
+        #[test]
+        fn parsing_carries_on_after_an_error() {
+            assert_eq!(
+                errors("x = *\nout )\ny = 2\nif y\nout (y\nend\nend\n"),
+                [(1, codes::SYNTAX), (2, codes::SYNTAX), (6, codes::SYNTAX), (7, codes::UNBALANCED_BLOCK)],
+            );
+        }
 
-            Node::Binary(node)
+        #[test]
+        fn unclosed_blocks_label_their_opener() {
+            let errors = build("func f\nif x\nout x\nend\n").unwrap_err();
+            let diagnostic = errors[0].diagnostic();
+            assert_eq!((errors.len(), diagnostic.code.as_deref()), (1, Some(codes::UNCLOSED_BLOCK)));
+            assert_eq!(diagnostic.labels.iter().map(|l| l.span.line).collect::<Vec<_>>(), [1]);
+        }
+
+        #[test]
+        fn format_specs_are_bounded() {
+            let errors = build("out \"a {x:.1001}\"").unwrap_err();
+            let diagnostic = errors[0].diagnostic();
+            assert_eq!(diagnostic.message, "`:.1001` has a precision of 1001, it can be at most 1000");
+            assert_eq!((diagnostic.span.col, diagnostic.span.end_col), (10, 16));
+            assert!(build("out \"{x:>99999999999999}\"").is_err());
+            assert!(build("out \"{x:>1000.1000}\"").is_ok());
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,444 @@
+pub mod cli {
+    use crate::{diagnostic::diagnostic::Diagnostic, lint::lint::{Level, Levels}, source::source::Sources};
+
+    /*
+        What the process exits with, so a script or CI job can tell how a run went.
+        A program can pick its own code with `sys.exit(code)`.
+
+        0   the program ran (or was checked) without errors
+        1   an error stopped the program while it was running, or `fmt --check` found a file to format
+        2   the command line was wrong
+        3   the program couldn't be read, had a syntax error or broke a denied lint, so it never ran
+    */
+    pub const SUCCESS: i32 = 0;
+    pub const RUNTIME_ERROR: i32 = 1;
+    pub const UNFORMATTED: i32 = 1;
+    pub const USAGE_ERROR: i32 = 2;
+    pub const COMPILE_ERROR: i32 = 3;
+
+    // How diagnostics are written to stderr
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ErrorFormat {
+        Human,
+        Json,
+    }
+
+    // How `darcy lex` and `darcy parse` print what they read
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum DumpFormat {
+        // One token per line with its span, the default for `lex`
+        Compact,
+        // An indented S-expression, the default for `parse`
+        Sexpr,
+        Json,
+        // A Graphviz graph of the tree
+        Dot,
+        // Rust's own `{:#?}`, every field of every token or node
+        Debug,
+    }
+
+    // Where the program is read from
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Input {
+        File(String),
+        // `-` in place of a file
+        Stdin,
+        // The code after `-e`
+        Inline(String),
+    }
+
+    impl Input {
+        // What the program is called in messages
+        pub fn name(&self) -> &str {
+            match self {
+                Input::File(path) => path,
+                Input::Stdin => "<stdin>",
+                Input::Inline(_) => "<-e>",
+            }
+        }
+    }
+
+    // What to do with a program, and how
+    #[derive(Debug, Clone)]
+    pub struct Options {
+        pub input: Input,
+        pub format: ErrorFormat,
+        pub levels: Levels,
+        // Only set for `lex` and `parse`
+        pub dump: Option<DumpFormat>,
+        // What comes after the program on the command line, for `sys.args()`
+        pub args: Vec<String>,
+        // Run the program again whenever one of its files changes
+        pub watch: bool,
+        // Only say whether `darcy fmt` would change the file
+        pub check: bool,
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum Command {
+        Run(Options),
+        Check(Options),
+        Lex(Options),
+        Parse(Options),
+        Fmt(Options),
+        Repl(ErrorFormat),
+        Explain(Option<String>),
+        Help(Option<String>),
+        Version,
+    }
+
+    // Writes each diagnostic to stderr in the format asked for
+    // JSON output is one object per line and nothing else, so tools can read stderr as it is
+    pub fn emit(diagnostics: &[Diagnostic], sources: &Sources, format: ErrorFormat) {
+        for diagnostic in diagnostics {
+            // Something in an included file says how that file came to be included
+            let mut diagnostic = diagnostic.clone();
+            for note in sources.included_from(diagnostic.span.line) {
+                diagnostic = diagnostic.with_note(&note);
+            }
+
+            match format {
+                ErrorFormat::Json => eprintln!("{}", diagnostic.to_json(sources)),
+                ErrorFormat::Human => eprintln!("{}", diagnostic.render(sources)),
+            }
+        }
+    }
+
+    // The commands that take a file, and whether the lint flags mean anything to them
+    const FILE_COMMANDS: &[(&str, bool)] = &[("run", true), ("check", true), ("lex", false), ("parse", false), ("fmt", false)];
+
+    // The formats `--format=` takes for each command that dumps, the first being the default
+    const DUMP_FORMATS: &[(&str, &[(&str, DumpFormat)])] = &[
+        ("lex", &[("compact", DumpFormat::Compact), ("json", DumpFormat::Json), ("debug", DumpFormat::Debug)]),
+        ("parse", &[
+            ("sexpr", DumpFormat::Sexpr), ("json", DumpFormat::Json), ("dot", DumpFormat::Dot), ("debug", DumpFormat::Debug),
+        ]),
+    ];
+
+    /*
+        Reads the arguments after the program name. Flags can go before or after the file,
+        in any order, a file on its own is run and nothing at all starts the REPL.
+        Anything else after the file that is run is passed to it, everything after `--` is:
+
+        darcy run main.dy --deny=warnings --error-format=json
+        darcy main.dy data.csv -- --verbose
+        cat main.dy | darcy check -
+        darcy -e 'out 2 * 3'
+        darcy fmt --check main.dy
+        darcy help check
+    */
+    pub fn parse(args: &[String]) -> Result<Command, String> {
+        let Some(first) = args.first() else {
+            return Ok(Command::Repl(ErrorFormat::Human));
+        };
+
+        let (command, rest) = match first.as_str() {
+            "-h" | "--help" | "help" => return Ok(Command::Help(args.get(1).cloned())),
+            "-V" | "--version" => return Ok(Command::Version),
+            "explain" | "--explain" => return Ok(Command::Explain(args.get(1).cloned())),
+            "repl" => return repl(&args[1..]),
+            name if FILE_COMMANDS.iter().any(|(c, _)| *c == name) => (name, &args[1..]),
+            name if name.starts_with('-') => ("run", args),
+            name if name.ends_with(".dy") || std::path::Path::new(name).is_file() => ("run", args),
+            name => return Err(format!("unknown command `{name}`")),
+        };
+        let lints = FILE_COMMANDS.iter().any(|(c, lints)| *c == command && *lints);
+        let dumps = DUMP_FORMATS.iter().find(|(c, _)| *c == command).map(|(_, formats)| *formats);
+
+        let mut input = None;
+        let mut format = ErrorFormat::Human;
+        let mut levels = Levels::default();
+        let mut dump = dumps.map(|formats| formats[0].1);
+        let mut program_args = Vec::new();
+        let mut watch = false;
+        let mut check = false;
+
+        let mut rest = rest.iter();
+        while let Some(arg) = rest.next() {
+            if arg == "--" && command == "run" && input.is_some() {
+                program_args.extend(rest.by_ref().cloned());
+                break;
+            }
+
+            if arg == "-h" || arg == "--help" {
+                return Ok(Command::Help(Some(command.to_string())));
+            }
+
+            if let Some(value) = arg.strip_prefix("--error-format=") {
+                format = match value {
+                    "human" => ErrorFormat::Human,
+                    "json" => ErrorFormat::Json,
+                    other => return Err(format!("unknown error format `{other}`, expected `human` or `json`")),
+                };
+                continue;
+            }
+
+            if arg == "--watch" {
+                if command != "run" {
+                    return Err("`--watch` only applies to `darcy run`".to_string());
+                }
+                watch = true;
+                continue;
+            }
+
+            if arg == "--check" {
+                if command != "fmt" {
+                    return Err("`--check` only applies to `darcy fmt`, `darcy check` checks a program".to_string());
+                }
+                check = true;
+                continue;
+            }
+
+            if let Some(value) = arg.strip_prefix("--format=") {
+                let Some(formats) = dumps else {
+                    return Err("`--format` only applies to `darcy lex` and `darcy parse`".to_string());
+                };
+                let Some((_, chosen)) = formats.iter().find(|(name, _)| *name == value) else {
+                    let names: Vec<String> = formats.iter().map(|(name, _)| format!("`{name}`")).collect();
+                    return Err(format!("unknown format `{value}` for `darcy {command}`, expected one of {}", names.join(", ")));
+                };
+                dump = Some(*chosen);
+                continue;
+            }
+
+            // `--allow=name`, `--warn=name` or `--deny=name` sets the level of a lint
+            let level = [Level::Allow, Level::Warn, Level::Deny].into_iter()
+                .find_map(|level| Some((level, arg.strip_prefix(&format!("--{}=", level.name()))?)));
+            if let Some((level, name)) = level {
+                if !lints {
+                    return Err(format!("`--{}` only applies to `darcy run` and `darcy check`", level.name()));
+                }
+                levels.set(name, level)?;
+                continue;
+            }
+
+            // `-e code` runs the code itself, `-` reads the program from stdin
+            let given = match arg.as_str() {
+                "-e" => match rest.next() {
+                    Some(code) => Input::Inline(code.clone()),
+                    None => return Err("`-e` needs some code after it, e.g. `darcy -e 'out 2 * 3'`".to_string()),
+                },
+                "-" => Input::Stdin,
+                flag if flag.starts_with('-') => return Err(format!("unknown flag `{flag}` for `darcy {command}`")),
+                _ if command == "run" && input.is_some() => {
+                    program_args.push(arg.clone());
+                    continue;
+                },
+                path => Input::File(path.to_string()),
+            };
+            if let Some(first) = &input {
+                return Err(format!("`darcy {command}` takes one program, found `{}` and `{}`", describe(first), describe(&given)));
+            }
+            input = Some(given);
+        }
+
+        let Some(input) = input else {
+            return Err(format!("`darcy {command}` needs a file, e.g. `darcy {command} main.dy`, `-` for stdin or `-e <code>`"));
+        };
+        if watch && !matches!(input, Input::File(_)) {
+            return Err("`--watch` needs a file to watch, not `-` or `-e`".to_string());
+        }
+        let options = Options { input, format, levels, dump, args: program_args, watch, check };
+
+        Ok(match command {
+            "run" => Command::Run(options),
+            "check" => Command::Check(options),
+            "lex" => Command::Lex(options),
+            "parse" => Command::Parse(options),
+            _ => Command::Fmt(options),
+        })
+    }
+
+    // How an input was given on the command line, for a message about having two of them
+    fn describe(input: &Input) -> String {
+        match input {
+            Input::Inline(_) => "-e".to_string(),
+            Input::Stdin => "-".to_string(),
+            Input::File(path) => path.clone(),
+        }
+    }
+
+    // `darcy repl` only takes the error format
+    fn repl(args: &[String]) -> Result<Command, String> {
+        let mut format = ErrorFormat::Human;
+        for arg in args {
+            format = match arg.as_str() {
+                "-h" | "--help" => return Ok(Command::Help(Some("repl".to_string()))),
+                "--error-format=human" => ErrorFormat::Human,
+                "--error-format=json" => ErrorFormat::Json,
+                other => return Err(format!("unknown argument `{other}` for `darcy repl`")),
+            };
+        }
+        Ok(Command::Repl(format))
+    }
+
+    const USAGE: &str = "\
+darcy, the DarcyLang interpreter
+
+Usage: darcy <command> [options] <file>
+       darcy <file> [options] [args]   runs the file, passing it the args
+       darcy <command> [options] -     reads the program from stdin
+       darcy -e <code> [options]       runs the code, any command takes `-e <code>` in place of a file
+       darcy                           starts the REPL
+
+Commands:
+    run       Run a program
+    check     Report errors and warnings without running the program
+    lex       Print the tokens of a file, one per line
+    parse     Print the syntax tree of a file as an S-expression, JSON or a Graphviz graph
+    fmt       Rewrite a file with the standard indentation and spacing
+    repl      Type in code and see what it does, line by line
+    explain   Explain an error code, e.g. `darcy explain D0402`
+    help      Print this, or more about a command, e.g. `darcy help run`
+
+Options:
+    --error-format=human|json     How errors are written to stderr (human by default)
+    --allow=<lint>                Turn a lint off, `warnings` stands for every lint
+    --warn=<lint>                 Report a lint as a warning
+    --deny=<lint>                 Report a lint as an error, so the program doesn't run
+    --format=<format>             How `lex` and `parse` print, see `darcy help lex`
+    --watch                       Run the program again whenever it (or a file it includes) changes
+    --check                       Have `fmt` report whether a file needs formatting, without changing it
+    -h, --help                    Print help
+    -V, --version                 Print the version
+
+Exit codes:
+    0    success
+    1    an error while the program was running, or `fmt --check` found a file that needs formatting
+    2    a mistake on the command line
+    3    the program couldn't be read, or has errors that stop it from running
+    A program can exit with a code of its own with `sys.exit(code)`.
+";
+
+    const INPUT: &str = concat!(
+        "    -                             Read the program from stdin, in place of a file\n",
+        "    -e <code>                     Use the code given, in place of a file\n",
+    );
+
+    const WATCH: &str = "    --watch                       Run the program again whenever it (or a file it includes) changes\n";
+
+    const CHECK: &str = "    --check                       Don't change the file, exit with 1 if it needs formatting\n";
+
+    const ERROR_FORMAT: &str = "    --error-format=human|json     How errors are written to stderr (human by default)\n";
+
+    const LINT_LEVELS: &str = concat!(
+        "    --allow=<lint>                Turn a lint off, `warnings` stands for every lint\n",
+        "    --warn=<lint>                 Report a lint as a warning\n",
+        "    --deny=<lint>                 Report a lint as an error\n",
+    );
+
+    const LEX_FORMAT: &str = concat!(
+        "    --format=compact              One token per line with its span (the default)\n",
+        "    --format=json                 One JSON object per token\n",
+        "    --format=debug                Every field of every token\n",
+    );
+
+    const PARSE_FORMAT: &str = concat!(
+        "    --format=sexpr                An indented S-expression (the default)\n",
+        "    --format=json                 The same tree as JSON\n",
+        "    --format=dot                  A Graphviz graph, e.g. `darcy parse main.dy --format=dot | dot -Tsvg`\n",
+        "    --format=debug                Every field of every node\n",
+    );
+
+    // Returns the help for `darcy help`, or for one command
+    pub fn help(topic: Option<&str>) -> Result<String, String> {
+        let (usage, about, lints) = match topic {
+            None => return Ok(USAGE.to_string()),
+            Some("run") => ("darcy run <file> [options] [args] [-- args]", "Checks a program for errors and warnings, then runs it.\n`darcy <file>` does the same. The args after the file are the program's, see `sys.args()`,\nand everything after `--` is passed on as it is, flags included.", true),
+            Some("check") => ("darcy check <file> [options]", "Reports every error and warning in a program without running it.", true),
+            Some("lex") => ("darcy lex <file> [options]", "Prints each token of a file with the span it covers, one per line.", false),
+            Some("parse") => ("darcy parse <file> [options]", "Prints the syntax tree of a file, along with the files it includes.", false),
+            Some("fmt") => ("darcy fmt <file> [options]", "Rewrites a file with blocks indented by four spaces, spaces around operators and\nat most one blank line in a row, keeping its comments. Formatting `-` or `-e <code>`\nprints the result instead. A file with syntax errors is left as it is.", false),
+            Some("repl") => return Ok(format!(
+                "Usage: darcy repl [options]\n\nStarts an interactive session, `darcy` with no arguments does the same.\nType `:help` in it for its commands.\n\nOptions:\n{ERROR_FORMAT}    -h, --help                    Print help\n",
+            )),
+            Some("explain") => return Ok("Usage: darcy explain <code>\n\nExplains an error code with examples, e.g. `darcy explain D0402`.\nWithout a code, lists every code.\n".to_string()),
+            Some("help") => return Ok("Usage: darcy help [command]\n\nPrints the commands, or more about one of them.\n".to_string()),
+            Some(other) => return Err(format!("unknown command `{other}`")),
+        };
+
+        let mut text = format!("Usage: {usage}\n\n{about}\n\nOptions:\n{INPUT}{ERROR_FORMAT}");
+        if lints {
+            text.push_str(LINT_LEVELS);
+        }
+        match topic {
+            Some("run") => text.push_str(WATCH),
+            Some("fmt") => text.push_str(CHECK),
+            Some("lex") => text.push_str(LEX_FORMAT),
+            Some("parse") => text.push_str(PARSE_FORMAT),
+            _ => {},
+        }
+        text.push_str("    -h, --help                    Print help\n");
+        Ok(text)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{help, parse, Command, DumpFormat, ErrorFormat, Input};
+
+        fn command(line: &str) -> Result<Command, String> {
+            let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+            parse(&args)
+        }
+
+        #[test]
+        fn error_format_is_human_or_json() {
+            let Ok(Command::Check(options)) = command("check main.dy --error-format=json") else {
+                panic!("expected `darcy check`");
+            };
+            assert_eq!(options.format, ErrorFormat::Json);
+            assert!(matches!(command("repl --error-format=json"), Ok(Command::Repl(ErrorFormat::Json))));
+            assert!(command("run main.dy --error-format=xml").unwrap_err().contains("unknown error format `xml`"));
+        }
+
+        #[test]
+        fn commands_take_a_file_and_their_own_flags() {
+            assert!(matches!(command(""), Ok(Command::Repl(ErrorFormat::Human))));
+            let Ok(Command::Run(options)) = command("main.dy") else {
+                panic!("a file on its own should be run");
+            };
+            assert_eq!(options.input, Input::File("main.dy".to_string()));
+            assert!(matches!(command("check main.dy"), Ok(Command::Check(_))));
+            let Ok(Command::Parse(options)) = command("parse --format=dot main.dy") else {
+                panic!("expected `darcy parse`");
+            };
+            assert_eq!(options.dump, Some(DumpFormat::Dot));
+            assert!(matches!(command("lex main.dy"), Ok(Command::Lex(options)) if options.dump == Some(DumpFormat::Compact)));
+            assert!(matches!(command("fmt --check main.dy"), Ok(Command::Fmt(options)) if options.check));
+
+            assert_eq!(command("build main.dy").unwrap_err(), "unknown command `build`");
+            assert!(command("lex main.dy --deny=warnings").unwrap_err().contains("only applies to `darcy run` and `darcy check`"));
+            assert!(command("run main.dy --debug").unwrap_err().contains("unknown flag `--debug`"));
+            assert!(command("check main.dy --watch").unwrap_err().contains("only applies to `darcy run`"));
+            assert!(command("run main.dy --format=json").unwrap_err().contains("only applies to `darcy lex` and `darcy parse`"));
+            assert!(command("check a.dy b.dy").unwrap_err().contains("takes one program"));
+            assert!(command("check").unwrap_err().contains("needs a file"));
+        }
+
+        #[test]
+        fn help_is_given_for_each_command() {
+            assert!(matches!(command("--help"), Ok(Command::Help(None))));
+            assert!(matches!(command("fmt --help"), Ok(Command::Help(Some(topic))) if topic == "fmt"));
+            assert!(help(Some("check")).unwrap().contains("--deny"));
+            assert!(help(Some("fmt")).unwrap().contains("--check"));
+            assert!(!help(Some("lex")).unwrap().contains("--deny"));
+            assert!(help(Some("build")).is_err());
+        }
+
+        #[test]
+        fn programs_come_from_a_file_stdin_or_e() {
+            let args = |args: &[&str]| parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+
+            let Ok(Command::Run(options)) = args(&["-e", "out 2 * 3"]) else {
+                panic!("`-e` on its own should be run");
+            };
+            assert_eq!(options.input, Input::Inline("out 2 * 3".to_string()));
+            assert_eq!(options.input.name(), "<-e>");
+            assert!(matches!(args(&["check", "-"]), Ok(Command::Check(options)) if options.input == Input::Stdin));
+
+            assert!(args(&["-e"]).unwrap_err().contains("`-e` needs some code after it"));
+            assert!(args(&["check", "-", "main.dy"]).unwrap_err().contains("takes one program, found"));
+            assert!(args(&["run", "-", "--watch"]).unwrap_err().contains("needs a file to watch"));
+        }
+    }
+}
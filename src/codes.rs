@@ -0,0 +1,513 @@
+pub mod codes {
+    /*
+        Every diagnostic carries one of the codes below, so an error can be searched for
+        and linked to. Codes never change meaning once they're published, new errors get new codes.
+
+        D01xx   names
+        D02xx   syntax
+        D03xx   arguments and calls
+        D04xx   types and values
+        D05xx   indexing
+        D06xx   matrices
+        D07xx   lints, which are warnings unless they're denied
+        D08xx   errors raised by the program
+        D09xx   files and includes
+    */
+    pub const UNDEFINED_NAME: &str = "D0101";
+    pub const SYNTAX: &str = "D0201";
+    pub const UNTERMINATED_STRING: &str = "D0202";
+    pub const UNCLOSED_BLOCK: &str = "D0203";
+    pub const UNBALANCED_BLOCK: &str = "D0204";
+    pub const ARGUMENT_COUNT: &str = "D0301";
+    pub const ARGUMENT_LABEL: &str = "D0302";
+    pub const CALL_DEPTH: &str = "D0303";
+    pub const TYPE_MISMATCH: &str = "D0401";
+    pub const NIL_VALUE: &str = "D0402";
+    pub const NOT_A_BOOL: &str = "D0403";
+    pub const DIVISION_BY_ZERO: &str = "D0404";
+    pub const CONVERSION: &str = "D0405";
+    pub const INDEX_RANGE: &str = "D0501";
+    pub const MATRIX_SHAPE: &str = "D0601";
+    pub const SINGULAR_MATRIX: &str = "D0602";
+    pub const UNUSED_VARIABLE: &str = "D0701";
+    pub const CONST_SHADOW: &str = "D0702";
+    pub const UNREACHABLE_CODE: &str = "D0703";
+    pub const DUPLICATE_CONDITION: &str = "D0704";
+    pub const FLOAT_EQUALITY: &str = "D0705";
+    pub const RAISED: &str = "D0801";
+    pub const MISSING_FILE: &str = "D0901";
+    pub const INCLUDE_CYCLE: &str = "D0902";
+
+    // The long-form explanation of a code, printed by `explain`
+    pub struct Explanation {
+        pub code: &'static str,
+        pub title: &'static str,
+        pub text: &'static str,
+    }
+
+    pub const CATALOGUE: &[Explanation] = &[
+        Explanation {
+            code: UNDEFINED_NAME,
+            title: "a name is used before it is defined",
+            text: "\
+Every variable and function has to be defined before it is used, and is only
+visible inside the block it was defined in (and the blocks inside that).
+
+    out total           // `total` is not defined yet
+    total = 5
+
+Define the name first, or check the spelling:
+
+    total = 5
+    out total
+",
+        },
+        Explanation {
+            code: SYNTAX,
+            title: "the code doesn't follow DarcyLang's grammar",
+            text: "\
+The parser found a token where it can't be. The message says what it expected
+instead, and the caret points at what it found.
+
+    out 5 +             // `+` needs something on its right
+
+Every statement ends at a new line or a `;`, so two statements can't share a
+line without one:
+
+    x = 5 y = 6         // write `x = 5; y = 6`
+",
+        },
+        Explanation {
+            code: UNTERMINATED_STRING,
+            title: "a string is missing its closing quote",
+            text: "\
+Strings start and end with `\"` on the same line.
+
+    title = \"A Graph      // the string runs to the end of the line
+
+Close the string, and use `\\n` for a line break inside it:
+
+    title = \"A Graph\\nof Points\"
+",
+        },
+        Explanation {
+            code: UNCLOSED_BLOCK,
+            title: "a block is never closed with `end`",
+            text: "\
+`func`, `if`, `for`, `match`, `enum` and `matrix` blocks all finish with `end`.
+The file ended while one of them was still open, the label shows which.
+
+    func area <- w, h
+        if w > 0
+            return w * h
+        end
+                        // the `func` still needs its `end`
+
+Add the missing `end`:
+
+    func area <- w, h
+        if w > 0
+            return w * h
+        end
+    end
+",
+        },
+        Explanation {
+            code: UNBALANCED_BLOCK,
+            title: "an `end`, `elif`, `else` or `case` has no block to belong to",
+            text: "\
+`end` closes the innermost open block, so an extra `end` is left with nothing
+to close. `elif` and `else` have to follow the body of an `if` (or `else` of a
+`match`), and `case` can only be used inside a `match`.
+
+    if x > 5
+        out \"big\"
+    end
+    else                // the `if` was already closed by the `end` above
+        out \"small\"
+    end
+
+Remove the extra `end`, or move the branch inside the block it belongs to:
+
+    if x > 5
+        out \"big\"
+    else
+        out \"small\"
+    end
+",
+        },
+        Explanation {
+            code: ARGUMENT_COUNT,
+            title: "a call has the wrong number of arguments",
+            text: "\
+A function or method was given more or fewer arguments than it has parameters.
+
+    func area <- w, h
+        return w * h
+    end
+    out area(2, 3, 4)   // `area` takes 2 arguments
+
+Parameters with a default value may be left out:
+
+    func area <- w, h = 1
+        return w * h
+    end
+    out area(2)
+",
+        },
+        Explanation {
+            code: ARGUMENT_LABEL,
+            title: "a labelled argument doesn't match the function",
+            text: "\
+Arguments can be passed by label with `label as value`. The label has to match a
+parameter's label (or its name if it has none), each can only be given once, and
+labelled arguments come after all the positional ones.
+
+    func area <- w #width, h #height
+        return w * h
+    end
+    out area(w as 2, height as 3)       // callers use `width`, not `w`
+    out area(width as 2, 3)             // positional after labelled
+
+    out area(2, height as 3)
+",
+        },
+        Explanation {
+            code: CALL_DEPTH,
+            title: "functions call each other too deeply",
+            text: "\
+A call can only start while fewer than 1000 others are still running. This is
+almost always a recursive function that never reaches the case that stops it.
+
+    func count <- n
+        return count(n - 1)     // nothing stops at 0
+    end
+
+Give the recursion a case that returns without calling again:
+
+    func count <- n
+        if n == 0
+            return 0
+        end
+        return count(n - 1)
+    end
+",
+        },
+        Explanation {
+            code: TYPE_MISMATCH,
+            title: "a value has the wrong type for what is done with it",
+            text: "\
+The operation or function doesn't work on a value of this type, e.g. adding a
+number to a string or calling something that isn't a function.
+
+    out \"total: \" + 5   // cannot add a Str and an Int
+
+Convert the value or put it in the string instead:
+
+    out \"total: {5}\"
+",
+        },
+        Explanation {
+            code: NIL_VALUE,
+            title: "nil is used where a value is needed",
+            text: "\
+`nil` is what a function without a `return`, `max()` on an empty list and a
+missing map key give back. It can't be used as a number, indexed or called.
+The note says where the nil came from.
+
+    scores = []
+    best = scores.max()
+    out best + 1        // `best` is nil
+
+Check for nil first, or use an Option to make the missing case explicit:
+
+    best = scores.max()
+    if best != nil
+        out best + 1
+    end
+
+    match ages.get(\"bob\")
+    case Option:Some(age)
+        out age + 1
+    case Option:None
+        out \"no age\"
+    end
+",
+        },
+        Explanation {
+            code: NOT_A_BOOL,
+            title: "a condition isn't a Bool",
+            text: "\
+`if`, `elif`, `and`, `or` and `not` only take Bool values, there is no
+\"truthiness\", so numbers, strings and lists have to be compared explicitly.
+
+    if count            // count is an Int
+        out \"some\"
+    end
+
+    if count != 0
+        out \"some\"
+    end
+",
+        },
+        Explanation {
+            code: DIVISION_BY_ZERO,
+            title: "a value is divided by zero",
+            text: "\
+There is no Int, Num or matrix for `x / 0`, so dividing by zero (or `0.0`) is an error
+rather than giving `inf` or `NaN`.
+
+    out 10 / 0
+    out 1.5 / 0.0
+    out m / 0                   // dividing every entry of a matrix
+
+Check the divisor first, or catch the error with `try`:
+
+    if d != 0
+        out 10 / d
+    end
+",
+        },
+        Explanation {
+            code: CONVERSION,
+            title: "a value can't be converted to another type",
+            text: "\
+`int`, `num` and `str` convert values, and `int` and `num` fail on a Str that
+isn't a number (spaces around it are fine).
+
+    out int(\"12\") + 1     // 13
+    out int(\"twelve\")     // can't be converted
+
+Catch the error when the text comes from outside the program:
+
+    try
+        count = int(line)
+    catch
+        count = 0
+    end
+",
+        },
+        Explanation {
+            code: INDEX_RANGE,
+            title: "an index is out of range",
+            text: "\
+Lists, tuples and strings are indexed from 0, matrices from 1.
+
+    xs = [1, 2, 3]
+    out xs[3]           // valid indices are 0 to 2
+
+    m = identity(2)
+    out m[0, 1]         // valid rows are 1 to 2
+
+Use `len()` (or `rows()` and `cols()`) to check the size first.
+",
+        },
+        Explanation {
+            code: MATRIX_SHAPE,
+            title: "matrices have shapes that don't fit together",
+            text: "\
+Matrices can only be added or subtracted when they are the same shape, and
+multiplied when the left has as many columns as the right has rows. Every row of
+a matrix literal has to be the same length.
+
+    a = matrix
+        [1, 2]
+        [3, 4]
+    end
+    b = matrix
+        [1, 2, 3]
+    end
+    out a * b           // 2x2 times 1x3, a has 2 columns but b has 1 row
+
+    out b.transpose() * b   // 3x1 times 1x3 fits, giving a 3x3 matrix
+",
+        },
+        Explanation {
+            code: SINGULAR_MATRIX,
+            title: "a matrix has no inverse",
+            text: "\
+A matrix with a determinant of 0 can't be inverted, and `solve` has no unique
+answer for it. This happens when a row is a multiple of the others.
+
+    m = matrix
+        [1, 2]
+        [2, 4]
+    end
+    out m.inverse()     // row 2 is twice row 1
+
+Check `m.determinant() != 0` (or `m.rank()`) first.
+",
+        },
+        Explanation {
+            code: UNUSED_VARIABLE,
+            title: "a variable is never read (lint `unused_variable`)",
+            text: "\
+A variable, parameter or loop variable is given a value that nothing ever reads.
+This is often a typo in a later use of the name, or code left over from a change.
+
+    func area <- w, h
+        result = w * h
+        return w * w    // `h` and `result` are never read
+    end
+
+Start the name with an underscore if it is meant to go unused:
+
+    for _ in [1, 2, 3]
+        out \"again\"
+    end
+",
+        },
+        Explanation {
+            code: CONST_SHADOW,
+            title: "a name declared `const` is reused (lint `const_shadow`)",
+            text: "\
+A `const` is meant to keep the same value everywhere it is visible. Assigning to
+it again, or using its name for a parameter or loop variable, hides the value
+the rest of the code expects.
+
+    const LIMIT = 10
+    func check <- LIMIT     // the parameter shadows the constant
+        return LIMIT > 5
+    end
+
+Pick a different name for the new value.
+",
+        },
+        Explanation {
+            code: UNREACHABLE_CODE,
+            title: "code after a `return` can never run (lint `unreachable_code`)",
+            text: "\
+A `return` leaves the function straight away, so the statements after it in
+the same block are never run.
+
+    func sign <- x
+        return x > 0
+        out \"checked\"     // never runs
+    end
+
+Move the code before the `return`, or remove it.
+",
+        },
+        Explanation {
+            code: DUPLICATE_CONDITION,
+            title: "an `elif` repeats an earlier condition (lint `duplicate_condition`)",
+            text: "\
+Branches are checked in order and only the first that holds runs, so an `elif`
+with the same condition as an earlier branch can never run.
+
+    if x > 10
+        out \"big\"
+    elif x > 10             // already handled above
+        out \"huge\"
+    end
+
+This is usually a copy-paste slip, the second condition was meant to differ.
+",
+        },
+        Explanation {
+            code: FLOAT_EQUALITY,
+            title: "Nums are compared with `==` (lint `float_equality`)",
+            text: "\
+Nums can't hold most decimals exactly, so arithmetic gives results that are a
+tiny bit off and `==` is false when it looks like it should be true.
+
+    out 0.1 + 0.2 == 0.3    // false
+
+Check that the difference is small instead:
+
+    d = 0.1 + 0.2 - 0.3
+    out d < 0.000001 and d > -0.000001
+",
+        },
+        Explanation {
+            code: RAISED,
+            title: "the program raised an error that nothing caught",
+            text: "\
+`raise` stops the program with an error, unless a `try` around it (in the same
+function or any function that called it) catches it.
+
+    func parse_point <- text
+        parts = text.split(\",\")
+        if parts.len() != 2
+            raise \"expected `x,y`, found {text}\"
+        end
+        return (num(parts[0]), num(parts[1]))
+    end
+
+    try
+        point = parse_point(\"3\")
+    catch err
+        out err.message()
+        point = (0.0, 0.0)
+    end
+
+Every runtime error can be caught the same way, and `raise err` inside a
+`catch` passes a caught error on.
+",
+        },
+        Explanation {
+            code: MISSING_FILE,
+            title: "a file couldn't be read",
+            text: "\
+Each name in an `include` is a file in the same directory as the file including
+it, with `.dy` added to the end.
+
+    // in examples/main.dy
+    include plotting end        // reads examples/plotting.dy
+
+Check that the file exists, that it ends in `.dy` and that it can be read.
+The same goes for the program given to `darcy` itself, which is read from the
+path given, from stdin with `-`, or taken from the command line with `-e`:
+
+    darcy run examples/main.dy
+    cat examples/main.dy | darcy run -
+    darcy -e 'out 2 * 3'
+
+Files (and stdin) have to be UTF-8 text.
+",
+        },
+        Explanation {
+            code: INCLUDE_CYCLE,
+            title: "a file includes itself",
+            text: "\
+A file can't be included while it is still being included, which happens when it
+includes itself or includes a file that (directly or not) includes it back.
+
+    // main.dy
+    include shapes end
+
+    // shapes.dy
+    include main end            // main.dy is what is including shapes.dy
+
+Move what both files need into a third file and include that from each of them,
+a file that has already been included is only run the first time.
+",
+        },
+    ];
+
+    pub fn explain(code: &str) -> Option<&'static Explanation> {
+        CATALOGUE.iter().find(|e| e.code.eq_ignore_ascii_case(code))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{explain, CATALOGUE, NIL_VALUE};
+
+        #[test]
+        fn codes_are_unique_and_in_order() {
+            for pair in CATALOGUE.windows(2) {
+                assert!(pair[0].code < pair[1].code, "`{}` should come before `{}`", pair[0].code, pair[1].code);
+            }
+            for explanation in CATALOGUE {
+                assert!(explanation.code.len() == 5 && explanation.code.starts_with('D'), "`{}` isn't a code", explanation.code);
+                assert!(explanation.text.ends_with('\n'));
+            }
+        }
+
+        #[test]
+        fn codes_are_explained_in_any_case() {
+            assert_eq!(explain("d0402").map(|e| e.code), Some(NIL_VALUE));
+            assert!(explain("D9999").is_none());
+        }
+    }
+}
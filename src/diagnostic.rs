@@ -0,0 +1,511 @@
+pub mod diagnostic {
+    use std::fmt::Write;
+    use colored::{Color, Colorize};
+    use crate::source::source::Sources;
+
+    // How many columns a tab takes up when a source line is printed
+    const TAB_WIDTH: usize = 4;
+
+    /*
+        A region of the source, from (line, col) up to but not including (end_line, end_col).
+        Lines and columns both start at 1 and columns count characters, not bytes.
+
+        A column of 0 means the column isn't known, the whole line is marked instead.
+        A line of 0 is nowhere in the source, for errors about a file that couldn't be read at all.
+    */
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct Span {
+        pub line: usize,
+        pub col: usize,
+        pub end_line: usize,
+        pub end_col: usize,
+    }
+
+    impl Span {
+        pub fn new(line: usize, col: usize, end_line: usize, end_col: usize) -> Self {
+            Self { line, col, end_line, end_col }
+        }
+
+        // Marks the whole of a line
+        pub fn line(line: usize) -> Self {
+            Self { line, col: 0, end_line: line, end_col: 0 }
+        }
+
+        // Returns the span from the start of this one to the end of the other
+        pub fn to(self, other: Span) -> Self {
+            Self { line: self.line, col: self.col, end_line: other.end_line, end_col: other.end_col }
+        }
+
+        pub fn nowhere() -> Self {
+            Self::default()
+        }
+
+        pub fn is_nowhere(&self) -> bool {
+            self.line == 0
+        }
+
+        pub fn has_column(&self) -> bool {
+            self.col != 0
+        }
+
+        // Fills in the columns of a whole-line span from the source, leaving out the indentation
+        pub fn resolve(self, sources: &Sources) -> Self {
+            if self.has_column() {
+                return self;
+            }
+
+            let content = sources.line(self.line).unwrap_or("");
+            let indent = content.chars().take_while(|c| c.is_whitespace()).count();
+            let length = content.trim_end().chars().count();
+            Self { col: indent + 1, end_col: length.max(indent) + 1, ..self }
+        }
+
+        // Lines are counted within the span's own file
+        pub fn to_json(self, sources: &Sources) -> String {
+            if self.is_nowhere() {
+                return "null".to_string();
+            }
+
+            let span = self.resolve(sources);
+            format!(
+                "{{\"file\":{},\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{}}}",
+                json_string(&sources.file(sources.file_of(span.line)).name),
+                sources.local(span.line), span.col, sources.local(span.end_line), span.end_col,
+            )
+        }
+    }
+
+    // Errors that only know their line convert straight into a span
+    impl From<usize> for Span {
+        fn from(line: usize) -> Self {
+            Span::line(line)
+        }
+    }
+
+    // A span with a message, shown underneath the source
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Label {
+        pub span: Span,
+        pub message: String,
+    }
+
+    // A change that fixes the problem, replacing the span (which may be empty) with new text
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Fix {
+        pub span: Span,
+        pub replacement: String,
+        pub message: String,
+    }
+
+    // A call that was running when a runtime error happened, and where it was called from
+    // Anonymous functions have no name
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Frame {
+        pub function: Option<String>,
+        pub span: Span,
+    }
+
+    // Errors stop the program, warnings are reported and it runs anyway
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Severity {
+        Error,
+        Warning,
+    }
+
+    impl Severity {
+        pub fn name(&self) -> &'static str {
+            match self {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            }
+        }
+
+        fn color(&self) -> Color {
+            match self {
+                Severity::Error => Color::Red,
+                Severity::Warning => Color::Yellow,
+            }
+        }
+    }
+
+    /*
+        A diagnostic is everything that gets reported about one problem:
+
+        TypeError[D0401]: cannot add a value of type Int and a value of type Str
+          --> line 3, column 5
+           |
+         3 | out 5 + "a"
+           |     ^^^^^^^ primary label
+           |
+           = note: ...
+           = help: ...
+
+        Secondary labels are underlined with `-` on whichever lines they fall on.
+        Warnings are drawn in yellow instead of red.
+
+        Runtime errors inside a function end with the calls that led there, innermost first:
+
+           = trace: most recent call first
+             in `divide`, called on line 7, column 11
+                 out divide(x, 0)
+             in `report`, called on line 12, column 7
+                 report()
+    */
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Diagnostic {
+        pub severity: Severity,
+        pub kind: String,
+        pub code: Option<String>,
+        pub message: String,
+        pub span: Span,
+        pub label: Option<String>,
+        pub labels: Vec<Label>,
+        pub notes: Vec<String>,
+        pub help: Vec<String>,
+        pub fixes: Vec<Fix>,
+        pub trace: Vec<Frame>,
+    }
+
+    // One underline to draw, either the primary span or a secondary label
+    struct Mark<'a> {
+        span: Span,
+        message: Option<&'a str>,
+        primary: bool,
+    }
+
+    impl Diagnostic {
+        pub fn new(kind: &str, message: &str, span: Span) -> Self {
+            Self {
+                severity: Severity::Error,
+                kind: kind.to_string(),
+                code: None,
+                message: message.to_string(),
+                span,
+                label: None,
+                labels: Vec::new(),
+                notes: Vec::new(),
+                help: Vec::new(),
+                fixes: Vec::new(),
+                trace: Vec::new(),
+            }
+        }
+
+        pub fn with_severity(mut self, severity: Severity) -> Self {
+            self.severity = severity;
+            self
+        }
+
+        // Sets the message shown under the primary span
+        pub fn with_label(mut self, message: &str) -> Self {
+            self.label = Some(message.to_string());
+            self
+        }
+
+        // Adds a message shown under another part of the source
+        pub fn with_secondary(mut self, span: Span, message: &str) -> Self {
+            self.labels.push(Label { span, message: message.to_string() });
+            self
+        }
+
+        pub fn with_note(mut self, note: &str) -> Self {
+            self.notes.push(note.to_string());
+            self
+        }
+
+        pub fn with_help(mut self, help: &str) -> Self {
+            self.help.push(help.to_string());
+            self
+        }
+
+        // Fixes are shown as help in the terminal, the JSON output keeps them apart so editors can apply them
+        pub fn with_fix(mut self, fix: Fix) -> Self {
+            self.help.push(fix.message.clone());
+            self.fixes.push(fix);
+            self
+        }
+
+        pub fn with_trace(mut self, trace: Vec<Frame>) -> Self {
+            self.trace = trace;
+            self
+        }
+
+        /*
+            Returns the diagnostic as a single line of JSON, for editors and CI:
+
+            {"code":"D0402","severity":"error" or "warning","kind":"TypeError","message":"...","file":"main.dy",
+             "span":{"file":"main.dy","line":4,"column":5,"end_line":4,"end_column":9},
+             "labels":[{"span":{...},"message":"...","primary":true}],
+             "notes":["..."],"help":["..."],
+             "fixes":[{"span":{...},"replacement":"\"","message":"..."}],
+             "trace":[{"function":"divide" or null,"span":{...}}]}
+
+            Lines and columns start at 1 and the end column is exclusive. The top-level
+            `file` is the one the primary span is in, every span also names its own file.
+            Both are `null` when the error isn't about a place in the source.
+        */
+        pub fn to_json(&self, sources: &Sources) -> String {
+            let strings = |items: &[String]| -> String {
+                let items: Vec<String> = items.iter().map(|i| json_string(i)).collect();
+                format!("[{}]", items.join(","))
+            };
+
+            let mut labels = vec![format!(
+                "{{\"span\":{},\"message\":{},\"primary\":true}}",
+                self.span.to_json(sources),
+                self.label.as_deref().map_or("null".to_string(), json_string),
+            )];
+            for label in &self.labels {
+                labels.push(format!(
+                    "{{\"span\":{},\"message\":{},\"primary\":false}}",
+                    label.span.to_json(sources), json_string(&label.message),
+                ));
+            }
+
+            let fixes: Vec<String> = self.fixes.iter().map(|fix| format!(
+                "{{\"span\":{},\"replacement\":{},\"message\":{}}}",
+                fix.span.to_json(sources), json_string(&fix.replacement), json_string(&fix.message),
+            )).collect();
+
+            let trace: Vec<String> = self.trace.iter().map(|frame| format!(
+                "{{\"function\":{},\"span\":{}}}",
+                frame.function.as_deref().map_or("null".to_string(), json_string), frame.span.to_json(sources),
+            )).collect();
+
+            format!(
+                "{{\"code\":{},\"severity\":{},\"kind\":{},\"message\":{},\"file\":{},\"span\":{},\"labels\":[{}],\"notes\":{},\"help\":{},\"fixes\":[{}],\"trace\":[{}]}}",
+                self.code.as_deref().map_or("null".to_string(), json_string),
+                json_string(self.severity.name()),
+                json_string(&self.kind),
+                json_string(&self.message),
+                match self.span.is_nowhere() {
+                    true => "null".to_string(),
+                    false => json_string(&sources.file(sources.file_of(self.span.line)).name),
+                },
+                self.span.to_json(sources),
+                labels.join(","),
+                strings(&self.notes),
+                strings(&self.help),
+                fixes.join(","),
+                trace.join(","),
+            )
+        }
+
+        // Returns the diagnostic rendered against the lines of the source, ready to print
+        pub fn render(&self, sources: &Sources) -> String {
+            let mut out = String::new();
+
+            let mut marks = vec![Mark { span: self.span, message: self.label.as_deref(), primary: true }];
+            for label in &self.labels {
+                marks.push(Mark { span: label.span, message: Some(&label.message), primary: false });
+            }
+
+            // Every line any mark touches, in order
+            let mut lines: Vec<usize> = marks.iter()
+                .flat_map(|m| m.span.line..=m.span.end_line.max(m.span.line))
+                .filter(|&l| sources.line(l).is_some())
+                .collect();
+            lines.sort_unstable();
+            lines.dedup();
+
+            // Line numbers are shown as they are in their own file
+            let width = lines.iter().map(|&l| sources.local(l).to_string().len()).max().unwrap_or(1);
+            let gutter = |text: &str| format!("{text:>width$} |").color(Color::Blue).bold();
+            let blank = " ".repeat(width);
+
+            let heading = match &self.code {
+                Some(code) => format!("{}[{code}]", self.kind),
+                None => self.kind.clone(),
+            };
+            writeln!(out, "{}: {}", heading.color(self.severity.color()).bold(), self.message.bold()).unwrap();
+            if !self.span.is_nowhere() {
+                writeln!(out, "{}{} {}", blank, "-->".blue().bold(), sources.locate(self.span)).unwrap();
+            }
+
+            if !lines.is_empty() {
+                writeln!(out, "{}", gutter(&blank)).unwrap();
+            }
+
+            let mut file = sources.file_of(self.span.line);
+            let mut previous = None;
+            for line in lines {
+                // A label in another file (e.g. the `include` of this one) says which file it's in
+                if sources.file_of(line) != file {
+                    file = sources.file_of(line);
+                    writeln!(out, "{}{} {}", blank, ":::".blue().bold(), sources.file(file).name).unwrap();
+                } else if previous.is_some_and(|p| line > p + 1) {
+                    // Skipped lines between two marks are shown as `...`
+                    writeln!(out, "{}", "...".blue().bold()).unwrap();
+                }
+                previous = Some(line);
+
+                let content = sources.line(line).unwrap_or("");
+                writeln!(out, "{} {}", gutter(&sources.local(line).to_string()), expand_tabs(content).trim_end()).unwrap();
+
+                for mark in marks.iter().filter(|m| m.span.line <= line && line <= m.span.end_line.max(m.span.line)) {
+                    let (start, end) = mark_columns(mark.span, line, content);
+                    let (symbol, color) = if mark.primary { ('^', self.severity.color()) } else { ('-', Color::Blue) };
+                    let underline = symbol.to_string().repeat(end.saturating_sub(start).max(1));
+
+                    // The message goes after the last line of the mark
+                    let message = match mark.message {
+                        Some(message) if line == mark.span.end_line.max(mark.span.line) => format!(" {message}"),
+                        _ => String::new(),
+                    };
+                    writeln!(
+                        out, "{} {}{}",
+                        gutter(&blank), " ".repeat(start),
+                        format!("{underline}{message}").color(color).bold(),
+                    ).unwrap();
+                }
+            }
+
+            if !self.notes.is_empty() || !self.help.is_empty() {
+                writeln!(out, "{}", gutter(&blank)).unwrap();
+            }
+            for note in &self.notes {
+                writeln!(out, "{} {} {note}", blank, "= note:".bold()).unwrap();
+            }
+            for help in &self.help {
+                writeln!(out, "{} {} {help}", blank, "= help:".cyan().bold()).unwrap();
+            }
+
+            if !self.trace.is_empty() {
+                self.render_trace(&mut out, sources, &blank);
+            }
+
+            out
+        }
+
+        // Writes the calls that led to the error, folding a call repeated by recursion into one line
+        fn render_trace(&self, out: &mut String, sources: &Sources, blank: &str) {
+            if self.notes.is_empty() && self.help.is_empty() {
+                writeln!(out, "{}", format!("{blank} |").blue().bold()).unwrap();
+            }
+            writeln!(out, "{} {} most recent call first", blank, "= trace:".bold()).unwrap();
+
+            let mut frames = self.trace.iter().peekable();
+            while let Some(frame) = frames.next() {
+                let function = match &frame.function {
+                    Some(name) => format!("`{name}`"),
+                    None => "an anonymous function".to_string(),
+                };
+                writeln!(out, "{blank}   in {function}, called on {}", sources.locate(frame.span)).unwrap();
+
+                if let Some(content) = sources.line(frame.span.line) {
+                    writeln!(out, "{blank}       {}", expand_tabs(content).trim()).unwrap();
+                }
+
+                let mut repeats = 0;
+                while frames.next_if(|next| *next == frame).is_some() {
+                    repeats += 1;
+                }
+                if repeats > 0 {
+                    writeln!(out, "{blank}   ... the same call {repeats} more time{}", if repeats == 1 { "" } else { "s" }).unwrap();
+                }
+            }
+        }
+    }
+
+    // Quotes and escapes a string for JSON
+    pub fn json_string(text: &str) -> String {
+        let mut out = String::with_capacity(text.len() + 2);
+        out.push('"');
+        for c in text.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+        out
+    }
+
+    fn expand_tabs(content: &str) -> String {
+        content.replace('\t', &" ".repeat(TAB_WIDTH))
+    }
+
+    // Returns how many columns the first `chars` characters of a line take up once tabs are expanded
+    fn display_width(content: &str, chars: usize) -> usize {
+        content.chars().take(chars).map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+    }
+
+    /*
+        Returns the display columns (0-based, end exclusive) a mark covers on one line.
+        Lines in the middle of a multi-line mark, and marks without a column,
+        cover everything but the indentation.
+    */
+    fn mark_columns(span: Span, line: usize, content: &str) -> (usize, usize) {
+        let length = content.trim_end().chars().count();
+        let indent = content.chars().take_while(|c| c.is_whitespace()).count();
+
+        let start = if span.has_column() && line == span.line { span.col - 1 } else { indent };
+        let end = if span.has_column() && line == span.end_line { span.end_col - 1 } else { length };
+
+        (display_width(content, start), display_width(content, end.max(start + 1)))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Diagnostic, Span};
+        use crate::source::source::Sources;
+
+        // Renders without colors, against one file holding the text
+        fn render(diagnostic: Diagnostic, text: &str) -> String {
+            colored::control::set_override(false);
+            let mut sources = Sources::new();
+            sources.add("main.dy", None, text.to_string(), None);
+            diagnostic.render(&sources)
+        }
+
+        #[test]
+        fn carets_sit_under_the_span() {
+            let diagnostic = Diagnostic::new("TypeError", "cannot add", Span::new(2, 5, 2, 9))
+                .with_label("this is nil")
+                .with_help("check for nil first");
+            assert_eq!(
+                render(diagnostic, "best = nil\nout best + 1\n"),
+                "TypeError: cannot add\n --> line 2, column 5\n  |\n2 | out best + 1\n  |     ^^^^ this is nil\n  |\n  = help: check for nil first\n",
+            );
+        }
+
+        #[test]
+        fn secondary_labels_and_tabs_line_up() {
+            let diagnostic = Diagnostic::new("SyntaxError", "expected `end`", Span::new(3, 1, 3, 4))
+                .with_secondary(Span::new(1, 1, 1, 3), "opened here");
+            assert_eq!(
+                render(diagnostic, "if x\n\tout x\nout y\n"),
+                "SyntaxError: expected `end`\n --> line 3, column 1\n  |\n1 | if x\n  | -- opened here\n...\n3 | out y\n  | ^^^\n",
+            );
+            let diagnostic = Diagnostic::new("TypeError", "bad", Span::new(1, 2, 1, 5));
+            assert!(render(diagnostic, "\tout x\n").contains("1 |     out x\n  |     ^^^\n"));
+        }
+
+        #[test]
+        fn json_is_one_line_with_local_positions() {
+            let mut sources = Sources::new();
+            sources.add("main.dy", None, "include lib end\n".to_string(), None);
+            sources.add("lib.dy", None, "x = 1\nout \"a\" + x\n".to_string(), None);
+            let line = (1..20).find(|&l| sources.line(l) == Some("out \"a\" + x")).unwrap();
+
+            let mut diagnostic = Diagnostic::new("TypeError", "cannot add \"a\"", Span::new(line, 5, line, 12)).with_help("use {x}");
+            diagnostic.code = Some("D0401".to_string());
+            assert_eq!(
+                diagnostic.to_json(&sources),
+                concat!(
+                    r#"{"code":"D0401","severity":"error","kind":"TypeError","message":"cannot add \"a\"","file":"lib.dy","#,
+                    r#""span":{"file":"lib.dy","line":2,"column":5,"end_line":2,"end_column":12},"#,
+                    r#""labels":[{"span":{"file":"lib.dy","line":2,"column":5,"end_line":2,"end_column":12},"message":null,"primary":true}],"#,
+                    r#""notes":[],"help":["use {x}"],"fixes":[],"trace":[]}"#,
+                ),
+            );
+
+            let nowhere = Diagnostic::new("FileError", "cannot read `a.dy`", Span::nowhere()).to_json(&sources);
+            assert!(nowhere.contains(r#""file":null,"span":null"#));
+        }
+    }
+}
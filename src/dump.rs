@@ -0,0 +1,491 @@
+pub mod dump {
+    use std::fmt::Write;
+
+    use crate::{
+        ast::nodes::{ArgumentNode, CasePattern, FormatSpec, Node, ParamNode, Part, Pattern},
+        ast::nodes::{BinaryOp, CompareOp, Literal, LogicalOp, UnaryOp},
+        cli::cli::DumpFormat,
+        diagnostic::diagnostic::json_string,
+        source::source::Sources,
+        tokens::tokens::Token,
+    };
+
+    /*
+        Prints the tokens of a file for `darcy lex`, one line each with the span it covers,
+        so the output of two versions of the lexer can be compared with diff:
+
+        1:1-1:2     Identifier      "x"
+        1:3-1:4     Equal           "="
+        1:5-1:6     NumberLiteral   "1"
+        1:6-1:7     Newline         "newline"
+    */
+    pub fn tokens(tokens: &[Token], sources: &Sources, format: DumpFormat) -> String {
+        let mut out = String::new();
+        for token in tokens {
+            let span = token.span();
+            let kind = format!("{:?}", token.kind);
+            let kind = kind.split('(').next().unwrap_or(&kind);
+
+            match format {
+                DumpFormat::Json => writeln!(
+                    out, "{{\"kind\":{},\"lex\":{},\"span\":{}}}",
+                    json_string(kind), json_string(&token.lex), span.to_json(sources),
+                ).unwrap(),
+                DumpFormat::Debug => writeln!(out, "{token:#?}").unwrap(),
+                _ => {
+                    let span = format!("{}:{}-{}:{}", sources.local(span.line), span.col, sources.local(span.end_line), span.end_col);
+                    writeln!(out, "{span:<12}{kind:<16}{:?}", token.lex).unwrap();
+                },
+            }
+        }
+        out
+    }
+
+    // Prints the syntax tree of a program for `darcy parse`
+    pub fn nodes(nodes: &[Node], sources: &Sources, format: DumpFormat) -> String {
+        if format == DumpFormat::Debug {
+            return format!("{nodes:#?}\n");
+        }
+
+        let tree = Tree::new("program").children(nodes.iter().map(|node| Tree::of(node, sources)));
+        let mut out = String::new();
+        match format {
+            DumpFormat::Json => {
+                tree.json(&mut out, 0);
+                out.push('\n');
+            },
+            DumpFormat::Dot => {
+                out.push_str("digraph ast {\n    node [shape=box, fontname=\"monospace\"];\n");
+                tree.dot(&mut out, &mut 0);
+                out.push_str("}\n");
+            },
+            _ => {
+                tree.sexpr(&mut out, 0);
+                out.push('\n');
+            },
+        }
+        out
+    }
+
+    /*
+        A node of the syntax tree as the dumps see it: what kind of node it is, the name,
+        operator or literal it holds, the line it's on (within its own file) and what's under it.
+        Every format is written from this, so they all show the same tree.
+
+        (assign x @1
+          (binary + @1
+            (literal 1)
+            (identifier y @1)))
+    */
+    struct Tree {
+        kind: &'static str,
+        value: Option<String>,
+        line: Option<usize>,
+        children: Vec<Tree>,
+    }
+
+    impl Tree {
+        fn new(kind: &'static str) -> Self {
+            Self { kind, value: None, line: None, children: Vec::new() }
+        }
+
+        fn value(mut self, value: impl Into<String>) -> Self {
+            self.value = Some(value.into());
+            self
+        }
+
+        fn line(mut self, line: usize, sources: &Sources) -> Self {
+            self.line = Some(sources.local(line));
+            self
+        }
+
+        fn child(mut self, child: Tree) -> Self {
+            self.children.push(child);
+            self
+        }
+
+        fn children(mut self, children: impl IntoIterator<Item = Tree>) -> Self {
+            self.children.extend(children);
+            self
+        }
+
+        // A block of statements, e.g. the body of a function
+        fn block(kind: &'static str, nodes: &[Node], sources: &Sources) -> Self {
+            Self::new(kind).children(nodes.iter().map(|node| Self::of(node, sources)))
+        }
+
+        fn of(node: &Node, sources: &Sources) -> Self {
+            let of = |node: &Node| Self::of(node, sources);
+            let all = |nodes: &[Node]| nodes.iter().map(of).collect::<Vec<_>>();
+
+            match node {
+                Node::Binary(n) => Self::new("binary").value(binary(&n.op)).line(n.line, sources).child(of(&n.left)).child(of(&n.right)),
+                Node::Compare(n) => Self::new("compare").value(compare(&n.op)).line(n.line, sources).child(of(&n.left)).child(of(&n.right)),
+                Node::Logical(n) => Self::new("logical").value(logical(&n.op)).line(n.line, sources).child(of(&n.left)).child(of(&n.right)),
+                Node::Unary(n) => Self::new("unary").value(unary(&n.op)).line(n.line, sources).child(of(&n.right)),
+                Node::Literal(n) => Self::new("literal").value(literal(&n.literal)),
+                Node::Identifier(n) => Self::new("identifier").value(&n.name).line(n.span.line, sources),
+                Node::List(n) => Self::new("list").children(all(&n.items)),
+                Node::Tuple(n) => Self::new("tuple").children(all(&n.items)),
+                Node::Map(n) => Self::new("map").line(n.line, sources).children(
+                    n.entries.iter().map(|(key, value)| Self::new("entry").child(of(key)).child(of(value))),
+                ),
+                Node::Variant(n) => Self::new("variant").value(format!("{}:{}", n.enum_name, n.name)).line(n.line, sources)
+                    .children(n.field.iter().map(|field| of(field))),
+                Node::Matrix(n) => Self::new("matrix").line(n.line, sources)
+                    .children(n.rows.iter().map(|row| Self::new("row").children(all(row)))),
+                Node::Index(n) => Self::new("index").line(n.line, sources).child(of(&n.target)).children(all(&n.indices)),
+                Node::Slice(n) => Self::new("slice").line(n.line, sources).child(of(&n.target))
+                    .child(Self::new("start").children(n.start.iter().map(|start| of(start))))
+                    .child(Self::new("end").children(n.end.iter().map(|end| of(end)))),
+                Node::Interpolation(n) => Self::new("interpolation").line(n.line, sources).children(n.parts.iter().map(|part| match part {
+                    Part::Text(text) => Self::new("text").value(format!("{text:?}")),
+                    Part::Value(node, spec) => {
+                        let value = Self::new("value").child(of(node));
+                        match format_spec(spec) {
+                            Some(spec) => value.value(spec),
+                            None => value,
+                        }
+                    },
+                })),
+                Node::Call(n) => Self::new("call").line(n.line, sources).child(of(&n.callee))
+                    .children(n.args.iter().map(|arg| argument(arg, sources))),
+                Node::Method(n) => Self::new("method").value(&n.name).line(n.line, sources).child(of(&n.target)).children(all(&n.args)),
+                Node::Assign(n) => Self::new(if n.constant { "const" } else { "assign" }).value(&n.name).line(n.span.line, sources).child(of(&n.value)),
+                Node::Destructure(n) => Self::new("destructure").value(pattern(&n.pattern)).line(n.line, sources).child(of(&n.value)),
+                Node::Out(n) => Self::new("out").line(n.line, sources).child(of(&n.value)),
+                Node::If(n) => Self::new("if").line(n.line, sources)
+                    .children(n.branches.iter().map(|(condition, body)| {
+                        Self::new("branch").child(of(condition)).child(Self::block("then", body, sources))
+                    }))
+                    .children(n.otherwise.iter().map(|body| Self::block("else", body, sources))),
+                Node::Match(n) => Self::new("match").line(n.line, sources).child(of(&n.value))
+                    .children(n.cases.iter().map(|(case, body)| {
+                        let tree = match case {
+                            CasePattern::Variant { enum_name, name, binding: Some(binding) } => Self::new("case").value(format!("{enum_name}:{name}({binding})")),
+                            CasePattern::Variant { enum_name, name, binding: None } => Self::new("case").value(format!("{enum_name}:{name}")),
+                            CasePattern::Value(value) => Self::new("case").child(of(value)),
+                        };
+                        tree.child(Self::block("then", body, sources))
+                    }))
+                    .children(n.otherwise.iter().map(|body| Self::block("else", body, sources))),
+                Node::For(n) => Self::new("for").value(pattern(&n.pattern)).line(n.line, sources)
+                    .child(of(&n.iterable)).child(Self::block("body", &n.body, sources)),
+                Node::Func(n) | Node::Lambda(n) => Self::new(if matches!(node, Node::Func(_)) { "func" } else { "lambda" })
+                    .value(&n.name).line(n.line, sources)
+                    .children(n.params.iter().map(|param| parameter(param, sources)))
+                    .child(Self::block("body", &n.body, sources)),
+                Node::Enum(n) => Self::new("enum").value(&n.name).children(n.variants.iter().map(|(name, field)| {
+                    Self::new("variant").value(if *field { format!("{name}(_)") } else { name.clone() })
+                })),
+                Node::Return(n) => Self::new("return").line(n.line, sources).children(n.value.iter().map(|value| of(value))),
+                Node::Raise(n) => Self::new("raise").line(n.line, sources).child(of(&n.value)),
+                Node::Try(n) => {
+                    let catch = Self::block("catch", &n.handler, sources);
+                    let catch = match &n.binding {
+                        Some(binding) => catch.value(&binding.name),
+                        None => catch,
+                    };
+                    Self::new("try").line(n.line, sources).child(Self::block("body", &n.body, sources)).child(catch)
+                },
+                // The statements of the included files are shown where they run
+                Node::Include(n) => {
+                    let modules: Vec<&str> = n.modules.iter().map(|module| module.name.as_str()).collect();
+                    Self::new("include").value(modules.join(", ")).line(n.line, sources).children(all(&n.body))
+                },
+            }
+        }
+
+        fn sexpr(&self, out: &mut String, depth: usize) {
+            write!(out, "{}({}", "  ".repeat(depth), self.kind).unwrap();
+            if let Some(value) = &self.value {
+                write!(out, " {value}").unwrap();
+            }
+            if let Some(line) = self.line {
+                write!(out, " @{line}").unwrap();
+            }
+            for child in &self.children {
+                out.push('\n');
+                child.sexpr(out, depth + 1);
+            }
+            out.push(')');
+        }
+
+        // Every node has the same four keys, `null` where a node has no value or line
+        fn json(&self, out: &mut String, depth: usize) {
+            let indent = "  ".repeat(depth + 1);
+            writeln!(out, "{{").unwrap();
+            writeln!(out, "{indent}\"kind\": {},", json_string(self.kind)).unwrap();
+            writeln!(out, "{indent}\"value\": {},", self.value.as_deref().map_or("null".to_string(), json_string)).unwrap();
+            writeln!(out, "{indent}\"line\": {},", self.line.map_or("null".to_string(), |line| line.to_string())).unwrap();
+
+            if self.children.is_empty() {
+                writeln!(out, "{indent}\"children\": []").unwrap();
+            } else {
+                writeln!(out, "{indent}\"children\": [").unwrap();
+                for (i, child) in self.children.iter().enumerate() {
+                    write!(out, "{indent}  ").unwrap();
+                    child.json(out, depth + 2);
+                    out.push_str(if i + 1 < self.children.len() { ",\n" } else { "\n" });
+                }
+                writeln!(out, "{indent}]").unwrap();
+            }
+            write!(out, "{}}}", "  ".repeat(depth)).unwrap();
+        }
+
+        // Writes this node and the edges to its children, numbering nodes in the order they're written
+        fn dot(&self, out: &mut String, next: &mut usize) -> usize {
+            let id = *next;
+            *next += 1;
+
+            let mut label = self.kind.to_string();
+            if let Some(value) = &self.value {
+                write!(label, " {value}").unwrap();
+            }
+            if let Some(line) = self.line {
+                write!(label, "\nline {line}").unwrap();
+            }
+            let label = label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            writeln!(out, "    n{id} [label=\"{label}\"];").unwrap();
+
+            for child in &self.children {
+                let child = child.dot(out, next);
+                writeln!(out, "    n{id} -> n{child};").unwrap();
+            }
+            id
+        }
+    }
+
+    // A labelled argument is shown with its label, `width as 5` being `(argument width (literal 5))`
+    fn argument(arg: &ArgumentNode, sources: &Sources) -> Tree {
+        match &arg.label {
+            Some(label) => Tree::new("argument").value(label).child(Tree::of(&arg.value, sources)),
+            None => Tree::of(&arg.value, sources),
+        }
+    }
+
+    fn parameter(param: &ParamNode, sources: &Sources) -> Tree {
+        let name = match &param.label {
+            Some(label) => format!("{} #{label}", param.name),
+            None => param.name.clone(),
+        };
+        Tree::new("param").value(name).line(param.span.line, sources)
+            .children(param.default.iter().map(|default| Tree::of(default, sources)))
+    }
+
+    fn pattern(names: &Pattern) -> String {
+        match names {
+            Pattern::Name(name) => name.clone(),
+            Pattern::Tuple(items) => format!("({})", items.iter().map(pattern).collect::<Vec<_>>().join(", ")),
+        }
+    }
+
+    // The part after the `:` in `"{value:>8.2}"`, if there is one
+    fn format_spec(spec: &FormatSpec) -> Option<String> {
+        let mut text = String::new();
+        if let Some(align) = spec.align {
+            text.push(align);
+        }
+        if spec.width > 0 {
+            write!(text, "{}", spec.width).unwrap();
+        }
+        if let Some(precision) = spec.precision {
+            write!(text, ".{precision}").unwrap();
+        }
+        (!text.is_empty()).then_some(text)
+    }
+
+    fn literal(literal: &Literal) -> String {
+        match literal {
+            Literal::Interger(value) => value.to_string(),
+            Literal::Float(value) => format!("{value:?}"),
+            Literal::String(value) => format!("{value:?}"),
+            Literal::Bool(value) => value.to_string(),
+            Literal::Nil => "nil".to_string(),
+        }
+    }
+
+    fn binary(op: &BinaryOp) -> &'static str {
+        match op {
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+        }
+    }
+
+    fn compare(op: &CompareOp) -> &'static str {
+        match op {
+            CompareOp::Equal => "==",
+            CompareOp::NotEqual => "!=",
+            CompareOp::Less => "<",
+            CompareOp::LessEqual => "<=",
+            CompareOp::More => ">",
+            CompareOp::MoreEqual => ">=",
+        }
+    }
+
+    fn logical(op: &LogicalOp) -> &'static str {
+        match op {
+            LogicalOp::And => "and",
+            LogicalOp::Or => "or",
+        }
+    }
+
+    fn unary(op: &UnaryOp) -> &'static str {
+        match op {
+            UnaryOp::Negative => "-",
+            UnaryOp::Not => "not",
+            UnaryOp::Field => "&",
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{iter::Peekable, str::Chars};
+
+        use crate::{cli::cli::DumpFormat, loader::loader::Loader, source::source::Sources};
+
+        fn lexed(text: &str, format: DumpFormat) -> String {
+            let mut sources = Sources::new();
+            let root = sources.add("test.dy", None, text.to_string(), None);
+            let tokens = Loader::tokens(&sources, root).expect("the program should lex");
+            super::tokens(&tokens, &sources, format)
+        }
+
+        fn parsed(text: &str, format: DumpFormat) -> String {
+            let mut sources = Sources::new();
+            let root = sources.add("test.dy", None, text.to_string(), None);
+            let (nodes, _) = Loader::new(&mut sources).load(root).expect("the program should parse");
+            super::nodes(&nodes, &sources, format)
+        }
+
+        // Just enough JSON to read the tree back: objects keep their keys in order
+        #[derive(Debug, PartialEq)]
+        enum Json {
+            Null,
+            Number(f64),
+            Str(String),
+            Array(Vec<Json>),
+            Object(Vec<(String, Json)>),
+        }
+
+        fn json(chars: &mut Peekable<Chars>) -> Json {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.next().expect("the JSON ended early") {
+                'n' => {
+                    assert_eq!(chars.take(3).collect::<String>(), "ull");
+                    Json::Null
+                },
+                '"' => {
+                    let mut text = String::new();
+                    loop {
+                        match chars.next().expect("a string isn't closed") {
+                            '"' => break Json::Str(text),
+                            '\\' => text.push(match chars.next().expect("an escape isn't finished") {
+                                'n' => '\n',
+                                't' => '\t',
+                                c => c,
+                            }),
+                            c => text.push(c),
+                        }
+                    }
+                },
+                '[' => Json::Array(items(chars, ']', json)),
+                '{' => Json::Object(items(chars, '}', |chars| {
+                    let Json::Str(key) = json(chars) else { panic!("a key isn't a string") };
+                    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                    assert_eq!(chars.next(), Some(':'));
+                    (key, json(chars))
+                })),
+                c if c.is_ascii_digit() || c == '-' => {
+                    let mut number = c.to_string();
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                        number.push(c);
+                    }
+                    Json::Number(number.parse().unwrap())
+                },
+                c => panic!("unexpected `{c}` in the JSON"),
+            }
+        }
+
+        // The items of an array or object, up to the bracket that closes it
+        fn items<T>(chars: &mut Peekable<Chars>, close: char, mut item: impl FnMut(&mut Peekable<Chars>) -> T) -> Vec<T> {
+            let mut items = Vec::new();
+            loop {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if chars.next_if_eq(&close).is_some() {
+                    return items;
+                }
+                items.push(item(chars));
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                chars.next_if_eq(&',');
+            }
+        }
+
+        // Every node of the tree has `kind`, `value`, `line` and `children`, in that order
+        fn same_keys(node: &Json) -> usize {
+            let Json::Object(fields) = node else { panic!("a node isn't an object: {node:?}") };
+            let keys: Vec<&str> = fields.iter().map(|(key, _)| key.as_str()).collect();
+            assert_eq!(keys, ["kind", "value", "line", "children"]);
+            let Json::Array(children) = &fields[3].1 else { panic!("`children` isn't an array") };
+            1 + children.iter().map(same_keys).sum::<usize>()
+        }
+
+        #[test]
+        fn tokens_are_one_line_each_with_their_span() {
+            assert_eq!(
+                lexed("x = \"a b\"", DumpFormat::Compact),
+                concat!(
+                    "1:1-1:2     Identifier      \"x\"\n",
+                    "1:3-1:4     Equal           \"=\"\n",
+                    "1:5-1:10    StringLiteral   \"a b\"\n",
+                    "1:10-1:11   EndOfFile       \"<END OF FILE>\"\n",
+                ),
+            );
+            assert!(lexed("x = 1", DumpFormat::Json).starts_with("{\"kind\":\"Identifier\",\"lex\":\"x\",\"span\":"));
+        }
+
+        #[test]
+        fn a_program_is_an_s_expression() {
+            assert_eq!(
+                parsed("x = 1 + y\nout \"say \\\"hi\\\"\"", DumpFormat::Sexpr),
+                concat!(
+                    "(program\n",
+                    "  (assign x @1\n",
+                    "    (binary + @1\n",
+                    "      (literal 1)\n",
+                    "      (identifier y @1)))\n",
+                    "  (out @2\n",
+                    "    (literal \"say \\\"hi\\\"\")))\n",
+                ),
+            );
+        }
+
+        #[test]
+        fn json_nodes_all_have_the_same_keys() {
+            let text = parsed("func add <- a, b\nreturn a + b\nend\nout add(1, b as 2)\nxs = [\"a\\nb\", nil]", DumpFormat::Json);
+            let tree = json(&mut text.chars().peekable());
+            assert!(same_keys(&tree) > 10);
+
+            let Json::Object(fields) = &tree else { unreachable!() };
+            assert_eq!(fields[0].1, Json::Str("program".to_string()));
+            assert_eq!(fields[1].1, Json::Null);
+            let Json::Array(statements) = &fields[3].1 else { unreachable!() };
+            let Json::Object(func) = &statements[0] else { unreachable!() };
+            assert_eq!(func[1].1, Json::Str("add".to_string()));
+            assert_eq!(func[2].1, Json::Number(1.0));
+        }
+
+        #[test]
+        fn dot_labels_escape_quotes_and_newlines() {
+            let dot = parsed("out \"say \\\"hi\\\"\\n\"", DumpFormat::Dot);
+            assert!(dot.starts_with("digraph ast {\n"));
+            assert!(dot.contains("    n1 [label=\"out\\nline 1\"];\n"));
+            assert!(dot.contains("    n2 [label=\"literal \\\"say \\\\\\\"hi\\\\\\\"\\\\n\\\"\"];\n"));
+            assert!(dot.contains("    n1 -> n2;\n"));
+            // Every label is closed on the line it opens on
+            assert!(dot.lines().filter(|line| line.contains("[label=")).all(|line| line.ends_with("\"];")));
+        }
+    }
+}
@@ -1,55 +1,174 @@
 pub mod errors {
+    use crate::{codes::codes, diagnostic::diagnostic::{Diagnostic, Fix, Frame, Label, Span}, scope::values::ErrorValue};
+
     // Different error types take different values.
-    // Every error type takes a usize for the line number.
+    // Every error type takes a Span for where it happened, a bare line number converts into one.
+    #[allow(clippy::enum_variant_names)]
+    #[derive(Debug, Clone, PartialEq)]
     pub enum Errors {
-        IdentifierError(usize, String),
-        SyntaxError(usize, String),
-        ArgumentError(usize, u8, u8),
+        IdentifierError(Span, String),
+        SyntaxError(Span, String),
+        ArgumentError(Span, String),
+        RecursionError(Span, String),
+        TypeError(Span, String),
+        IndexError(Span, String),
+        ShapeError(Span, String),
+        ValueError(Span, String),
+        FileError(Span, String),
+
+        // Raised by `raise`, keeping the kind and span of a caught error that is raised again
+        Raised(Box<ErrorValue>),
+
+        // Any of the above with extra labels, notes or help attached
+        Detailed(Box<Errors>, Box<Details>),
+
+        // Not an error, `sys.exit(code)` on its way out of the program
+        // It goes through `try` and function calls untouched and is never reported
+        Exit(i32),
+    }
+
+    // The extra parts of a diagnostic, attached with `code`, `label`, `note`, `help`, `fix` and `trace`
+    #[derive(Debug, Clone, PartialEq, Default)]
+    pub struct Details {
+        pub code: Option<&'static str>,
+        pub labels: Vec<Label>,
+        pub notes: Vec<String>,
+        pub help: Vec<String>,
+        pub fixes: Vec<Fix>,
+        pub trace: Vec<Frame>,
     }
 
     impl Errors {
-        fn report_header(kind: &str, line: &usize) {
-            println!("--> {kind} at line {line}:");
+        fn detailed(error: Errors, details: Details) -> Self {
+            Errors::Detailed(Box::new(error), Box::new(details))
+        }
+
+        // Splits off the details so they can be added to
+        fn details(self) -> (Errors, Details) {
+            match self {
+                Errors::Detailed(error, details) => (*error, *details),
+                error => (error, Details::default()),
+            }
+        }
+
+        // Gives the error a more specific code than its kind has by default
+        pub fn code(self, code: &'static str) -> Self {
+            let (error, mut details) = self.details();
+            details.code = Some(code);
+            Self::detailed(error, details)
+        }
+
+        // Returns the code from the catalogue in `codes`, e.g. `D0101`
+        pub fn error_code(&self) -> &'static str {
+            match self {
+                Errors::IdentifierError(..) => codes::UNDEFINED_NAME,
+                Errors::SyntaxError(..) => codes::SYNTAX,
+                Errors::ArgumentError(..) => codes::ARGUMENT_COUNT,
+                Errors::RecursionError(..) => codes::CALL_DEPTH,
+                Errors::TypeError(..) => codes::TYPE_MISMATCH,
+                Errors::IndexError(..) => codes::INDEX_RANGE,
+                Errors::ShapeError(..) => codes::MATRIX_SHAPE,
+                Errors::ValueError(..) => codes::CONVERSION,
+                Errors::FileError(..) => codes::MISSING_FILE,
+                Errors::Raised(value) => value.code,
+                Errors::Detailed(error, details) => details.code.unwrap_or_else(|| error.error_code()),
+                // Only here for completeness, an exit is the program's own doing
+                Errors::Exit(..) => codes::RAISED,
+            }
         }
 
-        fn argument_error(&self, source: Vec<String>, line: &usize, params: u8, args: u8) {
-            let content = source[line - 1];
-            let open_par = content.find("(").unwrap();
-            let close_par = content.find(")").unwrap();
-            
-            let prefix = content[0..open_par];
-            let suffix = content[close_par..content.len()];
-            let arguments = content[open_par..close_par];
+        // Adds a message pointing at another part of the source
+        pub fn label(self, span: Span, message: &str) -> Self {
+            let (error, mut details) = self.details();
+            details.labels.push(Label { span, message: message.to_string() });
+            Self::detailed(error, details)
+        }
+
+        pub fn note(self, note: &str) -> Self {
+            let (error, mut details) = self.details();
+            details.notes.push(note.to_string());
+            Self::detailed(error, details)
+        }
+
+        pub fn help(self, help: &str) -> Self {
+            let (error, mut details) = self.details();
+            details.help.push(help.to_string());
+            Self::detailed(error, details)
+        }
+
+        // Suggests replacing the span with new text, the message says what the change does
+        pub fn fix(self, span: Span, replacement: &str, message: &str) -> Self {
+            let (error, mut details) = self.details();
+            details.fixes.push(Fix { span, replacement: replacement.to_string(), message: message.to_string() });
+            Self::detailed(error, details)
+        }
+
+        // Attaches the calls that were running when the error happened, innermost first
+        pub fn trace(self, trace: Vec<Frame>) -> Self {
+            let (error, mut details) = self.details();
+            details.trace = trace;
+            Self::detailed(error, details)
+        }
+
+        pub fn is_traced(&self) -> bool {
+            matches!(self, Errors::Detailed(_, details) if !details.trace.is_empty())
+        }
+
+        // Returns the error as a value for `catch`, the trace and any help are left behind
+        pub fn value(&self) -> ErrorValue {
+            if let Errors::Raised(value) = self {
+                return *value.clone();
+            }
 
-            let report_line = format!("{prefix}{arguments}{suffix}");
-            let report_spaces = " ".repeat(prefix.len() + 1);
-            let report_arrows = "^".repeat(arguments.len());
-            let report_msg = format!("{params} parameters were specific but {args} arguments were passed.");
+            let diagnostic = self.undecorated();
+            ErrorValue { kind: diagnostic.kind, message: diagnostic.message, span: diagnostic.span, code: self.error_code() }
+        }
 
-            // Print the error
-            Self::report_header("ArgumentError", line);
-            println!(" | offending line:");
-            println!(" | {report_line}");
-            println!(" | {report_spaces}{report_arrows} {report_msg}");
+        // Builds the diagnostic that gets rendered for this error
+        pub fn diagnostic(&self) -> Diagnostic {
+            let mut diagnostic = self.undecorated();
+            diagnostic.code = Some(self.error_code().to_string());
+            diagnostic
         }
 
-        pub fn report(&self, source: Vec<String>) {
-            // Determine error type
+        fn undecorated(&self) -> Diagnostic {
             match self {
-                Errors::IdentifierError(line, offender) => {
-                    todo!();
+                Errors::IdentifierError(span, offender) => {
+                    Diagnostic::new("IdentifierError", &format!("`{offender}` is not defined"), *span)
+                        .with_label("not found in this scope")
                 },
-                Errors::SyntaxError(line, offender) => {
-                    todo!();
-                },
-                Errors::ArgumentError(line, params, args) => {
+                Errors::SyntaxError(span, msg) => Diagnostic::new("SyntaxError", msg, *span),
+                Errors::ArgumentError(span, msg) => {
                     // Example:
                     // my_function(5, 10, 2)
-                    //             ^^^^^^^^ 2 parameters specified, but 3 arguments were given
-                    
-                    self.argument_error(source, line, params, args);
+                    //            ^^^^^^^^^^ 2 parameters specified, but 3 arguments were given
+                    Diagnostic::new("ArgumentError", msg, *span).with_label("these arguments")
+                },
+                Errors::RecursionError(span, msg) => Diagnostic::new("RecursionError", msg, *span),
+                Errors::TypeError(span, msg) => Diagnostic::new("TypeError", msg, *span),
+                Errors::IndexError(span, msg) => Diagnostic::new("IndexError", msg, *span),
+                Errors::ShapeError(span, msg) => Diagnostic::new("ShapeError", msg, *span),
+                Errors::ValueError(span, msg) => Diagnostic::new("ValueError", msg, *span),
+                Errors::FileError(span, msg) => Diagnostic::new("FileError", msg, *span),
+                Errors::Raised(value) => Diagnostic::new(&value.kind, &value.message, value.span),
+                Errors::Detailed(error, details) => {
+                    let mut diagnostic = error.undecorated();
+                    for label in &details.labels {
+                        diagnostic = diagnostic.with_secondary(label.span, &label.message);
+                    }
+                    for note in &details.notes {
+                        diagnostic = diagnostic.with_note(note);
+                    }
+                    for help in &details.help {
+                        diagnostic = diagnostic.with_help(help);
+                    }
+                    for fix in &details.fixes {
+                        diagnostic = diagnostic.with_fix(fix.clone());
+                    }
+                    diagnostic.with_trace(details.trace.clone())
                 },
+                Errors::Exit(code) => Diagnostic::new("Exit", &format!("the program exited with code {code}"), Span::nowhere()),
             }
         }
     }
-}
\ No newline at end of file
+}
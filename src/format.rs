@@ -0,0 +1,264 @@
+pub mod format {
+    use crate::{
+        ast::ast::Ast,
+        error::errors::Errors,
+        lexer::lexer::Lexer,
+        source::source::SourceMap,
+        tokens::tokens::{Token, TokenKind},
+    };
+
+    const INDENT: &str = "    ";
+
+    /*
+        Returns the file written the one way `darcy fmt` writes DarcyLang:
+
+        - blocks are indented by four spaces, with `elif`, `else`, `case`, `catch` and `end`
+          lined up with the line that opened the block
+        - a line that leaves a bracket open indents the lines after it until it's closed
+        - operators, `=`, `<-` and `->` get a space either side, commas and semicolons one after
+        - at most one blank line in a row, and none at the start or end of a block or the file
+        - no trailing spaces, and one newline at the end
+
+        Only the space between tokens changes, every token (comments included) is written
+        as it was read, so strings and numbers keep their escapes and underscores.
+        A file that doesn't lex or parse is given back as its errors, as there's no knowing
+        where its blocks start and end.
+    */
+    pub fn format(source: &SourceMap) -> Result<String, Vec<Errors>> {
+        let mut lexer = Lexer::new(source).with_comments();
+        let (tokens, errors) = lexer.scan();
+        if !errors.is_empty() {
+            return Err(errors.clone());
+        }
+        let tokens = tokens.clone();
+
+        let code: Vec<Token> = tokens.iter().filter(|t| !matches!(t.kind, TokenKind::Comment(_))).cloned().collect();
+        Ast::new(code).build()?;
+
+        // The tokens of each line, a blank line has none
+        let mut lines = vec![Vec::new()];
+        for token in tokens.iter().filter(|t| t.kind != TokenKind::EndOfFile) {
+            match token.kind {
+                TokenKind::Newline => lines.push(Vec::new()),
+                _ => lines.last_mut().unwrap().push(token.clone()),
+            }
+        }
+
+        let mut out: Vec<String> = Vec::new();
+        let (mut depth, mut brackets) = (0_usize, 0_usize);
+        // The bracket count to get back to for each line of brackets still open
+        let mut levels: Vec<usize> = Vec::new();
+        // The brackets open at each point, so a `:` knows whether it's in a map
+        let mut open: Vec<TokenKind> = Vec::new();
+        let mut opened = false;
+
+        for line in &lines {
+            if line.is_empty() {
+                if !opened && out.last().is_some_and(|last| !last.is_empty()) {
+                    out.push(String::new());
+                }
+                continue;
+            }
+
+            // `end` (and `elif`, `else`, `case` and `catch`) go back to the level of the line that opened the block
+            let dedents = matches!(line[0].kind, TokenKind::End | TokenKind::Elif | TokenKind::Else | TokenKind::Case | TokenKind::Catch);
+            let block = if dedents { depth.saturating_sub(1) } else { depth };
+            if line[0].kind == TokenKind::End && out.last().is_some_and(String::is_empty) {
+                out.pop();
+            }
+
+            // A line starting with closing brackets goes back to the level of the line that opened them
+            let closers = line.iter().take_while(|t| matches!(t.kind, TokenKind::RPar | TokenKind::RBrac | TokenKind::RCurl)).count();
+            let continued = levels.iter().filter(|&&level| level < brackets.saturating_sub(closers)).count();
+
+            let before = depth;
+            let mut lowest = brackets;
+            for i in 0..line.len() {
+                Ast::count(line, i, &mut depth, &mut brackets);
+                lowest = lowest.min(brackets);
+            }
+            levels.retain(|&level| level < lowest);
+            if brackets > lowest {
+                levels.push(lowest);
+            }
+            opened = depth > before;
+
+            out.push(format!("{}{}", INDENT.repeat(block + continued), self::line(source, line, &mut open)));
+        }
+
+        while out.last().is_some_and(String::is_empty) {
+            out.pop();
+        }
+        let mut text = out.join("\n");
+        text.push('\n');
+        Ok(text)
+    }
+
+    // Writes out the tokens of one line with the spacing between them
+    fn line(source: &SourceMap, tokens: &[Token], open: &mut Vec<TokenKind>) -> String {
+        let mut text = String::new();
+        // Whether the last token is written right up against the next one
+        let mut tight = true;
+
+        for (i, token) in tokens.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|i| &tokens[i].kind);
+            let spaced = match &token.kind {
+                _ if i == 0 => false,
+                TokenKind::Comment(_) => true,
+                TokenKind::RPar | TokenKind::RBrac | TokenKind::RCurl | TokenKind::Comma | TokenKind::Semicolon
+                    | TokenKind::Dot | TokenKind::Colon => false,
+                // A call or an index, rather than a bracket after a keyword or an operator
+                TokenKind::LPar | TokenKind::LBrac if prev.is_some_and(ends_value) => false,
+                _ => !tight,
+            };
+            if spaced {
+                text.push(' ');
+            }
+            text.push_str(&written(source, token));
+
+            tight = match &token.kind {
+                TokenKind::LPar | TokenKind::LBrac | TokenKind::LCurl | TokenKind::Dot | TokenKind::At
+                    | TokenKind::Hash | TokenKind::Ampersand => true,
+                // `-x` and `!x`, but `not x`
+                TokenKind::Minus => !prev.is_some_and(ends_value),
+                TokenKind::Bang => token.lex == "!",
+                TokenKind::Colon => !key(tokens, i, open.last()),
+                _ => false,
+            };
+
+            match token.kind {
+                TokenKind::LPar | TokenKind::LBrac | TokenKind::LCurl => open.push(token.kind.clone()),
+                TokenKind::RPar | TokenKind::RBrac | TokenKind::RCurl => {
+                    open.pop();
+                },
+                _ => {},
+            }
+        }
+        text
+    }
+
+    /*
+        Returns the first line (counting from 1) where the formatted text starts to differ,
+        along with the lines that go and the lines that take their place, for `darcy fmt --check`.
+        Lines the two have in common at the end aren't included.
+    */
+    pub fn changes<'a>(before: &'a str, after: &'a str) -> Option<(usize, Vec<&'a str>, Vec<&'a str>)> {
+        let before: Vec<&str> = before.split('\n').collect();
+        let after: Vec<&str> = after.split('\n').collect();
+
+        let start = before.iter().zip(&after).take_while(|(b, a)| b == a).count();
+        if start == before.len() && start == after.len() {
+            return None;
+        }
+        let end = before[start..].iter().rev().zip(after[start..].iter().rev()).take_while(|(b, a)| b == a).count();
+        Some((start + 1, before[start..before.len() - end].to_vec(), after[start..after.len() - end].to_vec()))
+    }
+
+    /*
+        Returns whether the `:` at `i` ends a map key, which is followed by a space:
+
+        {name: "Ada", shape: Shape:Circle(2)}
+        list[1:3]
+
+        `Shape:Circle` looks like a key too, but only the first name in an entry can be one.
+    */
+    fn key(tokens: &[Token], i: usize, bracket: Option<&TokenKind>) -> bool {
+        if bracket != Some(&TokenKind::LCurl) {
+            return false;
+        }
+        let upper = |t: Option<&Token>| matches!(t.map(|t| &t.kind), Some(TokenKind::Identifier(name)) if name.starts_with(char::is_uppercase));
+        let variant = upper(i.checked_sub(1).map(|i| &tokens[i])) && upper(tokens.get(i + 1));
+        let entry = i < 2 || matches!(tokens[i - 2].kind, TokenKind::LCurl | TokenKind::Comma);
+        entry || !variant
+    }
+
+    // Whether a token can end an operand, so a `-` after it takes something away and a `(` after it calls it
+    fn ends_value(kind: &TokenKind) -> bool {
+        matches!(
+            kind,
+            TokenKind::Identifier(_) | TokenKind::NumberLiteral(_) | TokenKind::StringLiteral(_) | TokenKind::True
+                | TokenKind::False | TokenKind::Nil | TokenKind::RPar | TokenKind::RBrac | TokenKind::RCurl,
+        )
+    }
+
+    // The token as it's written in the source, the lexeme of a string or number has lost its escapes and underscores
+    fn written(source: &SourceMap, token: &Token) -> String {
+        match &token.kind {
+            TokenKind::Comment(text) => text.clone(),
+            _ => source.line(token.line).unwrap_or("").chars().skip(token.col - 1).take(token.len).collect(),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::format;
+        use crate::{lexer::lexer::Lexer, source::source::SourceMap, tokens::tokens::TokenKind};
+
+        fn formatted(text: &str) -> String {
+            format(&SourceMap::new(text.to_string())).unwrap()
+        }
+
+        // Every token but the newlines, comments included
+        fn kinds(text: &str) -> Vec<TokenKind> {
+            let source = SourceMap::new(text.to_string());
+            let mut lexer = Lexer::new(&source).with_comments();
+            let (tokens, _) = lexer.scan();
+            tokens.iter().map(|t| t.kind.clone()).filter(|k| *k != TokenKind::Newline).collect()
+        }
+
+        const MESSY: &[&str] = &[
+            "5 + 5 ;",
+            "x=5\n\n\n\nif x>5   \n  out \"big\"\nelif x==5\n\t\tout \"five\"\nelse\nout  \"small\"\nend\n\n\n",
+            "// area\nfunc area<-height,width\n\n  return height*width // the area\n\n\nend\nout area( 5,10 )",
+            "m = matrix\n[5, 10, 0]\n  [6, 20, 0]\nend\nout m[1 , 2]",
+            "for i in range(0,3)\nmatch i\ncase 0\nout -i\nelse\ntry\nraise \"odd\"\ncatch e\nout not e\nend\nend\nend",
+            "xs = [1,\n2,\n      3\n]\nd = {a:1,\n b : Option:Some(-2)}\nout xs[1:2]\ndouble = func <- x -> x*2",
+            "@allow(unused_variable)\nconst TOTAL = 1_000\nout \"tab\\there\"",
+        ];
+
+        #[test]
+        fn formatting_twice_changes_nothing() {
+            for text in MESSY {
+                let once = formatted(text);
+                assert_eq!(formatted(&once), once, "formatting this again changed it:\n{once}");
+            }
+        }
+
+        #[test]
+        fn only_spacing_changes() {
+            for text in MESSY {
+                assert_eq!(kinds(&formatted(text)), kinds(text));
+            }
+        }
+
+        #[test]
+        fn blocks_are_indented() {
+            assert_eq!(
+                formatted("if x>5\nfor i in xs\n    out i\n  end\nelif x == 5\nout x\nend\n"),
+                "if x > 5\n    for i in xs\n        out i\n    end\nelif x == 5\n    out x\nend\n",
+            );
+            assert_eq!(formatted("xs = [1,\n2\n  ]\n"), "xs = [1,\n    2\n]\n");
+        }
+
+        #[test]
+        fn operators_are_spaced() {
+            assert_eq!(formatted("x=-1+f( 2 ,3 )[0]*-y"), "x = -1 + f(2, 3)[0] * -y\n");
+            assert_eq!(formatted("func  add<-a,b\nreturn a+b\nend"), "func add <- a, b\n    return a + b\nend\n");
+            assert_eq!(formatted("m = {a:1, b : Option:Some(2)}\nout m.keys(); out xs[1 : 2]"), "m = {a: 1, b: Option:Some(2)}\nout m.keys(); out xs[1:2]\n");
+        }
+
+        #[test]
+        fn blank_lines_collapse_and_comments_stay() {
+            assert_eq!(
+                formatted("\n\n// top\nx = 1   // one\n\n\n\nif x\n\n  // inside\n  out x\n\nend\n\n"),
+                "// top\nx = 1 // one\n\nif x\n    // inside\n    out x\nend\n",
+            );
+        }
+
+        #[test]
+        fn broken_files_are_not_formatted() {
+            assert!(format(&SourceMap::new("if x\nout x\n".to_string())).is_err());
+            assert!(format(&SourceMap::new("out \"open\n".to_string())).is_err());
+        }
+    }
+}
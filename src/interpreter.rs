@@ -0,0 +1,1692 @@
+pub mod interpreter {
+    use crate::{
+        ast::nodes::*,
+        codes::codes,
+        diagnostic::diagnostic::{Frame, Span},
+        error::errors::Errors,
+        lexer::lexer::KEYWORDS,
+        matrix::matrix::Matrix,
+        scope::{scope::{Environment, GlobalEnvironment}, values::{Enum, ErrorValue, Function, Map, NilOrigin, Type, Value, Variant}},
+        source::source::Sources,
+        suggest::suggest,
+    };
+    use std::rc::Rc;
+
+    // Functions that are called by name without being defined, handled in `call`
+    const BUILTINS: &[&str] = &["identity", "int", "num", "str"];
+
+    // The names `prelude` defines before a program starts
+    pub const PRELUDE: &[&str] = &["Option", "sys"];
+
+    // The most calls that can be running at once, past this a call raises a RecursionError
+    // `main` gives the interpreter a stack large enough to get this deep
+    pub const MAX_DEPTH: usize = 1000;
+
+    // Interpreter walks the AST and evaluates each node
+    // Values are stored in the environment given by `env` (0 for GE)
+    pub struct Interpreter<'a> {
+        pub glbl_env: &'a mut GlobalEnvironment,
+        pub env: usize,
+
+        // For saying which file a line is in when a message mentions one
+        sources: &'a Sources,
+
+        // Set by `return` so the blocks it is nested in stop running
+        returning: Option<Type>,
+
+        // The function calls running right now, outermost first
+        stack: Vec<Frame>,
+
+        // What `sys.args()` gives, the arguments after the program on the command line
+        args: Vec<String>,
+    }
+
+    impl<'a> Interpreter<'a> {
+        pub fn new(glbl_env: &'a mut GlobalEnvironment, sources: &'a Sources) -> Self {
+            let mut interpreter = Self { glbl_env, env: 0, sources, returning: None, stack: Vec::new(), args: Vec::new() };
+            interpreter.prelude();
+            interpreter
+        }
+
+        pub fn with_args(mut self, args: Vec<String>) -> Self {
+            self.args = args;
+            self
+        }
+
+        // Defines the names every program starts with
+        fn prelude(&mut self) {
+            // `Option:Some(value)` or `Option:None`, for results that may be missing
+            let option = Enum {
+                name: "Option".to_string(),
+                variants: vec![("Some".to_string(), true), ("None".to_string(), false)],
+            };
+            self.glbl_env.set("Option", Value::new(Type::Enum(option), 0));
+
+            // `sys.args()`, `sys.env(name)` and `sys.exit(code)`, for talking to whatever ran the program
+            self.glbl_env.set("sys", Value::new(Type::Module("sys"), 0));
+        }
+
+        // Runs every node in order
+        // Stops at the first runtime error or once a `return` has run
+        pub fn run(&mut self, nodes: &[Node]) -> Result<(), Errors> {
+            for node in nodes {
+                self.evaluate(node)?;
+                if self.returning.is_some() {
+                    break;
+                }
+            }
+            Ok(())
+        }
+
+        // Runs every node like `run`, giving back the value of the last one if it is an expression
+        // The REPL echoes it, statements such as `x = 1` or `out x` have no value to show
+        pub fn run_value(&mut self, nodes: &[Node]) -> Result<Option<Type>, Errors> {
+            let Some((last, rest)) = nodes.split_last() else {
+                return Ok(None);
+            };
+            self.run(rest)?;
+
+            let value = self.evaluate(last)?;
+            Ok(match last {
+                Node::Assign(_) | Node::Destructure(_) | Node::Out(_) | Node::If(_) | Node::Match(_) | Node::For(_)
+                    | Node::Func(_) | Node::Enum(_) | Node::Return(_) | Node::Raise(_) | Node::Try(_) | Node::Include(_) => None,
+                _ => Some(value),
+            })
+        }
+
+        fn evaluate(&mut self, node: &Node) -> Result<Type, Errors> {
+            match node {
+                Node::Literal(n) => Ok(match &n.literal {
+                    Literal::Interger(i) => Type::Integer(*i),
+                    Literal::Float(f) => Type::Number(*f),
+                    Literal::String(s) => Type::Str(s.clone()),
+                    Literal::Bool(b) => Type::Bool(*b),
+                    Literal::Nil => Type::nil(),
+                }),
+                Node::Identifier(n) => match self.glbl_env.get(self.env, &n.name) {
+                    Some(value) => Ok(value.typ.clone()),
+                    None => Err(self.undefined(&n.name, n.span)),
+                },
+                Node::List(n) => {
+                    let mut items = Vec::with_capacity(n.items.len());
+                    for item in &n.items {
+                        items.push(self.evaluate(item)?);
+                    }
+                    Ok(Type::List(items))
+                },
+                Node::Tuple(n) => Ok(Type::Tuple(self.arguments(&n.items)?)),
+                Node::Map(n) => {
+                    let mut map = Map::default();
+                    for (key, value) in &n.entries {
+                        let key = self.evaluate(key)?;
+                        Map::check_key(&key).map_err(|e| Errors::TypeError(n.line.into(), e))?;
+                        map.insert(key, self.evaluate(value)?);
+                    }
+                    Ok(Type::Map(map))
+                },
+                Node::Variant(n) => self.variant(n),
+                Node::Matrix(n) => self.matrix(n),
+                Node::Unary(n) => {
+                    let right = self.evaluate(&n.right)?;
+                    match (&n.op, right) {
+                        (UnaryOp::Negative, Type::Nil(origin)) => Err(Self::nil_error(&n.right, &origin, "negated", n.line)),
+                        (UnaryOp::Negative, Type::Integer(i)) => i.checked_neg().map(Type::Integer).ok_or_else(|| Errors::TypeError(n.line.into(), "integer overflow".to_string())),
+                        (UnaryOp::Negative, Type::Number(f)) => Ok(Type::Number(-f)),
+                        (UnaryOp::Negative, Type::Matrix(m)) => Ok(Type::Matrix(m.scale(-1.0))),
+                        (UnaryOp::Negative, other) => Err(Errors::TypeError(n.line.into(), format!("cannot negate a value of type {}", other.name()))),
+                        (UnaryOp::Not, Type::Bool(b)) => Ok(Type::Bool(!b)),
+                        (UnaryOp::Not, other) => Err(Errors::TypeError(n.line.into(), format!("`not` expects a Bool, found a value of type {}", other.name()))),
+                        (UnaryOp::Field, Type::Variant(v)) => match v.field {
+                            Some(field) => Ok(*field),
+                            None => Err(Errors::TypeError(n.line.into(), format!("`{}:{}` has no field to take with `&`", v.enum_name, v.name))),
+                        },
+                        (UnaryOp::Field, Type::Nil(origin)) => Err(Self::nil_error(&n.right, &origin, "used with `&`", n.line)),
+                        (UnaryOp::Field, other) => Err(Errors::TypeError(n.line.into(), format!("`&` takes the field of an enum variant, found a value of type {}", other.name()))),
+                    }
+                },
+                Node::Compare(n) => {
+                    let left = self.evaluate(&n.left)?;
+                    let right = self.evaluate(&n.right)?;
+                    if !matches!(n.op, CompareOp::Equal | CompareOp::NotEqual) {
+                        let action = format!("used with `{}`", Self::compare_symbol(&n.op));
+                        Self::not_nil(&n.left, &left, &action, n.line)?;
+                        Self::not_nil(&n.right, &right, &action, n.line)?;
+                    }
+                    Self::compare(&n.op, &left, &right, n.line).map(Type::Bool)
+                },
+                Node::Logical(n) => {
+                    // The right side only runs if the left side doesn't already decide the result
+                    let left = self.condition(&n.left, Self::logical_name(&n.op), n.line)?;
+                    let result = match (&n.op, left) {
+                        (LogicalOp::And, false) => false,
+                        (LogicalOp::Or, true) => true,
+                        _ => self.condition(&n.right, Self::logical_name(&n.op), n.line)?,
+                    };
+                    Ok(Type::Bool(result))
+                },
+                Node::Binary(n) => {
+                    let left = self.evaluate(&n.left)?;
+                    let right = self.evaluate(&n.right)?;
+                    let action = format!("used with `{}`", Self::binary_symbol(&n.op));
+                    Self::not_nil(&n.left, &left, &action, n.line)?;
+                    Self::not_nil(&n.right, &right, &action, n.line)?;
+                    self.binary(&n.op, left, right, n.line)
+                },
+                Node::Index(n) => self.index(n),
+                Node::Slice(n) => self.slice(n),
+                Node::Interpolation(n) => {
+                    let mut text = String::new();
+                    for part in &n.parts {
+                        match part {
+                            Part::Text(t) => text.push_str(t),
+                            Part::Value(node, spec) => {
+                                let value = self.evaluate(node)?;
+                                text.push_str(&Self::format(&value, spec, n.line)?);
+                            },
+                        }
+                    }
+                    Ok(Type::Str(text))
+                },
+                Node::Call(n) => self.call(n),
+                Node::Method(n) => self.method(n),
+                Node::Assign(n) => {
+                    let value = self.evaluate(&n.value)?;
+                    // `const` always defines the name where it is, rather than assigning to an outer one
+                    if n.constant {
+                        self.glbl_env.set(&n.name, Value::new(value.clone(), self.env));
+                    } else {
+                        self.glbl_env.assign(self.env, &n.name, value.clone());
+                    }
+                    Ok(value)
+                },
+                Node::Destructure(n) => {
+                    let value = self.evaluate(&n.value)?;
+                    self.bind(&n.pattern, value.clone(), false, n.line)?;
+                    Ok(value)
+                },
+                Node::Out(n) => {
+                    let value = self.evaluate(&n.value)?;
+                    println!("{value}");
+                    Ok(value)
+                },
+                Node::If(n) => {
+                    for (condition, body) in &n.branches {
+                        if self.condition(condition, "an `if` condition", n.line)? {
+                            self.run(body)?;
+                            return Ok(Type::nil());
+                        }
+                    }
+
+                    if let Some(body) = &n.otherwise {
+                        self.run(body)?;
+                    }
+                    Ok(Type::nil())
+                },
+                Node::Match(n) => self.match_statement(n),
+                Node::For(n) => self.for_loop(n),
+                Node::Func(n) => {
+                    self.glbl_env.capture(self.env);
+                    let function = Type::Function(Function { node: Rc::new(n.clone()), env: self.env });
+                    self.glbl_env.set(&n.name, Value::new(function.clone(), self.env));
+                    Ok(function)
+                },
+                Node::Enum(n) => {
+                    let declaration = Type::Enum(Enum { name: n.name.clone(), variants: n.variants.clone() });
+                    self.glbl_env.set(&n.name, Value::new(declaration.clone(), self.env));
+                    Ok(declaration)
+                },
+                // Anonymous functions capture the environment they are created in
+                Node::Lambda(n) => {
+                    self.glbl_env.capture(self.env);
+                    Ok(Type::Function(Function { node: Rc::new(n.clone()), env: self.env }))
+                },
+                Node::Raise(n) => Err(match self.evaluate(&n.value)? {
+                    Type::Str(message) => Errors::Raised(Box::new(ErrorValue {
+                        kind: "Error".to_string(),
+                        message,
+                        span: Span::line(n.line),
+                        code: codes::RAISED,
+                    })),
+                    // A caught error is raised again as it was
+                    Type::Error(e) => Errors::Raised(Box::new(e)),
+                    other => Errors::TypeError(n.line.into(), format!(
+                        "`raise` expects a Str message or a caught Error, found a value of type {}",
+                        other.name(),
+                    )),
+                }),
+                Node::Try(n) => {
+                    // Every runtime error is caught, from the body or any function it calls
+                    // `sys.exit` isn't an error, so the program still ends
+                    match self.run(&n.body) {
+                        Err(Errors::Exit(code)) => return Err(Errors::Exit(code)),
+                        Err(error) => {
+                            let caught = Type::Error(error.value());
+                            self.scoped(self.env, |this| {
+                                if let Some(binding) = &n.binding {
+                                    this.glbl_env.set(&binding.name, Value::new(caught, this.env));
+                                }
+                                this.run(&n.handler)
+                            })?;
+                        },
+                        Ok(()) => {},
+                    }
+                    Ok(Type::nil())
+                },
+                // The included files run in the environment of the include, so their names stay defined
+                Node::Include(n) => {
+                    self.run(&n.body)?;
+                    Ok(Type::nil())
+                },
+                Node::Return(n) => {
+                    let value = match &n.value {
+                        Some(value) => self.evaluate(value)?,
+                        None => Type::nil(),
+                    };
+                    self.returning = Some(value.clone());
+                    Ok(value)
+                },
+            }
+        }
+
+        // Runs a block of nodes in a new environment that is a child of `parent`
+        // The current environment is restored afterwards, even if the block fails,
+        // and the new one is freed unless a function made inside still needs it
+        fn scoped<T>(&mut self, parent: usize, run: impl FnOnce(&mut Self) -> Result<T, Errors>) -> Result<T, Errors> {
+            let previous = self.env;
+            self.env = Environment::new(self.glbl_env, parent);
+            let result = run(self);
+            self.glbl_env.release(self.env);
+            self.env = previous;
+            result
+        }
+
+        /*
+            Binds a value to the names in a pattern, unpacking tuples (and lists) as it goes.
+            `define` creates the names in the current environment (loop variables),
+            otherwise they are assigned like `x = value` would.
+        */
+        fn bind(&mut self, pattern: &Pattern, value: Type, define: bool, line: usize) -> Result<(), Errors> {
+            match pattern {
+                Pattern::Name(name) => {
+                    if define {
+                        self.glbl_env.set(name, Value::new(value, self.env));
+                    } else {
+                        self.glbl_env.assign(self.env, name, value);
+                    }
+                    Ok(())
+                },
+                Pattern::Tuple(patterns) => {
+                    let items = match value {
+                        Type::Tuple(items) | Type::List(items) => items,
+                        other => return Err(Errors::TypeError(line.into(), format!(
+                            "cannot unpack a value of type {} into {} names",
+                            other.name(), patterns.len(),
+                        ))),
+                    };
+
+                    if items.len() != patterns.len() {
+                        return Err(Errors::TypeError(line.into(), format!(
+                            "cannot unpack {} values into {} names",
+                            items.len(), patterns.len(),
+                        )));
+                    }
+
+                    for (pattern, item) in patterns.iter().zip(items) {
+                        self.bind(pattern, item, define, line)?;
+                    }
+                    Ok(())
+                },
+            }
+        }
+
+        // Runs the body once for each item, every pass gets its own environment
+        fn for_loop(&mut self, node: &ForNode) -> Result<Type, Errors> {
+            let items = match self.evaluate(&node.iterable)? {
+                Type::List(items) | Type::Tuple(items) => items,
+                Type::Str(s) => s.chars().map(|c| Type::Str(c.to_string())).collect(),
+                // Maps give `(key, value)` tuples, in insertion order
+                Type::Map(map) => map.entries().iter()
+                    .map(|(k, v)| Type::Tuple(vec![k.clone(), v.clone()]))
+                    .collect(),
+                Type::Matrix(m) => (0..m.rows)
+                    .map(|r| Type::List(m.row(r).into_iter().map(Type::Number).collect()))
+                    .collect(),
+                other => return Err(Errors::TypeError(node.line.into(), format!("cannot loop over a value of type {}", other.name()))),
+            };
+
+            for item in items {
+                self.scoped(self.env, |this| {
+                    this.bind(&node.pattern, item, true, node.line)?;
+                    this.run(&node.body)
+                })?;
+
+                if self.returning.is_some() {
+                    break;
+                }
+            }
+
+            Ok(Type::nil())
+        }
+
+        // Returns whether the variant has a field, or an error if the enum doesn't declare it
+        fn declared_variant(&self, enum_name: &str, name: &str, line: usize) -> Result<bool, Errors> {
+            let declaration = match self.glbl_env.get(self.env, enum_name).map(|v| &v.typ) {
+                Some(Type::Enum(declaration)) => declaration,
+                Some(other) => return Err(Errors::TypeError(line.into(), format!(
+                    "`{enum_name}` is a value of type {}, not an enum",
+                    other.name(),
+                ))),
+                None => return Err(self.undefined(enum_name, line.into())),
+            };
+
+            match declaration.variants.iter().find(|(v, _)| v == name) {
+                Some((_, field)) => Ok(*field),
+                None => {
+                    let variants: Vec<String> = declaration.variants.iter().map(|(v, _)| format!("`{v}`")).collect();
+                    Err(Errors::TypeError(line.into(), format!(
+                        "`{enum_name}` has no variant `{name}`, expected one of {}",
+                        variants.join(", "),
+                    )))
+                },
+            }
+        }
+
+        fn variant(&mut self, node: &VariantNode) -> Result<Type, Errors> {
+            let has_field = self.declared_variant(&node.enum_name, &node.name, node.line)?;
+            let field = match &node.field {
+                Some(field) => Some(Box::new(self.evaluate(field)?)),
+                None => None,
+            };
+
+            match (has_field, &field) {
+                (true, None) => Err(Errors::TypeError(node.line.into(), format!(
+                    "`{0}:{1}` has a field, e.g. `{0}:{1}(value)`",
+                    node.enum_name, node.name,
+                ))),
+                (false, Some(_)) => Err(Errors::TypeError(node.line.into(), format!("`{}:{}` has no field", node.enum_name, node.name))),
+                _ => Ok(Type::Variant(Variant { enum_name: node.enum_name.clone(), name: node.name.clone(), field })),
+            }
+        }
+
+        // Runs the body of the first case that fits, each case body gets its own environment
+        fn match_statement(&mut self, node: &MatchNode) -> Result<Type, Errors> {
+            let value = self.evaluate(&node.value)?;
+
+            for (pattern, body) in &node.cases {
+                // Some(binding) if the case fits
+                let fits = match pattern {
+                    CasePattern::Variant { enum_name, name, binding } => {
+                        let has_field = self.declared_variant(enum_name, name, node.line)?;
+                        if binding.is_some() && !has_field {
+                            return Err(Errors::TypeError(node.line.into(), format!("`{enum_name}:{name}` has no field to bind")));
+                        }
+
+                        match &value {
+                            Type::Variant(v) if v.enum_name == *enum_name && v.name == *name => {
+                                Some(binding.as_ref().zip(v.field.clone()))
+                            },
+                            _ => None,
+                        }
+                    },
+                    CasePattern::Value(expected) => {
+                        let expected = self.evaluate(expected)?;
+                        Self::compare(&CompareOp::Equal, &value, &expected, node.line)?.then_some(None)
+                    },
+                };
+
+                if let Some(binding) = fits {
+                    self.scoped(self.env, |this| {
+                        if let Some((name, field)) = binding {
+                            this.glbl_env.set(name, Value::new(*field, this.env));
+                        }
+                        this.run(body)
+                    })?;
+                    return Ok(Type::nil());
+                }
+            }
+
+            if let Some(body) = &node.otherwise {
+                self.run(body)?;
+            }
+            Ok(Type::nil())
+        }
+
+        fn logical_name(op: &LogicalOp) -> &'static str {
+            match op {
+                LogicalOp::And => "each side of `and`",
+                LogicalOp::Or => "each side of `or`",
+            }
+        }
+
+        // Builds the error for a name that isn't defined, suggesting the closest name that is
+        // Candidates are the names visible from here, the built-ins and the keywords
+        fn undefined(&self, name: &str, span: Span) -> Errors {
+            let error = Errors::IdentifierError(span, name.to_string());
+            let names = self.glbl_env.names(self.env);
+            let candidates = names.into_iter().chain(BUILTINS.iter().copied()).chain(KEYWORDS.iter().copied());
+
+            let Some(suggestion) = suggest::closest(name, candidates) else {
+                return error;
+            };
+
+            let message = if KEYWORDS.contains(&suggestion) {
+                format!("did you mean the keyword `{suggestion}`?")
+            } else {
+                format!("did you mean `{suggestion}`?")
+            };
+
+            // A replacement needs to know exactly where the name is
+            if span.has_column() {
+                error.fix(span, suggestion, &message)
+            } else {
+                error.help(&message)
+            }
+        }
+
+        // Returns where a node is, if it knows
+        // Only names know their columns, everything else marks its whole line
+        fn span_of(node: &Node) -> Option<Span> {
+            let line = match node {
+                Node::Identifier(n) => return Some(n.span),
+                Node::Binary(n) => n.line,
+                Node::Compare(n) => n.line,
+                Node::Logical(n) => n.line,
+                Node::Unary(n) => n.line,
+                Node::Index(n) => n.line,
+                Node::Call(n) => n.line,
+                Node::Method(n) => n.line,
+                Node::Variant(n) => n.line,
+                _ => return None,
+            };
+            Some(Span::line(line))
+        }
+
+        // Returns an expression written back out the way it would appear in the source, for error messages
+        // Falls back to "this value" for anything longer than a name, literal, index or call
+        fn describe(node: &Node) -> String {
+            match Self::source_text(node) {
+                Some(text) => format!("`{text}`"),
+                None => "this value".to_string(),
+            }
+        }
+
+        fn source_text(node: &Node) -> Option<String> {
+            let list = |nodes: &[Node]| -> String {
+                let items: Option<Vec<String>> = nodes.iter().map(Self::source_text).collect();
+                match items {
+                    Some(items) => items.join(", "),
+                    None => "...".to_string(),
+                }
+            };
+
+            Some(match node {
+                Node::Identifier(n) => n.name.clone(),
+                Node::Literal(n) => match &n.literal {
+                    Literal::Interger(i) => i.to_string(),
+                    Literal::Float(f) => f.to_string(),
+                    Literal::String(s) => format!("{s:?}"),
+                    Literal::Bool(b) => b.to_string(),
+                    Literal::Nil => "nil".to_string(),
+                },
+                Node::List(n) => format!("[{}]", list(&n.items)),
+                Node::Index(n) => format!("{}[{}]", Self::source_text(&n.target)?, list(&n.indices)),
+                Node::Method(n) => format!("{}.{}({})", Self::source_text(&n.target)?, n.name, list(&n.args)),
+                Node::Call(n) => {
+                    let args: Vec<Node> = n.args.iter().map(|a| a.value.clone()).collect();
+                    format!("{}({})", Self::source_text(&n.callee)?, list(&args))
+                },
+                Node::Unary(UnaryNode { op: UnaryOp::Field, right, .. }) => format!("&{}", Self::source_text(right)?),
+                Node::Variant(n) => match &n.field {
+                    Some(field) => format!("{}:{}({})", n.enum_name, n.name, Self::source_text(field)?),
+                    None => format!("{}:{}", n.enum_name, n.name),
+                },
+                _ => return None,
+            })
+        }
+
+        /*
+            Builds the error for a nil value used where it can't be, e.g.
+
+            TypeError: `total` is nil, so it can't be used with `+`
+              = note: the nil came from `scores.max()` on line 3, the list is empty
+
+            The origin is only known for nils DarcyLang made itself (missing keys, empty lists, functions without a return).
+        */
+        fn nil_error(node: &Node, origin: &NilOrigin, action: &str, line: usize) -> Errors {
+            let span = Self::span_of(node).unwrap_or(line.into());
+            let error = Errors::TypeError(span, format!("{} is nil, so it can't be {action}", Self::describe(node)))
+                .code(codes::NIL_VALUE);
+            match &origin.0 {
+                Some(origin) => error.note(&format!("the nil came from {origin}")),
+                None => error.help("check for it first, e.g. `x != nil`"),
+            }
+        }
+
+        // Returns the nil error if a value that's about to be used as a number is nil
+        fn not_nil(node: &Node, value: &Type, action: &str, line: usize) -> Result<(), Errors> {
+            match value {
+                Type::Nil(origin) => Err(Self::nil_error(node, origin, action, line)),
+                _ => Ok(()),
+            }
+        }
+
+        fn binary_symbol(op: &BinaryOp) -> &'static str {
+            match op {
+                BinaryOp::Plus => "+",
+                BinaryOp::Minus => "-",
+                BinaryOp::Multiply => "*",
+                BinaryOp::Divide => "/",
+            }
+        }
+
+        fn compare_symbol(op: &CompareOp) -> &'static str {
+            match op {
+                CompareOp::Equal => "==",
+                CompareOp::NotEqual => "!=",
+                CompareOp::Less => "<",
+                CompareOp::LessEqual => "<=",
+                CompareOp::More => ">",
+                CompareOp::MoreEqual => ">=",
+            }
+        }
+
+        /*
+            Evaluates a node that is used as a condition.
+            Only Bool values are allowed, there is no "truthiness" so `if count`
+            has to be written `if count != 0`.
+        */
+        fn condition(&mut self, node: &Node, what: &str, line: usize) -> Result<bool, Errors> {
+            match self.evaluate(node)? {
+                Type::Bool(b) => Ok(b),
+                other => {
+                    let span = Self::span_of(node).unwrap_or(line.into());
+                    let error = Errors::TypeError(span, format!("{what} must be a Bool, found a value of type {}", other.name()))
+                        .code(codes::NOT_A_BOOL);
+                    let hint = match other {
+                        Type::Integer(_) | Type::Number(_) => "compare it instead, e.g. `x != 0`",
+                        Type::List(_) | Type::Tuple(_) | Type::Str(_) => "check its length instead, e.g. `x.len() > 0`",
+                        Type::Nil(_) => "compare it instead, e.g. `x != nil`",
+                        Type::Variant(_) => "use `is_some()` or a `match` instead",
+                        _ => return Err(error),
+                    };
+                    Err(error.help(hint))
+                },
+            }
+        }
+
+        // Evaluates the Bool result of a predicate passed to a higher-order method
+        fn predicate(&mut self, callee: &Type, item: &Type, method: &str, span: Span) -> Result<bool, Errors> {
+            match self.call_with(callee, vec![item.clone()], method, span)? {
+                Type::Bool(b) => Ok(b),
+                other => Err(Errors::TypeError(span, format!(
+                    "the function passed to `{method}` must return a Bool, but it returned a value of type {}",
+                    other.name(),
+                ))),
+            }
+        }
+
+        fn compare(op: &CompareOp, left: &Type, right: &Type, line: usize) -> Result<bool, Errors> {
+            // Int and Num compare by value
+            if let (Some(a), Some(b)) = (left.as_number(), right.as_number()) {
+                return Ok(match op {
+                    CompareOp::Equal => a == b,
+                    CompareOp::NotEqual => a != b,
+                    CompareOp::Less => a < b,
+                    CompareOp::LessEqual => a <= b,
+                    CompareOp::More => a > b,
+                    CompareOp::MoreEqual => a >= b,
+                });
+            }
+
+            match (op, left, right) {
+                (CompareOp::Equal, l, r) => Ok(l == r),
+                (CompareOp::NotEqual, l, r) => Ok(l != r),
+                (op, Type::Str(a), Type::Str(b)) => Ok(match op {
+                    CompareOp::Less => a < b,
+                    CompareOp::LessEqual => a <= b,
+                    CompareOp::More => a > b,
+                    _ => a >= b,
+                }),
+                (op, l, r) => Err(Errors::TypeError(line.into(), format!(
+                    "cannot order a value of type {} and a value of type {} with `{}`",
+                    l.name(), r.name(), Self::compare_symbol(op),
+                ))),
+            }
+        }
+
+        // Evaluates every argument in order
+        fn arguments(&mut self, args: &[Node]) -> Result<Vec<Type>, Errors> {
+            args.iter().map(|a| self.evaluate(a)).collect()
+        }
+
+        fn binary(&mut self, op: &BinaryOp, left: Type, right: Type, line: usize) -> Result<Type, Errors> {
+            let shape = |e: String| Errors::ShapeError(line.into(), e);
+
+            // Nothing can be divided by zero, whether it's an Int, a Num or a matrix
+            let divisible = left.as_number().is_some() || matches!(left, Type::Matrix(_));
+            if *op == BinaryOp::Divide && divisible && right.as_number() == Some(0.0) {
+                return Err(Errors::TypeError(line.into(), "division by zero".to_string()).code(codes::DIVISION_BY_ZERO));
+            }
+
+            match (op, left, right) {
+                // Integers stay integers (including division, which truncates)
+                (op, Type::Integer(a), Type::Integer(b)) => {
+                    let result = match op {
+                        BinaryOp::Plus => a.checked_add(b),
+                        BinaryOp::Minus => a.checked_sub(b),
+                        BinaryOp::Multiply => a.checked_mul(b),
+                        BinaryOp::Divide => a.checked_div(b),
+                    };
+                    result.map(Type::Integer).ok_or_else(|| Errors::TypeError(line.into(), "integer overflow".to_string()))
+                },
+
+                // Strings join with `+` and repeat with `*`
+                (BinaryOp::Plus, Type::Str(a), Type::Str(b)) => Ok(Type::Str(a + &b)),
+                (BinaryOp::Plus, Type::Str(_), other) | (BinaryOp::Plus, other, Type::Str(_)) => {
+                    Err(Errors::TypeError(line.into(), format!(
+                        "cannot add a Str and a value of type {}, put the value in the string instead, e.g. \"total: {{x}}\"",
+                        other.name(),
+                    )))
+                },
+                (BinaryOp::Multiply, Type::Str(s), Type::Integer(n)) | (BinaryOp::Multiply, Type::Integer(n), Type::Str(s)) => {
+                    if n < 0 {
+                        return Err(Errors::TypeError(line.into(), format!("cannot repeat a string {n} times")));
+                    }
+                    Ok(Type::Str(s.repeat(n as usize)))
+                },
+
+                // Matrix arithmetic
+                (BinaryOp::Plus, Type::Matrix(a), Type::Matrix(b)) => a.add(&b, 1.0).map(Type::Matrix).map_err(shape),
+                (BinaryOp::Minus, Type::Matrix(a), Type::Matrix(b)) => a.add(&b, -1.0).map(Type::Matrix).map_err(shape),
+                (BinaryOp::Multiply, Type::Matrix(a), Type::Matrix(b)) => a.multiply(&b).map(Type::Matrix).map_err(shape),
+                (BinaryOp::Multiply, Type::Matrix(m), k) | (BinaryOp::Multiply, k, Type::Matrix(m)) if k.as_number().is_some() => {
+                    Ok(Type::Matrix(m.scale(k.as_number().unwrap())))
+                },
+                (BinaryOp::Divide, Type::Matrix(m), k) if k.as_number().is_some() => {
+                    Ok(Type::Matrix(m.scale(1.0 / k.as_number().unwrap())))
+                },
+
+                // Any other pair of numbers becomes a Num
+                (op, left, right) => match (left.as_number(), right.as_number()) {
+                    (Some(a), Some(b)) => Ok(Type::Number(match op {
+                        BinaryOp::Plus => a + b,
+                        BinaryOp::Minus => a - b,
+                        BinaryOp::Multiply => a * b,
+                        BinaryOp::Divide => a / b,
+                    })),
+                    _ => Err(Errors::TypeError(line.into(), format!(
+                        "cannot {} a value of type {} and a value of type {}",
+                        match op {
+                            BinaryOp::Plus => "add",
+                            BinaryOp::Minus => "subtract",
+                            BinaryOp::Multiply => "multiply",
+                            BinaryOp::Divide => "divide",
+                        },
+                        left.name(), right.name(),
+                    ))),
+                },
+            }
+        }
+
+        // Builds a matrix from the rows of a `matrix ... end` block
+        fn matrix(&mut self, node: &MatrixNode) -> Result<Type, Errors> {
+            let mut rows = Vec::with_capacity(node.rows.len());
+
+            for row in &node.rows {
+                let mut entries = Vec::with_capacity(row.len());
+                for entry_node in row {
+                    let entry = self.evaluate(entry_node)?;
+                    Self::not_nil(entry_node, &entry, "used as a matrix entry", node.line)?;
+                    match entry.as_number() {
+                        Some(x) => entries.push(x),
+                        None => return Err(Errors::TypeError(node.line.into(), format!("matrix entries must be numbers, found a value of type {}", entry.name()))),
+                    }
+                }
+                rows.push(entries);
+            }
+
+            Matrix::new(rows).map(Type::Matrix).map_err(|e| Errors::ShapeError(node.line.into(), e))
+        }
+
+        // Converts a DarcyLang index to a 0-based index
+        // `base` is 1 for matrices and 0 for lists
+        fn position(index: &Type, len: usize, base: usize, what: &str, line: usize) -> Result<usize, Errors> {
+            let i = match index {
+                Type::Integer(i) => *i,
+                other => return Err(Errors::TypeError(line.into(), format!("{what} indices must be of type Int, found {}", other.name()))),
+            };
+
+            if len == 0 {
+                return Err(Errors::IndexError(line.into(), format!("{what} index {i} is out of range; there is nothing to index")));
+            }
+            if i < base as i32 || i as usize >= len + base {
+                return Err(Errors::IndexError(line.into(), format!(
+                    "{what} index {i} is out of range; valid indices are {} to {}",
+                    base, len + base - 1,
+                )));
+            }
+
+            Ok(i as usize - base)
+        }
+
+        fn index(&mut self, node: &IndexNode) -> Result<Type, Errors> {
+            let target = self.evaluate(&node.target)?;
+            let indices = self.arguments(&node.indices)?;
+            let line = node.line;
+
+            match (&target, indices.as_slice()) {
+                // Matrices are indexed from 1, `m[row]` or `m[row, col]`
+                (Type::Matrix(m), [row]) => {
+                    let r = Self::position(row, m.rows, 1, "row", line)?;
+                    Ok(Type::List(m.row(r).into_iter().map(Type::Number).collect()))
+                },
+                (Type::Matrix(m), [row, col]) => {
+                    let r = Self::position(row, m.rows, 1, "row", line)?;
+                    let c = Self::position(col, m.cols, 1, "column", line)?;
+                    Ok(Type::Number(m.get(r, c)))
+                },
+                (Type::Matrix(m), _) => Err(Errors::IndexError(line.into(), format!(
+                    "a {} matrix takes one index (a row) or two (a row and a column), found {}",
+                    m.shape(), indices.len(),
+                ))),
+
+                // Lists and tuples are indexed from 0
+                (Type::List(items), [i]) => {
+                    let i = Self::position(i, items.len(), 0, "list", line)?;
+                    Ok(items[i].clone())
+                },
+                (Type::Tuple(items), [i]) => {
+                    let i = Self::position(i, items.len(), 0, "tuple", line)?;
+                    Ok(items[i].clone())
+                },
+
+                // Maps are indexed by key, a missing key gives nil
+                (Type::Map(map), [key]) => match map.get(key) {
+                    Some(value) => Ok(value.clone()),
+                    None => Ok(Type::Nil(NilOrigin::new(format!(
+                        "{} on {}, the key {} is not in the map",
+                        Self::describe(&Node::Index(node.clone())), self.sources.describe(line), key.repr(),
+                    )))),
+                },
+                (Type::Map(_), _) => Err(Errors::IndexError(line.into(), format!("a map takes exactly one key, found {}", indices.len()))),
+
+                // Strings are indexed by character from 0
+                (Type::Str(s), [i]) => {
+                    let i = Self::position(i, s.chars().count(), 0, "string", line)?;
+                    Ok(Type::Str(s.chars().nth(i).unwrap().to_string()))
+                },
+                (Type::List(_) | Type::Tuple(_) | Type::Str(_), _) => Err(Errors::IndexError(line.into(), format!(
+                    "a {} takes exactly one index, found {}",
+                    target.name().to_lowercase(), indices.len(),
+                ))),
+                (Type::Nil(origin), _) => Err(Self::nil_error(&node.target, origin, "indexed", line)),
+                (other, _) => Err(Errors::TypeError(line.into(), format!("a value of type {} cannot be indexed", other.name()))),
+            }
+        }
+
+        // Formats a value for an interpolated string
+        fn format(value: &Type, spec: &FormatSpec, line: usize) -> Result<String, Errors> {
+            let text = match (spec.precision, value.as_number()) {
+                (None, _) => value.to_string(),
+                (Some(p), Some(x)) => format!("{x:.p$}"),
+                (Some(p), None) => return Err(Errors::TypeError(line.into(), format!(
+                    "the `.{p}` format only applies to numbers, found a value of type {}",
+                    value.name(),
+                ))),
+            };
+
+            // Pad to the width, numbers line up on the right by default
+            let len = text.chars().count();
+            if len >= spec.width {
+                return Ok(text);
+            }
+            let padding = spec.width - len;
+            let align = spec.align.unwrap_or(if value.as_number().is_some() { '>' } else { '<' });
+
+            Ok(match align {
+                '>' => format!("{}{text}", " ".repeat(padding)),
+                '^' => format!("{}{text}{}", " ".repeat(padding / 2), " ".repeat(padding - padding / 2)),
+                _ => format!("{text}{}", " ".repeat(padding)),
+            })
+        }
+
+        // Returns a slice bound as a position between 0 and len
+        fn bound(&mut self, node: &Option<Box<Node>>, default: usize, len: usize, line: usize) -> Result<usize, Errors> {
+            let node = match node {
+                Some(node) => node,
+                None => return Ok(default),
+            };
+
+            match self.evaluate(node)? {
+                Type::Integer(i) if i >= 0 && i as usize <= len => Ok(i as usize),
+                Type::Integer(i) => Err(Errors::IndexError(line.into(), format!("slice bound {i} is out of range; valid bounds are 0 to {len}"))),
+                other => Err(Errors::TypeError(line.into(), format!("slice bounds must be of type Int, found {}", other.name()))),
+            }
+        }
+
+        // `target[start:end]` on strings (by character), lists and tuples
+        fn slice(&mut self, node: &SliceNode) -> Result<Type, Errors> {
+            let target = self.evaluate(&node.target)?;
+            let len = match &target {
+                Type::Str(s) => s.chars().count(),
+                Type::List(items) | Type::Tuple(items) => items.len(),
+                other => return Err(Errors::TypeError(node.line.into(), format!("a value of type {} cannot be sliced", other.name()))),
+            };
+
+            let start = self.bound(&node.start, 0, len, node.line)?;
+            let end = self.bound(&node.end, len, len, node.line)?;
+            if start > end {
+                return Err(Errors::IndexError(node.line.into(), format!("slice start {start} is after the end {end}")));
+            }
+
+            Ok(match target {
+                Type::Str(s) => Type::Str(s.chars().skip(start).take(end - start).collect()),
+                Type::Tuple(items) => Type::Tuple(items[start..end].to_vec()),
+                Type::List(items) => Type::List(items[start..end].to_vec()),
+                _ => unreachable!(),
+            })
+        }
+
+        // Checks the number of arguments passed to a built-in
+        fn arity(args: &[Type], params: usize, span: Span) -> Result<(), Errors> {
+            if args.len() != params {
+                return Err(Errors::ArgumentError(span, format!(
+                    "{params} parameters were specified but {} arguments were passed.",
+                    args.len(),
+                )));
+            }
+            Ok(())
+        }
+
+        // Methods that change the value they're called on write it back to the variable
+        fn write_back(&mut self, node: &MethodNode, updated: Type) -> Result<(), Errors> {
+            match &*node.target {
+                Node::Identifier(id) => {
+                    self.glbl_env.assign(self.env, &id.name, updated);
+                    Ok(())
+                },
+                _ => Err(Errors::TypeError(node.line.into(), format!(
+                    "`{}` changes the value it is called on, so it has to be called on a variable",
+                    node.name,
+                ))),
+            }
+        }
+
+        // Returns the argument as a string or a TypeError naming the method
+        fn expect_str<'v>(value: &'v Type, method: &str, line: usize) -> Result<&'v str, Errors> {
+            match value {
+                Type::Str(s) => Ok(s),
+                other => Err(Errors::TypeError(line.into(), format!("`{method}` expects a Str, found a value of type {}", other.name()))),
+            }
+        }
+
+        // Returns the argument as a matrix or a TypeError naming the function
+        fn expect_matrix(value: &Type, function: &str, line: usize) -> Result<Matrix, Errors> {
+            match value {
+                Type::Matrix(m) => Ok(m.clone()),
+                other => Err(Errors::TypeError(line.into(), format!("`{function}` expects a Matrix, found a value of type {}", other.name()))),
+            }
+        }
+
+        // Calls a function value, or a built-in if the callee is a name that isn't defined
+        fn call(&mut self, node: &CallNode) -> Result<Type, Errors> {
+            let line = node.line;
+            let span = node.args_span;
+
+            let builtin = match &*node.callee {
+                Node::Identifier(id) if self.glbl_env.get(self.env, &id.name).is_none() => Some(id),
+                _ => None,
+            };
+
+            let mut args = Vec::with_capacity(node.args.len());
+            for arg in &node.args {
+                args.push((arg.label.clone(), self.evaluate(&arg.value)?));
+            }
+
+            let (name, name_span) = match builtin {
+                Some(id) => (id.name.as_str(), id.span),
+                None => {
+                    let callee = self.evaluate(&node.callee)?;
+                    return self.call_value(&callee, args, span);
+                },
+            };
+
+            // Built-ins only take positional arguments
+            if let Some((Some(label), _)) = args.iter().find(|(label, _)| label.is_some()) {
+                return Err(Errors::ArgumentError(span, format!(
+                    "the built-in `{name}` does not take labelled arguments, remove `{label} as`",
+                )).code(codes::ARGUMENT_LABEL));
+            }
+            let args: Vec<Type> = args.into_iter().map(|(_, value)| value).collect();
+
+            match name {
+                "identity" => {
+                    Self::arity(&args, 1, span)?;
+                    match args[0] {
+                        Type::Integer(n) if n > 0 => Matrix::identity(n as usize).map(Type::Matrix).map_err(|e| Errors::ShapeError(line.into(), e)),
+                        _ => Err(Errors::TypeError(line.into(), "`identity` expects a positive Int for the size".to_string())),
+                    }
+                },
+                "int" | "num" | "str" => {
+                    Self::arity(&args, 1, span)?;
+                    Self::convert(name, &args[0], line)
+                },
+                _ => Err(self.undefined(name, name_span)),
+            }
+        }
+
+        /*
+            `int`, `num` and `str` convert a value to that type.
+            Strs are parsed (ignoring spaces around them), and a Num is cut down to an Int
+            towards zero. Anything else, or a Str that isn't a number, is a ValueError.
+        */
+        fn convert(to: &str, value: &Type, line: usize) -> Result<Type, Errors> {
+            let fail = || Errors::ValueError(line.into(), format!("cannot convert {} to {}", value.repr(), match to {
+                "int" => "an Int",
+                _ => "a Num",
+            }));
+
+            match (to, value) {
+                ("str", value) => Ok(Type::Str(value.to_string())),
+                ("int", Type::Integer(i)) => Ok(Type::Integer(*i)),
+                ("int", Type::Number(n)) if n.is_finite() && n.abs() < i32::MAX as f64 => Ok(Type::Integer(*n as i32)),
+                ("int", Type::Str(s)) => s.trim().parse().map(Type::Integer).map_err(|_| fail()),
+                ("num", Type::Integer(i)) => Ok(Type::Number(*i as f64)),
+                ("num", Type::Number(n)) => Ok(Type::Number(*n)),
+                ("num", Type::Str(s)) => s.trim().parse().map(Type::Number).map_err(|_| fail()),
+                _ => Err(fail()),
+            }
+        }
+
+        /*
+            Matches the arguments of a call to the parameters of the function:
+
+            1. Positional arguments fill parameters in order, and must all come first
+            2. Labelled arguments fill the parameter with that label
+            3. Anything left over takes its default value (or is missing)
+        */
+        fn call_function(&mut self, function: &Function, args: Vec<(Option<String>, Type)>, span: Span) -> Result<Type, Errors> {
+            let node = &function.node;
+            let params = &node.params;
+            let mut slots: Vec<Option<Type>> = vec![None; params.len()];
+            let mut labelled = false;
+
+            let count = args.len();
+            for (i, (label, value)) in args.into_iter().enumerate() {
+                let slot = match &label {
+                    None if labelled => return Err(Errors::ArgumentError(span, format!(
+                        "positional arguments must come before labelled ones in the call to `{}`",
+                        node.name,
+                    )).code(codes::ARGUMENT_LABEL)),
+                    None if i >= params.len() => return Err(Errors::ArgumentError(span, format!(
+                        "{} parameters were specified but {} arguments were passed.",
+                        params.len(), count,
+                    ))),
+                    None => i,
+                    Some(label) => {
+                        labelled = true;
+                        match params.iter().position(|p| p.external() == label) {
+                            Some(slot) => slot,
+                            None => return Err(Errors::ArgumentError(span, Self::unknown_label(node, label)).code(codes::ARGUMENT_LABEL)),
+                        }
+                    },
+                };
+
+                if slots[slot].is_some() {
+                    return Err(Errors::ArgumentError(span, format!(
+                        "`{}` was given more than once in the call to `{}`",
+                        params[slot].external(), node.name,
+                    )).code(codes::ARGUMENT_LABEL));
+                }
+                slots[slot] = Some(value);
+            }
+
+            if let Some(missing) = params.iter().zip(&slots).find(|(p, s)| s.is_none() && p.default.is_none()) {
+                return Err(Errors::ArgumentError(span, format!(
+                    "missing an argument for `{}` in the call to `{}`",
+                    missing.0.external(), node.name,
+                )));
+            }
+
+            // Runaway recursion stops here, rather than when Rust runs out of stack
+            if self.stack.len() >= MAX_DEPTH {
+                return Err(Errors::RecursionError(span, format!(
+                    "calls are nested too deeply, {MAX_DEPTH} are already running",
+                )).trace(self.stack.iter().rev().cloned().collect()));
+            }
+
+            // Run the body in a new environment inside the one the function was defined in
+            // An error from inside gets the calls that led to it, unless a call further in already added them
+            let name = (!node.anonymous).then(|| node.name.clone());
+            self.stack.push(Frame { function: name, span });
+            let result = self.scoped(function.env, |this| {
+                for (param, slot) in params.iter().zip(slots) {
+                    let value = match slot {
+                        Some(value) => value,
+                        // Defaults are evaluated at call time and can see earlier parameters
+                        None => this.evaluate(param.default.as_ref().unwrap())?,
+                    };
+                    this.glbl_env.set(&param.name, Value::new(value, this.env));
+                }
+
+                let result = this.run(&node.body);
+                let returned = this.returning.take();
+                result?;
+                Ok(returned.unwrap_or_else(|| Type::Nil(NilOrigin::new(format!(
+                    "`{}()` on {}, which finished without a `return`",
+                    node.name, this.sources.describe(span.line),
+                )))))
+            }).map_err(|error| match error.is_traced() || matches!(error, Errors::Exit(_)) {
+                true => error,
+                false => error.trace(self.stack.iter().rev().cloned().collect()),
+            });
+            self.stack.pop();
+            result
+        }
+
+        // Calls a value with positional and labelled arguments
+        // Returns a TypeError if the value is not a function
+        fn call_value(&mut self, callee: &Type, args: Vec<(Option<String>, Type)>, span: Span) -> Result<Type, Errors> {
+            match callee {
+                Type::Function(function) => self.call_function(function, args, span),
+                other => Err(Errors::TypeError(span, format!("a value of type {} cannot be called", other.name()))),
+            }
+        }
+
+        // Calls the function argument of a higher-order method with positional arguments
+        fn call_with(&mut self, callee: &Type, args: Vec<Type>, method: &str, span: Span) -> Result<Type, Errors> {
+            if !matches!(callee, Type::Function(_)) {
+                return Err(Errors::TypeError(span, format!("`{method}` expects a Func, found a value of type {}", callee.name())));
+            }
+            self.call_value(callee, args.into_iter().map(|a| (None, a)).collect(), span)
+        }
+
+        // Builds the message for a label that matches no parameter
+        fn unknown_label(node: &FuncNode, label: &str) -> String {
+            // Point out when the caller used the internal name of a labelled parameter
+            if let Some(param) = node.params.iter().find(|p| p.name == label && p.label.is_some()) {
+                return format!(
+                    "`{label}` is the name `{}` uses inside, callers pass it as `{} as ...`",
+                    node.name, param.external(),
+                );
+            }
+
+            let labels: Vec<String> = node.params.iter().map(|p| format!("`{}`", p.external())).collect();
+            if labels.is_empty() {
+                format!("`{}` has no parameter labelled `{label}`, it takes no arguments", node.name)
+            } else {
+                format!("`{}` has no parameter labelled `{label}`, expected one of {}", node.name, labels.join(", "))
+            }
+        }
+
+        // Calls a built-in method on the value of the target
+        fn method(&mut self, node: &MethodNode) -> Result<Type, Errors> {
+            let target = self.evaluate(&node.target)?;
+            let args = self.arguments(&node.args)?;
+            let line = node.line;
+            let span = node.args_span;
+            let shape = |e: String| {
+                let singular = e.starts_with("the matrix is singular");
+                let error = Errors::ShapeError(line.into(), e);
+                if singular { error.code(codes::SINGULAR_MATRIX) } else { error }
+            };
+
+            match (&target, node.name.as_str()) {
+                (Type::Matrix(m), "transpose") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Matrix(m.transpose()))
+                },
+                (Type::Matrix(m), "determinant") => {
+                    Self::arity(&args, 0, span)?;
+                    m.determinant().map(Type::Number).map_err(shape)
+                },
+                (Type::Matrix(m), "inverse") => {
+                    Self::arity(&args, 0, span)?;
+                    m.inverse().map(Type::Matrix).map_err(shape)
+                },
+                (Type::Matrix(m), "rank") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Integer(m.rank() as i32))
+                },
+                (Type::Matrix(m), "solve") => {
+                    Self::arity(&args, 1, span)?;
+                    let b = Self::expect_matrix(&args[0], "solve", line)?;
+                    m.solve(&b).map(Type::Matrix).map_err(shape)
+                },
+                (Type::Matrix(m), "rows") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Integer(m.rows as i32))
+                },
+                (Type::Matrix(m), "cols") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Integer(m.cols as i32))
+                },
+                (Type::List(items) | Type::Tuple(items), "len") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Integer(items.len() as i32))
+                },
+                (Type::List(items), "max" | "min") => {
+                    // An empty list has no largest item, so it gives nil
+                    Self::arity(&args, 0, span)?;
+                    let op = if node.name == "max" { CompareOp::More } else { CompareOp::Less };
+                    let mut best = match items.first() {
+                        Some(first) => first,
+                        None => return Ok(Type::Nil(NilOrigin::new(format!(
+                            "{} on {}, the list is empty",
+                            Self::describe(&Node::Method(node.clone())), self.sources.describe(line),
+                        )))),
+                    };
+                    for item in &items[1..] {
+                        if Self::compare(&op, item, best, line)? {
+                            best = item;
+                        }
+                    }
+                    Ok(best.clone())
+                },
+
+                // Option methods
+                (Type::Variant(v), "is_some" | "is_none") if v.enum_name == "Option" => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Bool((v.name == "Some") == (node.name == "is_some")))
+                },
+                (Type::Variant(v), "unwrap_or") if v.enum_name == "Option" => {
+                    Self::arity(&args, 1, span)?;
+                    Ok(v.field.as_deref().cloned().unwrap_or_else(|| args[0].clone()))
+                },
+                (Type::Variant(v), "unwrap") if v.enum_name == "Option" => {
+                    Self::arity(&args, 0, span)?;
+                    match &v.field {
+                        Some(field) => Ok(*field.clone()),
+                        None => Err(Errors::TypeError(line.into(), format!(
+                            "called `unwrap` on {}, which is `Option:None`; use `unwrap_or(default)` or a `match` instead",
+                            Self::describe(&node.target),
+                        ))),
+                    }
+                },
+
+                // Error methods, for errors caught with `catch err`
+                (Type::Error(e), "message" | "kind" | "code") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Str(match node.name.as_str() {
+                        "message" => e.message.clone(),
+                        "kind" => e.kind.clone(),
+                        _ => e.code.to_string(),
+                    }))
+                },
+                // The line within its own file, which `file()` names
+                (Type::Error(e), "line") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Integer(self.sources.local(e.span.line) as i32))
+                },
+                (Type::Error(e), "file") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Str(self.sources.file(self.sources.file_of(e.span.line)).name.clone()))
+                },
+
+                // Map methods
+                (Type::Map(map), "len") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Integer(map.len() as i32))
+                },
+                (Type::Map(map), "insert") => {
+                    Self::arity(&args, 2, span)?;
+                    Map::check_key(&args[0]).map_err(|e| Errors::TypeError(line.into(), e))?;
+                    let mut map = map.clone();
+                    map.insert(args[0].clone(), args[1].clone());
+                    self.write_back(node, Type::Map(map))?;
+                    Ok(args[1].clone())
+                },
+                (Type::Map(map), "remove") => {
+                    // Returns the value that was removed
+                    Self::arity(&args, 1, span)?;
+                    let mut map = map.clone();
+                    let removed = match map.remove(&args[0]) {
+                        Some(value) => value,
+                        None => return Err(Errors::IndexError(line.into(), format!("the key {} is not in the map", args[0].repr()))),
+                    };
+                    self.write_back(node, Type::Map(map))?;
+                    Ok(removed)
+                },
+                (Type::Map(map), "contains") => {
+                    Self::arity(&args, 1, span)?;
+                    Ok(Type::Bool(map.get(&args[0]).is_some()))
+                },
+                (Type::Map(map), "get") => {
+                    // `get(key)` gives an Option, `get(key, default)` gives the value or the default
+                    match args.as_slice() {
+                        [key] => Ok(map.get(key).cloned().map(Type::some).unwrap_or_else(Type::none)),
+                        [key, default] => Ok(map.get(key).cloned().unwrap_or_else(|| default.clone())),
+                        _ => Err(Errors::ArgumentError(span, format!(
+                            "`get` takes a key and an optional default, but {} arguments were passed.",
+                            args.len(),
+                        ))),
+                    }
+                },
+                (Type::Map(map), "keys") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::List(map.entries().iter().map(|(k, _)| k.clone()).collect()))
+                },
+                (Type::Map(map), "values") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::List(map.entries().iter().map(|(_, v)| v.clone()).collect()))
+                },
+                (Type::Map(map), "items") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::List(map.entries().iter().map(|(k, v)| Type::Tuple(vec![k.clone(), v.clone()])).collect()))
+                },
+
+                // String methods
+                (Type::Str(s), "len") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Integer(s.chars().count() as i32))
+                },
+                (Type::Str(s), "upper") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Str(s.to_uppercase()))
+                },
+                (Type::Str(s), "lower") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Str(s.to_lowercase()))
+                },
+                (Type::Str(s), "trim") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::Str(s.trim().to_string()))
+                },
+                (Type::Str(s), "split") => {
+                    // With no separator, splits on any run of whitespace
+                    let parts: Vec<Type> = match args.as_slice() {
+                        [] => s.split_whitespace().map(|p| Type::Str(p.to_string())).collect(),
+                        [sep] => {
+                            let sep = Self::expect_str(sep, "split", line)?;
+                            if sep.is_empty() {
+                                return Err(Errors::TypeError(line.into(), "`split` needs a separator that isn't empty".to_string()));
+                            }
+                            s.split(sep).map(|p| Type::Str(p.to_string())).collect()
+                        },
+                        _ => return Err(Errors::ArgumentError(span, format!(
+                            "`split` takes a separator or nothing, but {} arguments were passed.",
+                            args.len(),
+                        ))),
+                    };
+                    Ok(Type::List(parts))
+                },
+                (Type::Str(s), "join") => {
+                    // `", ".join(items)` puts the string between each item
+                    Self::arity(&args, 1, span)?;
+                    match &args[0] {
+                        Type::List(items) | Type::Tuple(items) => {
+                            let items: Vec<String> = items.iter().map(|i| i.to_string()).collect();
+                            Ok(Type::Str(items.join(s)))
+                        },
+                        other => Err(Errors::TypeError(line.into(), format!("`join` expects a List, found a value of type {}", other.name()))),
+                    }
+                },
+                (Type::Str(s), "replace") => {
+                    Self::arity(&args, 2, span)?;
+                    let from = Self::expect_str(&args[0], "replace", line)?;
+                    let to = Self::expect_str(&args[1], "replace", line)?;
+                    Ok(Type::Str(s.replace(from, to)))
+                },
+                (Type::Str(s), "find") => {
+                    // Returns the character index of the first match, or -1
+                    Self::arity(&args, 1, span)?;
+                    let needle = Self::expect_str(&args[0], "find", line)?;
+                    Ok(Type::Integer(match s.find(needle) {
+                        Some(byte) => s[..byte].chars().count() as i32,
+                        None => -1,
+                    }))
+                },
+                (Type::Str(s), "starts_with") => {
+                    Self::arity(&args, 1, span)?;
+                    Ok(Type::Bool(s.starts_with(Self::expect_str(&args[0], "starts_with", line)?)))
+                },
+                (Type::Str(s), "ends_with") => {
+                    Self::arity(&args, 1, span)?;
+                    Ok(Type::Bool(s.ends_with(Self::expect_str(&args[0], "ends_with", line)?)))
+                },
+                (Type::Str(s), "contains") => {
+                    Self::arity(&args, 1, span)?;
+                    Ok(Type::Bool(s.contains(Self::expect_str(&args[0], "contains", line)?)))
+                },
+
+                // Higher-order methods, each takes a function
+                (Type::List(items), "map") => {
+                    Self::arity(&args, 1, span)?;
+                    let mut mapped = Vec::with_capacity(items.len());
+                    for item in items {
+                        mapped.push(self.call_with(&args[0], vec![item.clone()], "map", span)?);
+                    }
+                    Ok(Type::List(mapped))
+                },
+                (Type::List(items), "each") => {
+                    Self::arity(&args, 1, span)?;
+                    for item in items {
+                        self.call_with(&args[0], vec![item.clone()], "each", span)?;
+                    }
+                    Ok(Type::nil())
+                },
+                (Type::List(items), "filter") => {
+                    Self::arity(&args, 1, span)?;
+                    let mut kept = Vec::new();
+                    for item in items {
+                        if self.predicate(&args[0], item, "filter", span)? {
+                            kept.push(item.clone());
+                        }
+                    }
+                    Ok(Type::List(kept))
+                },
+                (Type::List(items), "any") => {
+                    Self::arity(&args, 1, span)?;
+                    for item in items {
+                        if self.predicate(&args[0], item, "any", span)? {
+                            return Ok(Type::Bool(true));
+                        }
+                    }
+                    Ok(Type::Bool(false))
+                },
+                (Type::List(items), "all") => {
+                    Self::arity(&args, 1, span)?;
+                    for item in items {
+                        if !self.predicate(&args[0], item, "all", span)? {
+                            return Ok(Type::Bool(false));
+                        }
+                    }
+                    Ok(Type::Bool(true))
+                },
+                (Type::List(items), "reduce") => {
+                    // `reduce(f, start)` calls f(total, item) for every item
+                    Self::arity(&args, 2, span)?;
+                    let mut total = args[1].clone();
+                    for item in items {
+                        total = self.call_with(&args[0], vec![total, item.clone()], "reduce", span)?;
+                    }
+                    Ok(total)
+                },
+                // The `sys` module
+                (Type::Module("sys"), "args") => {
+                    Self::arity(&args, 0, span)?;
+                    Ok(Type::List(self.args.iter().map(|arg| Type::Str(arg.clone())).collect()))
+                },
+                (Type::Module("sys"), "env") => {
+                    // Like a map's `get`, `env(name)` gives an Option and `env(name, default)` the value or the default
+                    let (name, default) = match args.as_slice() {
+                        [name] => (name, None),
+                        [name, default] => (name, Some(default)),
+                        _ => return Err(Errors::ArgumentError(span, format!(
+                            "`env` takes a name and an optional default, but {} arguments were passed.",
+                            args.len(),
+                        ))),
+                    };
+                    let value = std::env::var(Self::expect_str(name, "env", line)?).ok().map(Type::Str);
+                    Ok(match default {
+                        Some(default) => value.unwrap_or_else(|| default.clone()),
+                        None => value.map(Type::some).unwrap_or_else(Type::none),
+                    })
+                },
+                (Type::Module("sys"), "exit") => {
+                    // `exit()` is a success, the same as the program finishing
+                    let code = match args.as_slice() {
+                        [] => 0,
+                        [Type::Integer(code)] if (0..=255).contains(code) => *code,
+                        [Type::Integer(code)] => return Err(Errors::ValueError(line.into(), format!(
+                            "`exit` takes a code from 0 to 255, found {code}",
+                        ))),
+                        [other] => return Err(Errors::TypeError(line.into(), format!("`exit` expects an Int, found a value of type {}", other.name()))),
+                        _ => return Err(Errors::ArgumentError(span, format!(
+                            "`exit` takes a code or nothing, but {} arguments were passed.",
+                            args.len(),
+                        ))),
+                    };
+                    Err(Errors::Exit(code))
+                },
+                (Type::Nil(origin), name) => Err(Self::nil_error(&node.target, origin, &format!("used with `.{name}()`"), line)),
+                (other, name) => Err(Errors::TypeError(line.into(), format!("a value of type {} has no method `{name}`", other.name()))),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::collections::HashMap;
+        use std::thread;
+
+        use super::{Interpreter, MAX_DEPTH};
+        use crate::{
+            codes::codes,
+            error::errors::Errors,
+            loader::loader::Loader,
+            scope::{scope::GlobalEnvironment, values::Type},
+            source::source::Sources,
+        };
+
+        // Runs a program, giving back how it finished and the environment it left behind
+        fn run(text: &str) -> (Result<(), Errors>, GlobalEnvironment) {
+            run_file("test.dy", text)
+        }
+
+        // Runs a program as if it was read from `name`, which is where its includes are looked for
+        fn run_file(name: &str, text: &str) -> (Result<(), Errors>, GlobalEnvironment) {
+            let mut sources = Sources::new();
+            let root = sources.add(name, None, text.to_string(), None);
+            let (nodes, _) = Loader::new(&mut sources).load(root).expect("the program should parse");
+            let mut glbl_env = GlobalEnvironment { values: HashMap::new(), children: Vec::new() };
+            let result = Interpreter::new(&mut glbl_env, &sources).run(&nodes);
+            (result, glbl_env)
+        }
+
+        // The value a program leaves in `result`
+        fn result(text: &str) -> Type {
+            let (outcome, glbl_env) = run(text);
+            if let Err(error) = outcome {
+                panic!("the program failed: {}", error.diagnostic().message);
+            }
+            glbl_env.values["result"].typ.clone()
+        }
+
+        // The error that stops a program
+        fn error(text: &str) -> Errors {
+            run(text).0.expect_err("the program should fail")
+        }
+
+        fn message(text: &str) -> String {
+            error(text).diagnostic().message
+        }
+
+        #[test]
+        fn matrices_index_from_one() {
+            assert_eq!(result("m = matrix\n[1, 2]\n[3, 4]\nend\nresult = m[1, 2]"), Type::Number(2.0));
+            assert_eq!(result("m = matrix\n[1, 2]\n[3, 4]\nend\nresult = m.determinant()"), Type::Number(-2.0));
+            assert!(message("m = matrix\n[1, 2]\nend\nresult = m[0, 1]").contains("out of range"));
+        }
+
+        #[test]
+        fn matrices_print_without_rounding_noise() {
+            let inverse = "m = matrix\n[3, 0]\n[1, 3]\nend\nresult = str(m.inverse())";
+            assert_eq!(result(inverse), Type::Str("[0.333333333333, 0]\n[-0.111111111111, 0.333333333333]".to_string()));
+            assert_eq!(result("m = matrix\n[1, 2, 3]\n[0, 1, 4]\n[5, 6, 0]\nend\nresult = m.determinant()"), Type::Number(1.0));
+            let huge = "result = nil\ntry\nout identity(100000)\ncatch e\nresult = e.code()\nend";
+            assert_eq!(result(huge), Type::Str(codes::MATRIX_SHAPE.to_string()));
+        }
+
+        #[test]
+        fn negating_the_smallest_integer_overflows() {
+            assert_eq!(message("out -(-2147483647 - 1)"), "integer overflow");
+            assert_eq!(result("result = -(-2147483647)"), Type::Integer(2147483647));
+        }
+
+        #[test]
+        fn tuples_destructure() {
+            assert_eq!(result("a, (b, c) = (1, (2, 3))\nresult = a + b * c"), Type::Integer(7));
+            assert_eq!(result("t = ((1, 2), 3)\n(q, w), e = t\nresult = q * 100 + w * 10 + e"), Type::Integer(123));
+            assert_eq!(result("a = 2\nresult = (a + 1) * 2"), Type::Integer(6));
+            assert_eq!(result("result = 0\nfor (x, y) in [(1, 2), (3, 4)]\nresult += x * y\nend"), Type::Integer(14));
+            assert!(message("a, b = (1, 2, 3)").contains("cannot unpack 3 values into 2 names"));
+        }
+
+        #[test]
+        fn arguments_take_labels_and_defaults() {
+            let area = "func area <- height, width = 1\nreturn height * width\nend\n";
+            assert_eq!(result(&format!("{area}result = area(width as 10, height as 5)")), Type::Integer(50));
+            assert_eq!(result(&format!("{area}result = area(7)")), Type::Integer(7));
+            assert_eq!(result("func f <- p #perimeter\nreturn p\nend\nresult = f(perimeter as 3)"), Type::Integer(3));
+            assert_eq!(error(&format!("{area}area(5, height as 2)")).error_code(), codes::ARGUMENT_LABEL);
+        }
+
+        #[test]
+        fn closures_keep_their_scope() {
+            let counter = "func make_counter\ncount = 0\nreturn func\ncount += 1\nreturn count\nend\nend\n";
+            assert_eq!(result(&format!("{counter}next = make_counter()\nnext()\nresult = next()")), Type::Integer(2));
+            assert_eq!(result("double = func <- x -> x * 2\nresult = [1, 2].map(double)"), Type::List(vec![Type::Integer(2), Type::Integer(4)]));
+        }
+
+        #[test]
+        fn finished_scopes_are_freed() {
+            let (outcome, glbl_env) = run("func g <- n\nreturn n * 2\nend\nfunc f <- n\nreturn g(n) + 1\nend\nfor i in [1, 2, 3, 4, 5]\nx = f(i)\nend");
+            assert!(outcome.is_ok());
+            assert!(glbl_env.children.is_empty(), "{} environments were left behind", glbl_env.children.len());
+
+            // Each closure keeps the call that made it, and the loop pass before it stays too
+            let (_, glbl_env) = run("func make\nn = 0\nreturn func -> n\nend\nfor i in [1, 2, 3]\ng = make()\nend");
+            assert_eq!(glbl_env.children.len(), 6);
+        }
+
+        #[test]
+        fn logical_operators_short_circuit() {
+            assert_eq!(result("result = false and undefined_name"), Type::Bool(false));
+            assert_eq!(result("result = true || undefined_name"), Type::Bool(true));
+            assert_eq!(result("result = not (1 < 2) or 2 >= 2"), Type::Bool(true));
+            assert!(message("if 1\nout 1\nend").contains("Bool"));
+        }
+
+        #[test]
+        fn strings_slice_and_interpolate() {
+            assert_eq!(result("s = \"héllo\"\nresult = s[1:3] + s[:1].upper()"), Type::Str("élH".to_string()));
+            assert_eq!(result("x = 3.14159\nname = \"pi\"\nresult = \"{name:>4}={x:.2} {{}}\""), Type::Str("  pi=3.14 {}".to_string()));
+            assert_eq!(result("result = \",\".join(\"a b\".split(\" \"))"), Type::Str("a,b".to_string()));
+        }
+
+        #[test]
+        fn maps_keep_insertion_order() {
+            let settings = "settings = {\ntheme: \"dark\"\n(0, 1): 5, size: 2}\n";
+            assert_eq!(result(&format!("{settings}result = settings[(0, 1)] + settings[\"size\"]")), Type::Integer(7));
+            assert_eq!(result(&format!("{settings}settings.insert(\"theme\", \"light\")\nresult = settings.keys()[0] + settings[\"theme\"]")), Type::Str("themelight".to_string()));
+            assert!(matches!(result(&format!("{settings}result = settings[\"missing\"]")), Type::Nil(_)));
+            assert!(message("m = {[1]: 2}").contains("can't be used as a map key"));
+        }
+
+        #[test]
+        fn options_and_nil_match() {
+            let ages = "ages = {bob: 30}\nresult = 0\n";
+            let lookup = "match ages.get(name)\ncase Option:Some(age)\nresult = age\ncase Option:None\nresult = -1\nend";
+            assert_eq!(result(&format!("{ages}name = \"bob\"\n{lookup}")), Type::Integer(30));
+            assert_eq!(result(&format!("{ages}name = \"ann\"\n{lookup}")), Type::Integer(-1));
+            assert_eq!(result("result = ([].max() == nil) and &Option:Some(5) == 5"), Type::Bool(true));
+            assert!(message("best = [].max()\nout best + 1").contains("`best` is nil"));
+        }
+
+        #[test]
+        fn runtime_errors_carry_their_codes() {
+            assert_eq!(error("out nope").error_code(), codes::UNDEFINED_NAME);
+            assert_eq!(error("if 1\nout 1\nend").error_code(), codes::NOT_A_BOOL);
+            assert_eq!(error("out int(\"x\")").error_code(), codes::CONVERSION);
+        }
+
+        #[test]
+        fn undefined_names_suggest_close_ones() {
+            let fixes = |text: &str| error(text).diagnostic().fixes.into_iter().map(|f| f.replacement).collect::<Vec<_>>();
+            assert_eq!(fixes("total = 1\nout totl"), ["total"]);
+            assert_eq!(fixes("out nill"), ["nil"]);
+            assert!(fixes("out something_else").is_empty());
+        }
+
+        #[test]
+        fn errors_in_functions_carry_the_calls() {
+            let program = "func inner <- x\nreturn x + nil\nend\nfunc outer\nreturn inner(1)\nend\nout outer()";
+            let trace = error(program).diagnostic().trace;
+            let calls: Vec<(Option<&str>, usize)> = trace.iter().map(|f| (f.function.as_deref(), f.span.line)).collect();
+            assert_eq!(calls, [(Some("inner"), 5), (Some("outer"), 7)]);
+
+            // Anonymous functions have no name in the trace, even when they're stored under one
+            let trace = error("lambda = func <- x -> x + nil\nout lambda(1)").diagnostic().trace;
+            assert_eq!(trace.iter().map(|f| f.function.clone()).collect::<Vec<_>>(), [None]);
+
+            // A caught error still knows the line it was raised on, inside the call
+            assert_eq!(result("func fail\nraise \"no\"\nend\nresult = 0\ntry\nfail()\ncatch e\nresult = e.line()\nend"), Type::Integer(2));
+        }
+
+        #[test]
+        fn runaway_recursion_can_be_caught() {
+            // A test thread's stack is too small to get MAX_DEPTH deep, so this gets the one `main` uses
+            let deepest = thread::Builder::new().stack_size(crate::STACK_SIZE).spawn(|| {
+                let forever = "func down <- n\nreturn down(n + 1)\nend\n";
+                let caught = result(&format!("{forever}result = nil\ntry\ndown(0)\ncatch e\nresult = e.code()\nend"));
+                let trace = error(&format!("{forever}down(0)")).diagnostic().trace.len();
+                (caught == Type::Str(codes::CALL_DEPTH.to_string()), trace)
+            }).unwrap().join().unwrap();
+            assert_eq!(deepest, (true, MAX_DEPTH));
+        }
+
+        #[test]
+        fn dividing_by_zero_can_be_caught() {
+            let caught = |division: &str| result(&format!("m = matrix\n[1, 2]\nend\nresult = nil\ntry\nout {division}\ncatch e\nresult = e.code()\nend"));
+            for division in ["1 / 0", "1.0 / 0", "1 / 0.0", "-2.5 / -0.0", "m / 0", "m / 0.0"] {
+                assert_eq!(caught(division), Type::Str(codes::DIVISION_BY_ZERO.to_string()), "{division}");
+            }
+            assert_eq!(result("result = 1.0 / 4"), Type::Number(0.25));
+        }
+
+        #[test]
+        fn raised_errors_are_values() {
+            let program = "result = nil\ntry\nraise \"too big\"\ncatch e\nresult = \"{e.kind()}: {e.message()} ({e.code()})\"\nend";
+            assert_eq!(result(program), Type::Str(format!("Error: too big ({})", codes::RAISED)));
+            assert_eq!(error("try\nout [1][5]\ncatch e\nraise e\nend").error_code(), codes::INDEX_RANGE);
+        }
+
+        #[test]
+        fn errors_are_on_the_line_they_are_written_on() {
+            // Comments, blank lines, tabs and CRLF line endings don't move anything
+            let span = error("// setup\r\n\r\nx = 1 // one\r\n\tout  \"é\" + y\r\n").diagnostic().span;
+            assert_eq!((span.line, span.col, span.end_col), (4, 13, 14));
+        }
+
+        #[test]
+        fn caught_errors_say_where_they_are_in_an_included_file() {
+            let directory = std::env::temp_dir().join(format!("darcy-include-{}", std::process::id()));
+            std::fs::create_dir_all(&directory).unwrap();
+            std::fs::write(directory.join("lib.dy"), "// helpers\n\nfunc check <- x\nraise \"bad {x}\"\nend\n").unwrap();
+
+            let main = directory.join("main.dy");
+            let program = "x = 1\ny = 2\ninclude lib end\nresult = nil\ntry\ncheck(3)\ncatch e\nresult = (e.file(), e.line())\nend";
+            let (outcome, glbl_env) = run_file(&main.display().to_string(), program);
+            std::fs::remove_dir_all(&directory).ok();
+
+            assert!(outcome.is_ok());
+            let file = directory.join("lib.dy").display().to_string();
+            assert_eq!(glbl_env.values["result"].typ, Type::Tuple(vec![Type::Str(file), Type::Integer(4)]));
+        }
+
+        #[test]
+        fn sys_exits_and_reads_the_arguments() {
+            // `exit` ends the program even inside a try, and isn't something `catch` sees
+            let (outcome, glbl_env) = run("result = 0\ntry\nsys.exit(3)\ncatch e\nresult = 1\nend\nresult = 2");
+            assert!(matches!(outcome, Err(Errors::Exit(3))));
+            assert_eq!(glbl_env.values["result"].typ, Type::Integer(0));
+            assert!(matches!(run("sys.exit()").0, Err(Errors::Exit(0))));
+            assert_eq!(message("sys.exit(256)"), "`exit` takes a code from 0 to 255, found 256");
+
+            let mut sources = Sources::new();
+            let root = sources.add("test.dy", None, "result = sys.args()".to_string(), None);
+            let (nodes, _) = Loader::new(&mut sources).load(root).unwrap();
+            let mut glbl_env = GlobalEnvironment { values: HashMap::new(), children: Vec::new() };
+            let args = vec!["data.csv".to_string(), "--verbose".to_string()];
+            Interpreter::new(&mut glbl_env, &sources).with_args(args).run(&nodes).unwrap();
+            assert_eq!(
+                glbl_env.values["result"].typ,
+                Type::List(vec![Type::Str("data.csv".to_string()), Type::Str("--verbose".to_string())]),
+            );
+
+            assert_eq!(result("result = sys.env(\"DARCY_SURELY_UNSET\", \"none\")"), Type::Str("none".to_string()));
+            assert_eq!(result("result = sys.env(\"DARCY_SURELY_UNSET\").is_none()"), Type::Bool(true));
+        }
+    }
+}
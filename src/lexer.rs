@@ -1,181 +1,151 @@
 pub mod lexer {
-    use std::iter::Peekable;
-    use crate::{error::errors::Errors, scope::scope::GlobalEnvironment, tokens::tokens::{MatchResult, Token, TokenKind}};
+    use std::{iter::Peekable, str::CharIndices};
+    use crate::{codes::codes, error::errors::Errors, source::source::SourceMap, tokens::tokens::{Token, TokenKind}};
+
+    // Every word `match_keyword` turns into a keyword, for suggesting one when a name is misspelled
+    pub const KEYWORDS: &[&str] = &[
+        "func", "return", "as", "matrix", "out", "if", "elif", "else", "for", "in", "true", "false",
+        "nil", "match", "case", "enum", "and", "or", "not", "const", "raise", "try", "catch", "include", "end",
+    ];
 
     // Lexer struct contains data to tokenize file
-    pub struct Lexer<Iter: Iterator<Item = char>> {
-        pub chars: Peekable<Iter>,
+    // Positions are kept as byte offsets and only turned into lines and columns by the source map
+    pub struct Lexer<'a> {
+        source: &'a SourceMap,
+        chars: Peekable<CharIndices<'a>>,
         pub tokens: Vec<Token>,
-        pub line: usize,
+        // Byte offset of `current`, and of the first character of the token being read
+        offset: usize,
+        start: usize,
         pub current: char,
-        pub lines: Vec<String>,
         pub errors: Vec<Errors>,
-        pub glbl_env: GlobalEnvironment,
+        // Keep comments as tokens rather than dropping them, only the formatter wants them
+        comments: bool,
     }
 
-    impl<Iter: Iterator<Item = char>> Lexer<Iter> {
+    impl<'a> Lexer<'a> {
 
         // Creat a new lexer istance storing iter and neccesary variables
-        pub fn new(chars: Peekable<Iter>, lines: Vec<String>, glbl_env: GlobalEnvironment) -> Self {
+        pub fn new(source: &'a SourceMap) -> Self {
             Self {
-                chars,
+                source,
+                chars: source.text().char_indices().peekable(),
                 tokens: Vec::new(),
-                line: 1 as usize,
+                offset: 0_usize,
+                start: 0_usize,
                 current: ' ',
-                lines,
                 errors: Vec::new(),
-                glbl_env,
+                comments: false,
             }
         }
 
-        fn end(&mut self) {
-            self.tokens.push(Token::end(&self.line));
+        // A lexer that gives back each comment as a `Comment` token
+        // The parser doesn't expect them, so these tokens are for reading the source as written
+        pub fn with_comments(mut self) -> Self {
+            self.comments = true;
+            self
         }
 
-        // Throws an error with error.rs file
-        // Exits the process
-        fn error(&mut self, kind: Errors, offender: &str) {
-            // Throw error
-            let line_content = self.take_line(self.line);
-            match line_content.1 {
-                Some(content) => {
-                    todo!("Error handle");
-                },
-                None => {
-                    todo!("Error handle");
-                }
-            }
-        }
+        /*
+            advance() sets current to '\0' once the iterator runs out,
+            so every helper stops on the sentinel instead of having to
+            signal EOF back up to scan() itself.
 
+            scan() pushes the single EndOfFile token once it sees '\0'.
+        */
         // Attempts to advance the iterator if possible
-        // If not possible, pushes an EOF token and returns None
+        // Returns false (and sets the sentinel) if not possible
         fn advance(&mut self) -> bool {
-            if let Some(character) = self.chars.next() {
+            if let Some((offset, character)) = self.chars.next() {
+                self.offset = offset;
                 self.current = character;
-                return true;
+                true
             } else {
-                self.end();
-                return false;
+                self.offset = self.source.text().len();
+                self.current = '\0';
+                false
             }
         }
 
-        // Attempts to get the content of a line from the lines vec
-        // Returns a tuple containing the line # (0) and content (1)
-        fn take_line(&mut self, line: usize) -> (usize, Option<&String>) {
-            let content = self.lines.get(line - 4);
-            match content {
-                Some(chars) => {
-                    return (line, Some(chars));
-                },
-                None => (0usize, None),
+        // Advances the iterator if the next character matches
+        // Returns whether or not it did
+        fn advance_if(&mut self, expected: char) -> bool {
+            if self.chars.peek().map(|&(_, c)| c) == Some(expected) {
+                self.advance();
+                true
+            } else {
+                false
             }
         }
 
         // Match a given string to a valid keyword
         // Returns the token of that keyword
-        fn match_keyword(&mut self, word: &String) -> Option<Token> {          
-            match word.trim() {
-                "func" => Some(Token::new(TokenKind::Func, "func", &self.line)),
-                "out" => Some(Token::new(TokenKind::Out, "out", &self.line)),
-                "if" => Some(Token::new(TokenKind::If, "if", &self.line)),
-                "elif" => Some(Token::new(TokenKind::Elif, "elif", &self.line)),
-                "else" => Some(Token::new(TokenKind::Else, "else", &self.line)),
-                "for" => Some(Token::new(TokenKind::For, "for", &self.line)),
-                "const" => Some(Token::new(TokenKind::Const, "const", &self.line)),
-                "end" => Some(Token::new(TokenKind::End, "end", &self.line)),
+        fn match_keyword(&mut self, word: &str) -> Option<Token> {
+            match word {
+                "func" => Some(Token::new(TokenKind::Func, "func")),
+                "return" => Some(Token::new(TokenKind::Return, "return")),
+                "as" => Some(Token::new(TokenKind::As, "as")),
+                "matrix" => Some(Token::new(TokenKind::Matrix, "matrix")),
+                "out" => Some(Token::new(TokenKind::Out, "out")),
+                "if" => Some(Token::new(TokenKind::If, "if")),
+                "elif" => Some(Token::new(TokenKind::Elif, "elif")),
+                "else" => Some(Token::new(TokenKind::Else, "else")),
+                "for" => Some(Token::new(TokenKind::For, "for")),
+                "in" => Some(Token::new(TokenKind::In, "in")),
+                "true" => Some(Token::new(TokenKind::True, "true")),
+                "false" => Some(Token::new(TokenKind::False, "false")),
+                "nil" => Some(Token::new(TokenKind::Nil, "nil")),
+                "match" => Some(Token::new(TokenKind::Match, "match")),
+                "case" => Some(Token::new(TokenKind::Case, "case")),
+                "enum" => Some(Token::new(TokenKind::Enum, "enum")),
+
+                // Word forms of the logical operators
+                "and" => Some(Token::new(TokenKind::And, "and")),
+                "or" => Some(Token::new(TokenKind::Or, "or")),
+                "not" => Some(Token::new(TokenKind::Bang, "not")),
+                "const" => Some(Token::new(TokenKind::Const, "const")),
+                "raise" => Some(Token::new(TokenKind::Raise, "raise")),
+                "try" => Some(Token::new(TokenKind::Try, "try")),
+                "catch" => Some(Token::new(TokenKind::Catch, "catch")),
+                "include" => Some(Token::new(TokenKind::Include, "include")),
+                "end" => Some(Token::new(TokenKind::End, "end")),
 
                 // If word is not a keyword
                 _ => None,
             }
         }
 
-
-        // Match a word to an existing identifier in current scope
-        // Returns the token of that identifier
-        fn match_id(&mut self, word: &String) -> Option<Token> {
-            return None;
-        }
-
-        /*
-            match_word() returns MatchResult because default Option 'None'
-            is used to signify that advance() has reached EOF,
-
-            All helper functions return 'None' back into the scan() method,
-            signifying EOF.
-        */
         // Tries to match a word to a keyword
-        // If not, tries to match to an identifier
-        fn match_word(&mut self, word: &String) -> MatchResult {
-            // Get keyword match
-            let keyword_match = self.match_keyword(word);
-            
-            // Determine if match found
-            match keyword_match {
-                // Return keyword if yes
-                Some(t) => {
-                    return MatchResult::Match(t);
-                },
-                // Otherwise search for identifier
-                None => {
-                    if let Some(t) = self.match_id(word) {
-                        // Return ID token if found
-                        return MatchResult::Match(t);
-                    } else {
-                        // Otherwise return none
-                        return MatchResult::None;
-                    }
-                }
-            }
-        }
-
-        // Skips whitespace and advances iterator to a character
-        // Returns None if reaches EOF
-        fn skip_ws(&mut self) -> Option<()> {
-            'wait_for_char: loop {
-                if self.advance() {
-                    if self.current == ' ' {
-                        continue 'wait_for_char;
-                    } else {
-                        return Some(());
-                    }
-                } else {
-                    return None;
-                }
+        // If not, the word is an identifier
+        fn match_word(&mut self, word: &str) -> Token {
+            match self.match_keyword(word) {
+                Some(t) => t,
+                None => Token::new(TokenKind::Identifier(word.to_string()), word),
             }
         }
 
         // Takes all alphanumeric characters
         // Exceptions for _
-        fn take_alphanum(&mut self) -> Option<String> {
+        fn take_alphanum(&mut self) -> String {
             let mut buffer = String::new();
 
             'take_alphanum: loop {
                 buffer.push(self.current);
+                self.advance();
 
-                if self.advance() {
-                    if self.current.is_alphanumeric() || self.current == '_' {
-                        // Continue of character is alphanumeric or _
-                        continue 'take_alphanum;
-                    } else {
-                        // Return word if character is not
-                        if buffer.is_empty() {
-                            //self.error(ErrorKind::IdentifierError, &buffer);
-                            todo!("Error handle");
-                            return Some(String::new());
-                        } else {
-                            return Some(buffer);
-                        }
-                    }
-                } else {    
-                    // Signals that advance() returned EOF.
-                    return None;
+                if self.current.is_alphanumeric() || self.current == '_' {
+                    // Continue if character is alphanumeric or _
+                    continue 'take_alphanum;
                 }
+
+                // Return word if character is not
+                return buffer;
             }
         }
 
         // Takes number literal
         // Advances the iterator
-        fn take_number_literal(&mut self) -> Option<Token> {
+        fn take_number_literal(&mut self) -> Token {
             let mut buffer = String::new();
 
             'literal: loop {
@@ -185,338 +155,293 @@ pub mod lexer {
                 */
 
                 // If '_', skip and go to next number
-                if self.current == '_' {
-                    if self.advance() {
-                        continue 'literal;
-                    } else {
-                        return None;
-                    }
+                if self.current != '_' {
+                    buffer.push(self.current);
                 }
 
-                // Push the current to the buffer
-                buffer.push(self.current);
+                // Go to next character
+                self.advance();
 
-                // Go to next character (if possible)
-                if self.advance() {
-                    // If character is white space
-                    if self.current == ' ' {
-                        // Drop the carriage return (if present)
-                        if buffer.ends_with('\r') {
-                            buffer.pop();
-                        }
-
-                        // Make token and return
-                        let token = Token::new(TokenKind::NumberLiteral(buffer), &buffer, &self.line);
-                        return Some(token);
-                    } else if let Some(t) = self.match_symbols() {
-                        // Get exception for dot
-                        if t.kind == TokenKind::Dot {
-                            // Continue loop (dot gets pushed to buffer)
-                            continue 'literal;
-                        }
+                // Only take a dot if it is followed by a digit (so `5.max()` still works)
+                let dot = self.current == '.'
+                    && !buffer.contains('.')
+                    && self.chars.peek().is_some_and(|(_, c)| c.is_ascii_digit());
 
-                        // Drop the carriage return (if present)
-                        if buffer.ends_with('\r') {
-                            buffer.pop();
-                        }
-
-                        // Make token and return
-                        let token = Token::new(TokenKind::NumberLiteral(buffer), &buffer, &self.line);
-                        return Some(token);
-                    } else {
-                        // Continue loop
-                        continue 'literal;
-                    }
-                } else {
-                    return None;
+                if self.current.is_ascii_digit() || self.current == '_' || dot {
+                    continue 'literal;
                 }
+
+                // Make token and return
+                return Token::new(TokenKind::NumberLiteral(buffer.clone()), &buffer);
             }
-        }   
+        }
 
         // Takes string literal
         // Peeks the iterator
-        fn take_string_literal(&mut self) -> Option<Token> {
+        fn take_string_literal(&mut self) -> Token {
             let mut buffer = String::new();
 
-            // Advance past the quotation mark
-            match self.advance() {
-                true => {},
-                false => return None,
-            }
-
             'literal: loop {
-                buffer.push(self.current);
-
-                if let Some(c) = self.chars.peek() {
-                    match *c {
-                        '"' => {
-                            let token = Token::new(TokenKind::StringLiteral(buffer), &buffer, &self.line);
-                            
-                            // Advance past the last quotation
-                            match self.advance() {
-                                true => {
-                                    // Return literal token
-                                    return Some(token);
-                                },
-                                false => {
-                                    // Push the literal token
-                                    self.tokens.push(token);
-
-                                    // Signal end of source file
-                                    return None;
-                                },
-                            }
-                        },
-                        _ => {
-                            self.advance();
-                            continue 'literal;
-                        }
-                    }
-                } else {
-                    self.end();
-                    return None;
+                match self.chars.peek().map(|&(_, c)| c) {
+                    Some('"') => {
+                        // Advance onto the last quotation
+                        self.advance();
+                        return Token::new(TokenKind::StringLiteral(buffer.clone()), &buffer);
+                    },
+                    Some('\n') | None => {
+                        // Strings may not span lines or run into EOF
+                        let end = self.offset + self.current.len_utf8();
+                        let span = self.source.span(self.start, end);
+                        let end = self.source.span(end, end);
+                        self.errors.push(Errors::SyntaxError(span, "unterminated string literal".to_string())
+                            .fix(end, "\"", "close the string with `\"` before the end of the line")
+                            .code(codes::UNTERMINATED_STRING));
+                        return Token::new(TokenKind::StringLiteral(buffer.clone()), &buffer);
+                    },
+                    Some('\\') => {
+                        // Escape sequences
+                        self.advance();
+                        let escaped = match self.chars.peek().map(|&(_, c)| c) {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some('"') => '"',
+                            Some('\\') => '\\',
+                            _ => {
+                                let escape = self.chars.peek().map_or(0, |(_, c)| c.len_utf8());
+                                let span = self.source.span(self.offset, self.offset + 1 + escape);
+                                self.errors.push(Errors::SyntaxError(span, "unknown escape sequence, expected one of `\\n`, `\\t`, `\\\"` or `\\\\`".to_string()));
+                                continue 'literal;
+                            },
+                        };
+                        self.advance();
+                        buffer.push(escaped);
+                    },
+                    Some(_) => {
+                        self.advance();
+                        buffer.push(self.current);
+                        continue 'literal;
+                    },
                 }
             }
         }
 
-        // Takes alphanumeric character until reaches one that doesn't
-        // Attempts to match that buffer to a keyword or identifier
-        // Returns the token of the identifier or keyword (if found)
-        fn take_alphanum_and_match(&mut self) -> Option<Token> {
-            let buffer = self.take_alphanum();
-
-            match buffer {
-                Some(string) => {
-                    let matched = self.match_word(&string);
-                    match matched {
-                        // If match then return token
-                        MatchResult::Match(t) => return Some(t),
-                        MatchResult::None => {
-                            //self.error(ErrorKind::IdentifierError, &string);
-                            todo!("Error handle");
-                            return Some(Token::new(TokenKind::Empty, "", &self.line));
-                        },
-                    }
-                },
-                None => {
-                    // Signals EOF
-                    return None;
+        // Skips a `//` comment up to (but not including) the newline
+        fn skip_comment(&mut self) {
+            while let Some(&(_, c)) = self.chars.peek() {
+                if c == '\n' {
+                    break;
                 }
+                self.advance();
             }
         }
 
         fn match_symbols(&mut self) -> Option<Token> {
             match self.current {
                 // Grouping Symbols
-                '(' => Some(Token::new(TokenKind::LPar, "(", &self.line)),
-                ')' => Some(Token::new(TokenKind::RPar, ")", &self.line)),
-                '[' => Some(Token::new(TokenKind::LBrac, "[", &self.line)),
-                ']' => Some(Token::new(TokenKind::RBrac, "]", &self.line)),
-                '{' => Some(Token::new(TokenKind::LCurl, "{", &self.line)),
-                '}' => Some(Token::new(TokenKind::RCurl, "}", &self.line)),
+                '(' => Some(Token::new(TokenKind::LPar, "(")),
+                ')' => Some(Token::new(TokenKind::RPar, ")")),
+                '[' => Some(Token::new(TokenKind::LBrac, "[")),
+                ']' => Some(Token::new(TokenKind::RBrac, "]")),
+                '{' => Some(Token::new(TokenKind::LCurl, "{")),
+                '}' => Some(Token::new(TokenKind::RCurl, "}")),
 
                 // Other Symbols
-                '.' => Some(Token::new(TokenKind::Dot, ".", &self.line)),
-                ',' => Some(Token::new(TokenKind::Comma, ",", &self.line)),
-                ':' => Some(Token::new(TokenKind::Colon, ":", &self.line)),
-                ';' => Some(Token::new(TokenKind::Semicolon, ";", &self.line)),
-                '*' => Some(Token::new(TokenKind::Star, "*", &self.line)),
+                '.' => Some(Token::new(TokenKind::Dot, ".")),
+                ',' => Some(Token::new(TokenKind::Comma, ",")),
+                ':' => Some(Token::new(TokenKind::Colon, ":")),
+                '#' => Some(Token::new(TokenKind::Hash, "#")),
+                '@' => Some(Token::new(TokenKind::At, "@")),
+                ';' => Some(Token::new(TokenKind::Semicolon, ";")),
+                '*' => Some(Token::new(TokenKind::Star, "*")),
 
                 // Misc
                 '\n' => {
-                    self.line += 1;
-                    Some(Token::new(TokenKind::Newline, "newline", &self.line))
-                },
-                '\r' => {
-                    Some(Token::new(TokenKind::Empty, "empty", &self.line))
+                    Some(Token::new(TokenKind::Newline, "newline"))
                 },
 
                 // Logical Operators
                 '+' => {
-                    if let Some(c) = self.chars.peek() {
-                        if *c == '=' {
-                            self.advance();
-                            Some(Token::new(TokenKind::PlusEqual, "+=", &self.line))
-                        } else {
-                            Some(Token::new(TokenKind::Plus, "+", &self.line))
-                        }
+                    if self.advance_if('=') {
+                        Some(Token::new(TokenKind::PlusEqual, "+="))
                     } else {
-                        Some(Token::new(TokenKind::Plus, "+", &self.line))
+                        Some(Token::new(TokenKind::Plus, "+"))
                     }
                 },
                 '-' => {
-                    if let Some(c) = self.chars.peek() {
-                        if *c == '=' {
-                            self.advance();
-                            Some(Token::new(TokenKind::MinusEqual, "-=", &self.line))
-                        } else if *c == '>' {
-                            Some(Token::new(TokenKind::RArrow, "->", &self.line))
-                        } else {
-                            Some(Token::new(TokenKind::Minus, "-", &self.line))
+                    if self.advance_if('=') {
+                        Some(Token::new(TokenKind::MinusEqual, "-="))
+                    } else if self.advance_if('>') {
+                        Some(Token::new(TokenKind::RArrow, "->"))
+                    } else {
+                        Some(Token::new(TokenKind::Minus, "-"))
+                    }
+                },
+                '/' => {
+                    if self.advance_if('/') {
+                        self.skip_comment();
+                        if !self.comments {
+                            return Some(Token::new(TokenKind::Empty, "empty"));
                         }
+                        let end = self.offset + self.current.len_utf8();
+                        let text = self.source.text()[self.start..end].trim_end().to_string();
+                        Some(Token::new(TokenKind::Comment(text.clone()), &text))
                     } else {
-                        Some(Token::new(TokenKind::Minus, "-", &self.line))
+                        Some(Token::new(TokenKind::Slash, "/"))
                     }
                 },
                 '>' => {
-                    if let Some(c) = self.chars.peek() {
-                        if *c == '=' {
-                            self.advance();
-                            Some(Token::new(TokenKind::MoreEqual, ">=", &self.line))
-                        } else {
-                            Some(Token::new(TokenKind::MoreThan, ">", &self.line))
-                        }
+                    if self.advance_if('=') {
+                        Some(Token::new(TokenKind::MoreEqual, ">="))
                     } else {
-                        Some(Token::new(TokenKind::MoreThan, ">", &self.line))
+                        Some(Token::new(TokenKind::MoreThan, ">"))
                     }
                 },
                 '<' => {
-                    if let Some(c) = self.chars.peek() {
-                        if *c == '=' {
-                            self.advance();
-                            Some(Token::new(TokenKind::LessEqual, "<=", &self.line))
-                        } else if *c == '-' {
-                            Some(Token::new(TokenKind::LArrow, "<-", &self.line))
-                        } else {
-                            Some(Token::new(TokenKind::LessThan, "<", &self.line))
-                        }
+                    if self.advance_if('=') {
+                        Some(Token::new(TokenKind::LessEqual, "<="))
+                    } else if self.advance_if('-') {
+                        Some(Token::new(TokenKind::LArrow, "<-"))
                     } else {
-                        Some(Token::new(TokenKind::LessThan, "<", &self.line))
+                        Some(Token::new(TokenKind::LessThan, "<"))
                     }
                 },
                 '=' => {
-                    if let Some(c) = self.chars.peek() {
-                        if *c == '=' {
-                            self.advance();
-                            Some(Token::new(TokenKind::EqualEqual, "==", &self.line))
-                        } else {
-                            Some(Token::new(TokenKind::Equal, "=", &self.line))
-                        }
+                    if self.advance_if('=') {
+                        Some(Token::new(TokenKind::EqualEqual, "=="))
                     } else {
-                        Some(Token::new(TokenKind::Equal, "=", &self.line))
+                        Some(Token::new(TokenKind::Equal, "="))
                     }
                 },
                 '!' => {
-                    if let Some(c) = self.chars.peek() {
-                        if *c == '=' {
-                            self.advance();
-                            Some(Token::new(TokenKind::BangEqual, "!=", &self.line))
-                        } else {
-                            Some(Token::new(TokenKind::Bang, "!", &self.line))
-                        }
+                    if self.advance_if('=') {
+                        Some(Token::new(TokenKind::BangEqual, "!="))
                     } else {
-                        Some(Token::new(TokenKind::Bang, "!", &self.line))
+                        Some(Token::new(TokenKind::Bang, "!"))
                     }
                 },
                 '&' => {
-                    if let Some(c) = self.chars.peek() {
-                        if *c == '&' {
-                            self.advance();
-                            Some(Token::new(TokenKind::And, "&&", &self.line))
-                        } else {
-                            Some(Token::new(TokenKind::Ampersand, "&", &self.line))
-                        }
+                    if self.advance_if('&') {
+                        Some(Token::new(TokenKind::And, "&&"))
                     } else {
-                        Some(Token::new(TokenKind::Ampersand, "&", &self.line))
+                        Some(Token::new(TokenKind::Ampersand, "&"))
                     }
                 },
                 '|' => {
-                    if let Some(c) = self.chars.peek() {
-                        if *c == '|' {
-                            self.advance();
-                            return Some(Token::new(TokenKind::Or, "||", &self.line));
-                        } else {
-                            return Some(Token::new(TokenKind::Bar, "|", &self.line));
-                        }
+                    if self.advance_if('|') {
+                        Some(Token::new(TokenKind::Or, "||"))
                     } else {
-                        return Some(Token::new(TokenKind::Bar, "|", &self.line));
+                        Some(Token::new(TokenKind::Bar, "|"))
                     }
                 },
 
                 // String literal
-                '"' => {
-                    if let Some(t) = self.take_string_literal() {
-                        return Some(t);
-                    } else {
-                        return None;
-                    }
-                }
-                _ => {
-                    None
-                },
+                '"' => Some(self.take_string_literal()),
+                _ => None,
             }
         }
-        
+
+        // Records where the token starts and ends (`end` is the offset after it) and stores it
+        fn push(&mut self, mut token: Token, end: usize) {
+            let span = self.source.span(self.start, end);
+            token.line = span.line;
+            token.col = span.col;
+            // Only a newline runs onto the next line, and it takes up one column
+            token.len = if span.end_line == span.line { span.end_col - span.col } else { 1 };
+            self.tokens.push(token);
+        }
+
         // Scan each character of the file
         pub fn scan(&mut self) -> (&Vec<Token>, &Vec<Errors>) {
-            // Define variables
-            let scanning = true;
-
             // Match character
-            'start: while scanning {
-                println!("Current char: `{}`", self.current);
-
+            'start: while self.current != '\0' {
                 // Skip token if whitespace
                 if self.current == ' ' || self.current == '\t' || self.current == '\r' {
-                    if self.advance() {
-                        continue 'start;
-                    } else {
-                        return (&self.tokens, &self.errors); 
-                    }
+                    self.advance();
+                    continue 'start;
                 }
 
+                self.start = self.offset;
+
                 // Match character to symbols
+                // `current` is still the last character of the symbol
                 if let Some(t) = self.match_symbols() {
-                    self.tokens.push(t);
-                    if self.advance() {
-                        continue 'start;
-                    } else {
-                        return (&self.tokens, &self.errors);
-                    }
-                } else {
-                    // Check if character could be a number literal
-                    /*
-                        This if statement repeats 'start if literal found,
-                        no need for else statement (avoids nesting)
-                    */
-
-                    if self.current.is_numeric() {
-                        println!("This is numeric");
-                        // Take number as a literal and parse
-                        if let Some(t) = self.take_number_literal() {
-                            self.tokens.push(t);
-                            continue 'start;
-                        } else {
-                            // If none returned
-                            self.end();
-                            return (&self.tokens, &self.errors);
-                        }
+                    if t.kind != TokenKind::Empty {
+                        self.push(t, self.offset + self.current.len_utf8());
                     }
+                    self.advance();
+                    continue 'start;
+                }
 
-                    // Match character to identifier/keyword
-                    if let Some(t) = self.take_alphanum_and_match() {
-
-                        // Match returned 
-                        match t.kind {
-                            // End: stop tokenizing
-                            TokenKind::EndOfFile => {
-                                self.tokens.push(t);
-                                return (&self.tokens, &self.errors);
-                            },
+                // Check if character could be a number literal
+                if self.current.is_ascii_digit() {
+                    let t = self.take_number_literal();
+                    self.push(t, self.offset);
+                    continue 'start;
+                }
 
-                            _ => {
-                                self.tokens.push(t);
-                                continue 'start;
-                            },
-                        }
-                    } else {
-                        continue 'start;
-                    }
+                // Match character to identifier/keyword
+                if self.current.is_alphabetic() || self.current == '_' {
+                    let word = self.take_alphanum();
+                    let t = self.match_word(&word);
+                    self.push(t, self.offset);
+                    continue 'start;
                 }
+
+                // Nothing matched the character
+                let span = self.source.span(self.offset, self.offset + self.current.len_utf8());
+                self.errors.push(Errors::SyntaxError(span, format!("unexpected character `{}`", self.current)));
+                self.advance();
             }
-            
-            self.tokens.push(Token::end(&self.line));
+
+            // The end of the file is shown just after its last line rather than on a line that isn't there
+            self.start = self.source.text().trim_end_matches(['\n', '\r']).len();
+            self.push(Token::end(), self.start);
             (&self.tokens, &self.errors)
         }
     }
-}
\ No newline at end of file
+
+    #[cfg(test)]
+    mod tests {
+        use super::Lexer;
+        use crate::{source::source::SourceMap, tokens::tokens::TokenKind};
+
+        // Returns the line, column and length of every token in the text
+        fn positions(text: &str) -> Vec<(TokenKind, usize, usize, usize)> {
+            let source = SourceMap::new(text.to_string());
+            let mut lexer = Lexer::new(&source);
+            let (tokens, errors) = lexer.scan();
+            assert!(errors.is_empty());
+            tokens.iter().map(|t| (t.kind.clone(), t.line, t.col, t.len)).collect()
+        }
+
+        #[test]
+        fn newline_is_on_the_line_it_ends() {
+            let tokens = positions("a = 1\nout a\n");
+            assert_eq!(tokens[3], (TokenKind::Newline, 1, 6, 1));
+            assert_eq!(tokens[4], (TokenKind::Out, 2, 1, 3));
+            assert_eq!(tokens[6], (TokenKind::Newline, 2, 6, 1));
+        }
+
+        #[test]
+        fn crlf_lines_match_lf_lines() {
+            assert_eq!(positions("a = 1\r\nout a\r\n"), positions("a = 1\nout a\n"));
+        }
+
+        #[test]
+        fn columns_count_characters() {
+            let tokens = positions("\tout \"é→\" + x");
+            assert_eq!(tokens[0], (TokenKind::Out, 1, 2, 3));
+            assert_eq!(tokens[1], (TokenKind::StringLiteral("é→".to_string()), 1, 6, 4));
+            assert_eq!(tokens[3], (TokenKind::Identifier("x".to_string()), 1, 13, 1));
+        }
+
+        #[test]
+        fn end_of_file_follows_the_last_line() {
+            let tokens = positions("out 1\n\n");
+            assert_eq!(tokens.last(), Some(&(TokenKind::EndOfFile, 1, 6, 0)));
+
+            let tokens = positions("out 1");
+            assert_eq!(tokens.last(), Some(&(TokenKind::EndOfFile, 1, 6, 0)));
+        }
+    }
+}
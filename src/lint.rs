@@ -0,0 +1,633 @@
+pub mod lint {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::{
+        ast::nodes::*,
+        codes::codes,
+        diagnostic::diagnostic::{Diagnostic, Fix, Severity, Span},
+        error::errors::Errors,
+        source::source::Sources,
+    };
+
+    // What happens when a lint finds something
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Level {
+        Allow,
+        Warn,
+        Deny,
+    }
+
+    impl Level {
+        pub fn from_name(name: &str) -> Option<Self> {
+            match name {
+                "allow" => Some(Level::Allow),
+                "warn" => Some(Level::Warn),
+                "deny" => Some(Level::Deny),
+                _ => None,
+            }
+        }
+
+        pub fn name(&self) -> &'static str {
+            match self {
+                Level::Allow => "allow",
+                Level::Warn => "warn",
+                Level::Deny => "deny",
+            }
+        }
+    }
+
+    pub struct Lint {
+        pub name: &'static str,
+        pub code: &'static str,
+    }
+
+    // Every lint warns unless it is set otherwise
+    pub const LINTS: &[Lint] = &[
+        Lint { name: "unused_variable", code: codes::UNUSED_VARIABLE },
+        Lint { name: "const_shadow", code: codes::CONST_SHADOW },
+        Lint { name: "unreachable_code", code: codes::UNREACHABLE_CODE },
+        Lint { name: "duplicate_condition", code: codes::DUPLICATE_CONDITION },
+        Lint { name: "float_equality", code: codes::FLOAT_EQUALITY },
+    ];
+
+    // Stands for every lint at once, e.g. `--deny=warnings`
+    pub const ALL: &str = "warnings";
+
+    fn find(name: &str) -> Option<&'static Lint> {
+        LINTS.iter().find(|lint| lint.name == name)
+    }
+
+    // Returns the lints a name refers to, or None if it isn't a lint
+    fn matching(name: &str) -> Option<Vec<&'static Lint>> {
+        if name == ALL {
+            Some(LINTS.iter().collect())
+        } else {
+            find(name).map(|lint| vec![lint])
+        }
+    }
+
+    fn unknown(name: &str) -> String {
+        let names: Vec<String> = LINTS.iter().map(|lint| format!("`{}`", lint.name)).collect();
+        format!("unknown lint `{name}`, expected `{ALL}` or one of {}", names.join(", "))
+    }
+
+    // Where the level of a lint was set, so the diagnostic can say
+    #[derive(Debug, Clone, Copy)]
+    enum Origin {
+        Default,
+        CommandLine,
+        Annotation(usize),
+    }
+
+    // The level of each lint, as set from the command line
+    #[derive(Debug, Clone, Default)]
+    pub struct Levels {
+        levels: HashMap<&'static str, Level>,
+    }
+
+    impl Levels {
+        // Sets a lint (or all of them) from an `--allow=`, `--warn=` or `--deny=` flag
+        pub fn set(&mut self, name: &str, level: Level) -> Result<(), String> {
+            for lint in matching(name).ok_or_else(|| unknown(name))? {
+                self.levels.insert(lint.name, level);
+            }
+            Ok(())
+        }
+    }
+
+    /*
+        The linter walks the AST once before it runs, looking for code that is valid
+        but probably not what was meant. Each lint is reported at the level set on the
+        command line, unless an annotation around the code sets it differently:
+
+        @allow(unused_variable)
+        func callback <- event, index
+            out event
+        end
+
+        Names are tracked in scopes that follow the environments the program will run in:
+        a function call, each pass of a `for`, each `case` and a `catch` get their own, and
+        `x = value` changes the `x` an enclosing scope already has before it makes a new one.
+        So the same name in two functions is two different names.
+    */
+    pub struct Linter<'a> {
+        levels: Levels,
+        annotations: &'a [Annotation],
+        sources: &'a Sources,
+        diagnostics: Vec<Diagnostic>,
+
+        // The scopes around the node being checked, outermost (the program) first
+        scopes: Vec<Scope>,
+    }
+
+    // A name given a value in a scope
+    struct Name {
+        name: String,
+        // Where it was first given a value
+        span: Span,
+        read: bool,
+        // Last given a Num, for `float_equality`
+        number: bool,
+    }
+
+    #[derive(Default)]
+    struct Scope {
+        names: Vec<Name>,
+
+        // Names declared with `const` here, and where
+        constants: HashMap<String, Span>,
+
+        // Names read before anything around them defined them, which a function body can
+        // do with a name the program only assigns after the function
+        unresolved: HashSet<String>,
+    }
+
+    impl<'a> Linter<'a> {
+        pub fn new(levels: Levels, annotations: &'a [Annotation], sources: &'a Sources) -> Self {
+            Self {
+                levels,
+                annotations,
+                sources,
+                diagnostics: Vec::new(),
+                scopes: vec![Scope::default()],
+            }
+        }
+
+        // Returns every warning (and denied lint) in the program, in line order
+        // An annotation naming a lint that doesn't exist is an error
+        pub fn check(mut self, nodes: &[Node]) -> Result<Vec<Diagnostic>, Errors> {
+            for annotation in self.annotations {
+                if let Some((name, span)) = annotation.lints.iter().find(|(name, _)| matching(name).is_none()) {
+                    return Err(Errors::SyntaxError(*span, unknown(name)));
+                }
+            }
+
+            self.block(nodes);
+            self.close();
+
+            self.diagnostics.sort_by_key(|d| d.span.line);
+            Ok(self.diagnostics)
+        }
+
+        // Checks nodes in a scope of their own
+        fn scoped(&mut self, check: impl FnOnce(&mut Self)) {
+            self.scopes.push(Scope::default());
+            check(self);
+            self.close();
+        }
+
+        // Ends the innermost scope, reporting the names in it that nothing read
+        // A name it couldn't find is looked for again in the scope around it
+        fn close(&mut self) {
+            let scope = self.scopes.pop().expect("the program's own scope is closed last");
+
+            for name in &scope.names {
+                if name.read || scope.unresolved.contains(&name.name) || name.name.starts_with('_') {
+                    continue;
+                }
+
+                let (name, span) = (&name.name, name.span);
+                let help = format!("if this is on purpose, start the name with an underscore: `_{name}`");
+                self.report("unused_variable", span, format!("`{name}` is never read"), |d| {
+                    let d = d.with_label("given a value here");
+                    if span.has_column() {
+                        let start = Span::new(span.line, span.col, span.line, span.col);
+                        d.with_fix(Fix { span: start, replacement: "_".to_string(), message: help })
+                    } else {
+                        d.with_help(&help)
+                    }
+                });
+            }
+
+            if let Some(outer) = self.scopes.last_mut() {
+                let names = &scope.names;
+                outer.unresolved.extend(scope.unresolved.into_iter().filter(|u| !names.iter().any(|n| n.name == *u)));
+            }
+        }
+
+        // Returns the name from the innermost scope that has one by that name
+        fn find(&self, name: &str) -> Option<&Name> {
+            self.scopes.iter().rev().find_map(|scope| scope.names.iter().find(|n| n.name == name))
+        }
+
+        fn find_mut(&mut self, name: &str) -> Option<&mut Name> {
+            self.scopes.iter_mut().rev().find_map(|scope| scope.names.iter_mut().find(|n| n.name == name))
+        }
+
+        fn read(&mut self, name: &str) {
+            match self.find_mut(name) {
+                Some(found) => found.read = true,
+                None => {
+                    self.scopes.last_mut().unwrap().unresolved.insert(name.to_string());
+                },
+            }
+        }
+
+        // Returns the level of a lint on a line, and where that level was set
+        // The innermost annotation around the line wins, then the command line
+        fn level(&self, lint: &Lint, line: usize) -> (Level, Origin) {
+            let mut level = match self.levels.levels.get(lint.name) {
+                Some(level) => (*level, Origin::CommandLine),
+                None => (Level::Warn, Origin::Default),
+            };
+
+            let mut innermost = 0;
+            for annotation in self.annotations.iter().filter(|a| a.first <= line && line <= a.last) {
+                if annotation.first < innermost {
+                    continue;
+                }
+                if let Some((_, span)) = annotation.lints.iter().find(|(name, _)| name == lint.name || name == ALL) {
+                    level = (annotation.level, Origin::Annotation(span.line));
+                    innermost = annotation.first;
+                }
+            }
+
+            level
+        }
+
+        fn report(&mut self, name: &str, span: Span, message: String, build: impl FnOnce(Diagnostic) -> Diagnostic) {
+            let lint = find(name).expect("lints are reported by their name in LINTS");
+            let (level, origin) = self.level(lint, span.line);
+            let (kind, severity) = match level {
+                Level::Allow => return,
+                Level::Warn => ("Warning", Severity::Warning),
+                Level::Deny => ("LintError", Severity::Error),
+            };
+
+            let note = match origin {
+                Origin::Default => format!("`{}` is on by default, `@allow({0})` on the line before turns it off", lint.name),
+                Origin::CommandLine => format!("`{}` is set to {} on the command line", lint.name, level.name()),
+                Origin::Annotation(line) => format!(
+                    "`{}` is set to {} by the annotation on {}",
+                    lint.name, level.name(), self.sources.describe(line),
+                ),
+            };
+
+            let mut diagnostic = build(Diagnostic::new(kind, &message, span).with_severity(severity)).with_note(&note);
+            diagnostic.code = Some(lint.code.to_string());
+            self.diagnostics.push(diagnostic);
+        }
+
+        // Checks a block of statements, anything after a `return` or `raise` in it is unreachable
+        fn block(&mut self, nodes: &[Node]) {
+            let mut returned = None;
+            let mut reported = false;
+
+            for node in nodes {
+                if let (Some((line, keyword)), false) = (returned, reported) {
+                    reported = true;
+                    let span = Span::line(line_of(node).unwrap_or(line));
+                    self.report("unreachable_code", span, "this code is never run".to_string(), |d| {
+                        d.with_label("unreachable").with_secondary(Span::line(line), &format!("any code after this `{keyword}` never runs"))
+                    });
+                }
+
+                match node {
+                    Node::Return(n) => {
+                        returned.get_or_insert((n.line, "return"));
+                    },
+                    Node::Raise(n) => {
+                        returned.get_or_insert((n.line, "raise"));
+                    },
+                    _ => {},
+                }
+                self.node(node);
+            }
+        }
+
+        fn nodes(&mut self, nodes: &[Node]) {
+            for node in nodes {
+                self.node(node);
+            }
+        }
+
+        fn node(&mut self, node: &Node) {
+            match node {
+                Node::Identifier(n) => self.read(&n.name),
+                Node::Literal(_) | Node::Enum(_) => {},
+                Node::Binary(n) => {
+                    self.node(&n.left);
+                    self.node(&n.right);
+                },
+                Node::Logical(n) => {
+                    self.node(&n.left);
+                    self.node(&n.right);
+                },
+                Node::Compare(n) => {
+                    self.node(&n.left);
+                    self.node(&n.right);
+
+                    let symbol = match n.op {
+                        CompareOp::Equal => "==",
+                        CompareOp::NotEqual => "!=",
+                        _ => return,
+                    };
+                    if self.is_number(&n.left) || self.is_number(&n.right) {
+                        self.report("float_equality", Span::line(n.line), format!("Nums are compared with `{symbol}`"), |d| {
+                            d.with_note("Nums can't hold most decimals exactly, so results of arithmetic are often a tiny bit off")
+                                .with_help("check that the difference is smaller than a tolerance instead, e.g. `d < 0.000001 and d > -0.000001`")
+                        });
+                    }
+                },
+                Node::Unary(n) => self.node(&n.right),
+                Node::List(n) => self.nodes(&n.items),
+                Node::Tuple(n) => self.nodes(&n.items),
+                Node::Matrix(n) => {
+                    for row in &n.rows {
+                        self.nodes(row);
+                    }
+                },
+                Node::Map(n) => {
+                    for (key, value) in &n.entries {
+                        self.node(key);
+                        self.node(value);
+                    }
+                },
+                Node::Variant(n) => {
+                    if let Some(field) = &n.field {
+                        self.node(field);
+                    }
+                },
+                Node::Index(n) => {
+                    self.node(&n.target);
+                    self.nodes(&n.indices);
+                },
+                Node::Slice(n) => {
+                    self.node(&n.target);
+                    for bound in [&n.start, &n.end].into_iter().flatten() {
+                        self.node(bound);
+                    }
+                },
+                Node::Interpolation(n) => {
+                    for part in &n.parts {
+                        if let Part::Value(value, _) = part {
+                            self.node(value);
+                        }
+                    }
+                },
+                Node::Call(n) => {
+                    self.node(&n.callee);
+                    for arg in &n.args {
+                        self.node(&arg.value);
+                    }
+                },
+                Node::Method(n) => {
+                    self.node(&n.target);
+                    self.nodes(&n.args);
+                },
+                Node::Assign(n) => {
+                    self.node(&n.value);
+                    self.shadows(&n.name, n.span, true);
+
+                    let number = self.is_number(&n.value);
+                    match self.find_mut(&n.name) {
+                        Some(existing) => existing.number = number,
+                        None => self.define(&n.name, n.span, number),
+                    }
+                    if n.constant {
+                        self.scopes.last_mut().unwrap().constants.entry(n.name.clone()).or_insert(n.span);
+                    }
+                },
+                Node::Destructure(n) => {
+                    self.node(&n.value);
+                    self.pattern(&n.pattern, Span::line(n.line));
+                },
+                Node::Out(n) => self.node(&n.value),
+                Node::If(n) => {
+                    for (i, (condition, body)) in n.branches.iter().enumerate() {
+                        self.node(condition);
+
+                        if let Some((earlier, _)) = n.branches[..i].iter().find(|(c, _)| same(c, condition)) {
+                            let earlier = span_of(earlier).unwrap_or(Span::line(n.line));
+                            let span = span_of(condition).unwrap_or(Span::line(n.line));
+                            self.report("duplicate_condition", span, "this `elif` has the same condition as an earlier branch".to_string(), |d| {
+                                d.with_label("so this branch never runs").with_secondary(earlier, "checked here first")
+                            });
+                        }
+
+                        self.block(body);
+                    }
+
+                    if let Some(body) = &n.otherwise {
+                        self.block(body);
+                    }
+                },
+                Node::Match(n) => {
+                    self.node(&n.value);
+                    for (pattern, body) in &n.cases {
+                        self.scoped(|this| {
+                            match pattern {
+                                // A field doesn't have to be used, the case may only care about the variant
+                                CasePattern::Variant { binding: Some(binding), .. } => {
+                                    this.shadows(binding, Span::line(n.line), false);
+                                    this.define(binding, Span::line(n.line), false);
+                                    this.read(binding);
+                                },
+                                CasePattern::Variant { .. } => {},
+                                CasePattern::Value(value) => this.node(value),
+                            }
+                            this.block(body);
+                        });
+                    }
+
+                    if let Some(body) = &n.otherwise {
+                        self.scoped(|this| this.block(body));
+                    }
+                },
+                Node::For(n) => {
+                    self.node(&n.iterable);
+                    self.scoped(|this| {
+                        this.pattern(&n.pattern, Span::line(n.line));
+                        this.block(&n.body);
+                    });
+                },
+                Node::Func(n) | Node::Lambda(n) => self.scoped(|this| {
+                    for param in &n.params {
+                        if let Some(default) = &param.default {
+                            this.node(default);
+                        }
+                        this.shadows(&param.name, param.span, false);
+                        this.define(&param.name, param.span, false);
+                    }
+                    this.block(&n.body);
+                }),
+                Node::Return(n) => {
+                    if let Some(value) = &n.value {
+                        self.node(value);
+                    }
+                },
+                Node::Raise(n) => self.node(&n.value),
+                Node::Try(n) => {
+                    self.block(&n.body);
+                    self.scoped(|this| {
+                        if let Some(binding) = &n.binding {
+                            this.shadows(&binding.name, binding.span, false);
+                            this.define(&binding.name, binding.span, false);
+                        }
+                        this.block(&n.handler);
+                    });
+                },
+                Node::Include(n) => self.block(&n.body),
+            }
+        }
+
+        // Defines the names in a loop variable or destructuring pattern
+        fn pattern(&mut self, pattern: &Pattern, span: Span) {
+            match pattern {
+                Pattern::Name(name) => {
+                    self.shadows(name, span, false);
+                    self.define(name, span, false);
+                },
+                Pattern::Tuple(patterns) => {
+                    for pattern in patterns {
+                        self.pattern(pattern, span);
+                    }
+                },
+            }
+        }
+
+        // Gives a name a value in the innermost scope, a name already there keeps where it was first given one
+        fn define(&mut self, name: &str, span: Span, number: bool) {
+            let scope = self.scopes.last_mut().unwrap();
+            match scope.names.iter_mut().find(|n| n.name == name) {
+                Some(existing) => existing.number = number,
+                None => scope.names.push(Name { name: name.to_string(), span, read: false, number }),
+            }
+        }
+
+        // Reports a name that reuses a constant, `assigned` for `name = value` rather than a new binding
+        // Only the constant the name would refer to counts, not one in another function
+        fn shadows(&mut self, name: &str, span: Span, assigned: bool) {
+            let scope = self.scopes.iter().rev()
+                .find(|scope| scope.constants.contains_key(name) || scope.names.iter().any(|n| n.name == name));
+            let Some(declared) = scope.and_then(|scope| scope.constants.get(name)).copied() else {
+                return;
+            };
+
+            let message = if assigned {
+                format!("`{name}` is a constant, but is assigned to again")
+            } else {
+                format!("`{name}` shadows a constant")
+            };
+            self.report("const_shadow", span, message, |d| d.with_secondary(declared, "declared `const` here"));
+        }
+
+        // Returns whether an expression is known to give a Num
+        fn is_number(&self, node: &Node) -> bool {
+            match node {
+                Node::Literal(n) => matches!(n.literal, Literal::Float(_)),
+                Node::Identifier(n) => self.find(&n.name).is_some_and(|name| name.number),
+                Node::Unary(n) => n.op == UnaryOp::Negative && self.is_number(&n.right),
+                Node::Binary(n) => self.is_number(&n.left) || self.is_number(&n.right),
+                Node::Method(n) => n.name == "determinant",
+                _ => false,
+            }
+        }
+    }
+
+    // Returns whether two conditions are written the same way
+    // Calls never count as the same, they may give a different answer each time
+    fn same(a: &Node, b: &Node) -> bool {
+        let all = |a: &[Node], b: &[Node]| a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b));
+
+        match (a, b) {
+            (Node::Identifier(a), Node::Identifier(b)) => a.name == b.name,
+            (Node::Literal(a), Node::Literal(b)) => a.literal == b.literal,
+            (Node::Binary(a), Node::Binary(b)) => a.op == b.op && same(&a.left, &b.left) && same(&a.right, &b.right),
+            (Node::Compare(a), Node::Compare(b)) => a.op == b.op && same(&a.left, &b.left) && same(&a.right, &b.right),
+            (Node::Logical(a), Node::Logical(b)) => a.op == b.op && same(&a.left, &b.left) && same(&a.right, &b.right),
+            (Node::Unary(a), Node::Unary(b)) => a.op == b.op && same(&a.right, &b.right),
+            (Node::Index(a), Node::Index(b)) => same(&a.target, &b.target) && all(&a.indices, &b.indices),
+            (Node::List(a), Node::List(b)) => all(&a.items, &b.items),
+            (Node::Tuple(a), Node::Tuple(b)) => all(&a.items, &b.items),
+            (Node::Variant(a), Node::Variant(b)) => a.enum_name == b.enum_name && a.name == b.name && match (&a.field, &b.field) {
+                (Some(a), Some(b)) => same(a, b),
+                (None, None) => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    // Returns the first line a node is on, if it knows
+    fn line_of(node: &Node) -> Option<usize> {
+        Some(match node {
+            Node::Identifier(n) => n.span.line,
+            Node::Assign(n) => n.span.line,
+            Node::Binary(n) => n.line,
+            Node::Compare(n) => n.line,
+            Node::Logical(n) => n.line,
+            Node::Unary(n) => n.line,
+            Node::Index(n) => n.line,
+            Node::Slice(n) => n.line,
+            Node::Call(n) => n.line,
+            Node::Method(n) => n.line,
+            Node::Variant(n) => n.line,
+            Node::Map(n) => n.line,
+            Node::Matrix(n) => n.line,
+            Node::Interpolation(n) => n.line,
+            Node::Destructure(n) => n.line,
+            Node::Out(n) => n.line,
+            Node::If(n) => n.line,
+            Node::Match(n) => n.line,
+            Node::For(n) => n.line,
+            Node::Func(n) | Node::Lambda(n) => n.line,
+            Node::Return(n) => n.line,
+            Node::Raise(n) => n.line,
+            Node::Try(n) => n.line,
+            Node::Include(n) => n.line,
+            Node::List(n) => return n.items.first().and_then(line_of),
+            Node::Tuple(n) => return n.items.first().and_then(line_of),
+            Node::Literal(_) | Node::Enum(_) => return None,
+        })
+    }
+
+    // Names know their columns, everything else marks its whole line
+    fn span_of(node: &Node) -> Option<Span> {
+        match node {
+            Node::Identifier(n) => Some(n.span),
+            _ => line_of(node).map(Span::line),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Levels, Linter};
+        use crate::{loader::loader::Loader, source::source::Sources};
+
+        // The message of every warning in a program, in line order
+        fn warnings(text: &str) -> Vec<String> {
+            let mut sources = Sources::new();
+            let root = sources.add("test.dy", None, text.to_string(), None);
+            let (nodes, annotations) = Loader::new(&mut sources).load(root).expect("the program should parse");
+            let diagnostics = Linter::new(Levels::default(), &annotations, &sources).check(&nodes).unwrap();
+            diagnostics.into_iter().map(|d| d.message).collect()
+        }
+
+        #[test]
+        fn a_name_is_unused_in_its_own_function() {
+            // `total` is read in `b`, which says nothing about the `total` in `a`
+            let program = "func a\ntotal = 1\nend\nfunc b\ntotal = 2\nreturn total\nend\nout a()\nout b()";
+            assert_eq!(warnings(program), ["`total` is never read"]);
+
+            let program = "func a <- x\nreturn 1\nend\nfunc b <- x\nreturn x\nend\nout a(1) + b(2)";
+            assert_eq!(warnings(program), ["`x` is never read"]);
+        }
+
+        #[test]
+        fn assigning_reaches_the_enclosing_name() {
+            // The loop changes the program's `count`, so reading it afterwards counts
+            assert!(warnings("count = 0\nfor i in [1, 2]\ncount += i\nend\nout count").is_empty());
+            // A function can read a name the program only assigns after it
+            assert!(warnings("func show\nout limit\nend\nlimit = 3\nshow()").is_empty());
+        }
+
+        #[test]
+        fn constants_are_only_shadowed_where_they_are_seen() {
+            let program = "func a\nconst LIMIT = 1\nreturn LIMIT\nend\nfunc b <- LIMIT\nreturn LIMIT\nend\nout a() + b(2)";
+            assert!(warnings(program).is_empty());
+            let program = "const LIMIT = 1\nfunc b <- LIMIT\nreturn LIMIT\nend\nout b(2) + LIMIT";
+            assert_eq!(warnings(program), ["`LIMIT` shadows a constant"]);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+pub mod loader {
+    use std::{fs, io::ErrorKind, path::Path};
+
+    use crate::{
+        ast::{ast::Ast, nodes::{Annotation, IdentifierNode, Node}},
+        cli::cli::Input,
+        codes::codes,
+        diagnostic::diagnostic::Span,
+        error::errors::Errors,
+        lexer::lexer::Lexer,
+        source::source::{FileId, Sources},
+        suggest::suggest,
+        tokens::tokens::Token,
+    };
+
+    /*
+        Reads a program and every file it includes into the source database, filling in
+        the `body` of each `include` with the statements of the files it names.
+
+        A file is only loaded the first time it is included, so two files can both include
+        a third. Errors from every file are collected, the same as the parser does for statements.
+    */
+    pub struct Loader<'a> {
+        sources: &'a mut Sources,
+
+        // The files being loaded right now, outermost first, to catch a file that includes itself
+        loading: Vec<FileId>,
+        annotations: Vec<Annotation>,
+        errors: Vec<Errors>,
+    }
+
+    impl<'a> Loader<'a> {
+        pub fn new(sources: &'a mut Sources) -> Self {
+            Self { sources, loading: Vec::new(), annotations: Vec::new(), errors: Vec::new() }
+        }
+
+        // Returns the tokens of a file in the database
+        pub fn tokens(sources: &Sources, file: FileId) -> Result<Vec<Token>, Vec<Errors>> {
+            let mut lexer = Lexer::new(&sources.file(file).map);
+            let (tokens, errors) = lexer.scan();
+            match errors.is_empty() {
+                true => Ok(tokens.clone()),
+                false => Err(errors.clone()),
+            }
+        }
+
+        // Returns the statements of the file, along with the annotations of every file loaded
+        pub fn load(mut self, file: FileId) -> Result<(Vec<Node>, Vec<Annotation>), Vec<Errors>> {
+            let nodes = self.file(file);
+            match self.errors.is_empty() {
+                true => Ok((nodes, self.annotations)),
+                false => Err(self.errors),
+            }
+        }
+
+        fn file(&mut self, file: FileId) -> Vec<Node> {
+            let tokens = match Self::tokens(self.sources, file) {
+                Ok(tokens) => tokens,
+                Err(errors) => {
+                    self.errors.extend(errors);
+                    return Vec::new();
+                },
+            };
+
+            let mut ast = Ast::new(tokens);
+            if let Err(errors) = ast.build() {
+                self.errors.extend(errors);
+                return Vec::new();
+            }
+            self.annotations.append(&mut ast.annotations);
+
+            // Only the top level of a file can include, the parser makes sure of that
+            let mut nodes = std::mem::take(&mut ast.nodes);
+            self.loading.push(file);
+            for node in &mut nodes {
+                if let Node::Include(include) = node {
+                    for module in &include.modules {
+                        let body = self.include(file, module);
+                        include.body.extend(body);
+                    }
+                }
+            }
+            self.loading.pop();
+
+            nodes
+        }
+
+        /*
+            Loads the file a name in an `include` refers to, found next to the file including it.
+            Its name is the path it is read from, e.g. `include plotting end` in `examples/main.dy`
+            reads `examples/plotting.dy`, while its full path tells whether it is loaded already.
+        */
+        fn include(&mut self, from: FileId, module: &IdentifierNode) -> Vec<Node> {
+            let directory = Path::new(&self.sources.file(from).name).parent().unwrap_or(Path::new("")).to_path_buf();
+            let relative = directory.join(format!("{}.dy", module.name));
+            let name = relative.display().to_string();
+            let path = fs::canonicalize(&relative).unwrap_or_else(|_| relative.clone());
+
+            if let Some(id) = self.sources.find(&path) {
+                if self.loading.contains(&id) {
+                    let includer = &self.sources.file(from).name;
+                    let message = match id == from {
+                        true => format!("`{name}` includes itself"),
+                        false => format!("`{name}` includes itself through `{includer}`"),
+                    };
+                    self.errors.push(Errors::FileError(module.span, message).help(&format!(
+                        "move what `{name}` and `{includer}` both need into a file of its own and include that from each",
+                    )).code(codes::INCLUDE_CYCLE));
+                }
+                return Vec::new();
+            }
+
+            let text = match fs::read_to_string(&relative) {
+                Ok(text) => text,
+                Err(error) => {
+                    self.sources.add_missing(path);
+                    self.errors.push(Self::unreadable(&module.name, &name, &directory, error.kind(), module));
+                    return Vec::new();
+                },
+            };
+
+            let id = self.sources.add(&name, Some(path), text, Some(module.span));
+            self.file(id)
+        }
+
+        /*
+            The error for a program given on the command line that can't be read, which isn't
+            anywhere in the source. A missing file suggests a file nearby with a similar name.
+
+            darcy run mian.dy       "cannot read `mian.dy`: there is no such file", did you mean `main.dy`?
+            darcy run - < image     "cannot read the program from stdin: it isn't valid UTF-8 text"
+            darcy run -e ...        "cannot read the program given with `-e` on the command line: ..."
+        */
+        pub fn unreadable_input(input: &Input, kind: ErrorKind) -> Errors {
+            let path = match input {
+                Input::File(path) => path,
+                Input::Stdin => return Errors::FileError(Span::nowhere(), format!("cannot read the program from stdin: {}", reason(kind))),
+                Input::Inline(_) => return Errors::FileError(Span::nowhere(), format!(
+                    "cannot read the program given with `-e` on the command line: {}",
+                    reason(kind),
+                )),
+            };
+            let error = Errors::FileError(Span::nowhere(), format!("cannot read `{path}`: {}", reason(kind)));
+
+            let path = Path::new(path);
+            let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                return error;
+            };
+            match kind {
+                ErrorKind::NotFound => match suggest::closest(stem, nearby(path.parent().unwrap_or(Path::new(""))).iter().map(String::as_str)) {
+                    Some(close) => error.help(&format!("did you mean `{}`?", path.with_file_name(format!("{close}.dy")).display())),
+                    None => error,
+                },
+                _ => error,
+            }
+        }
+
+        // The error for an included file that can't be read, suggesting a file nearby with a similar name
+        fn unreadable(module: &str, name: &str, directory: &Path, kind: ErrorKind, node: &IdentifierNode) -> Errors {
+            let error = Errors::FileError(node.span, format!("cannot include `{module}`, `{name}` can't be read: {}", reason(kind)));
+            match suggest::closest(module, nearby(directory).iter().map(String::as_str)) {
+                Some(close) => error.fix(node.span, close, &format!("did you mean `{close}`?")),
+                None => error,
+            }
+        }
+    }
+
+    // Says why a file couldn't be read, e.g. "there is no such file"
+    fn reason(kind: ErrorKind) -> String {
+        match kind {
+            ErrorKind::NotFound => "there is no such file".to_string(),
+            ErrorKind::PermissionDenied => "permission to read it was denied".to_string(),
+            ErrorKind::InvalidData => "it isn't valid UTF-8 text".to_string(),
+            ErrorKind::IsADirectory => "it is a directory".to_string(),
+            other => other.to_string(),
+        }
+    }
+
+    // Returns the names (without `.dy`) of the DarcyLang files in a directory
+    fn nearby(directory: &Path) -> Vec<String> {
+        let lookup = if directory.as_os_str().is_empty() { Path::new(".") } else { directory };
+        fs::read_dir(lookup).into_iter().flatten().flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|e| e == "dy"))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{fs, io::ErrorKind, path::Path};
+
+        use super::Loader;
+        use crate::{cli::cli::Input, source::source::Sources};
+
+        #[test]
+        fn unreadable_programs_say_why() {
+            let stdin = Loader::unreadable_input(&Input::Stdin, ErrorKind::InvalidData).diagnostic();
+            assert_eq!(stdin.message, "cannot read the program from stdin: it isn't valid UTF-8 text");
+
+            let inline = Loader::unreadable_input(&Input::Inline("out 1".to_string()), ErrorKind::InvalidData).diagnostic();
+            assert_eq!(inline.message, "cannot read the program given with `-e` on the command line: it isn't valid UTF-8 text");
+
+            let denied = Loader::unreadable_input(&Input::File("main.dy".to_string()), ErrorKind::PermissionDenied).diagnostic();
+            assert_eq!(denied.message, "cannot read `main.dy`: permission to read it was denied");
+
+            let directory = std::env::temp_dir().join(format!("darcy-unreadable-{}", std::process::id()));
+            fs::create_dir_all(&directory).unwrap();
+            fs::write(directory.join("prices.dy"), "").unwrap();
+            let missing = directory.join("prcies.dy");
+            let error = Loader::unreadable_input(&Input::File(missing.display().to_string()), ErrorKind::NotFound).diagnostic();
+            fs::remove_dir_all(&directory).ok();
+
+            assert_eq!(error.message, format!("cannot read `{}`: there is no such file", missing.display()));
+            assert_eq!(error.help, [format!("did you mean `{}`?", directory.join("prices.dy").display())]);
+        }
+
+        #[test]
+        fn includes_that_cant_be_read_are_recorded() {
+            let directory = std::env::temp_dir().join(format!("darcy-missing-{}", std::process::id()));
+            fs::create_dir_all(&directory).unwrap();
+            fs::write(directory.join("lib.dy"), "x = 1\n").unwrap();
+
+            let mut sources = Sources::new();
+            let main = directory.join("main.dy").display().to_string();
+            let root = sources.add(&main, None, "include lib, plots end\nout x".to_string(), None);
+            let errors = Loader::new(&mut sources).load(root).unwrap_err();
+            fs::remove_dir_all(&directory).ok();
+
+            assert_eq!(errors.len(), 1);
+            let missing: Vec<&Path> = sources.missing().collect();
+            assert_eq!(missing, [directory.join("plots.dy")]);
+            assert_eq!(sources.paths().count(), 1);
+        }
+    }
+}
@@ -1,98 +1,272 @@
+// Every file wraps its items in an inner module of the same name (`lexer::lexer`)
+#![allow(clippy::module_inception)]
+
 use std::collections::HashMap;
 use std::env;
-use std::fs::File;
-use std::io::Read;
+use std::fs;
+use std::io::{self, IsTerminal};
 use std::process;
-<<<<<<< HEAD
-
-use lexer::*;
-use error::*;
-use ast::*;
-use scope::*;
-use tokens::*;
-
-use crate::lexer::lexer::Lexer;
-use crate::scope::scope::GlobalEnvironment;
-use crate::tokens::tokens::{Token, TokenKind};
-=======
-//use error::error::{DarcyError, ErrorKind};
-use lexer::lexer::Lexer;
-use tokens::tokens::{Token, TokenKind};
-use error::{error_kind::ErrorKind, darcy_error::DarcyError};
->>>>>>> bd1a31444c128380d505fb63bcef4d74f948947a
+use std::thread;
 
+use crate::ast::nodes::{Annotation, Node};
+use crate::cli::cli::{emit, help, parse, Command, DumpFormat, ErrorFormat, Input, Options};
+use crate::cli::cli::{COMPILE_ERROR, RUNTIME_ERROR, SUCCESS, UNFORMATTED, USAGE_ERROR};
+use crate::codes::codes::{CATALOGUE, explain as explanation_of};
+use crate::diagnostic::diagnostic::{Diagnostic, Severity};
+use crate::dump::dump::{nodes as dump_nodes, tokens as dump_tokens};
+use crate::error::errors::Errors;
+use crate::format::format::{changes, format};
+use crate::interpreter::interpreter::Interpreter;
+use crate::loader::loader::Loader;
+use crate::lint::lint::Linter;
+use crate::repl::repl::Repl;
 use crate::scope::scope::GlobalEnvironment;
+use crate::source::source::{FileId, Sources};
+use crate::watch::watch::watch;
 
+mod cli;
+mod source;
 mod lexer;
 mod error;
+mod diagnostic;
+mod dump;
+mod format;
+mod codes;
+mod suggest;
 mod ast;
+mod lint;
+mod loader;
+mod repl;
 mod scope;
 mod tokens;
+mod matrix;
+mod interpreter;
+mod watch;
+
+// Each DarcyLang call is several nested Rust calls, and the main thread's stack runs out
+// long before `MAX_DEPTH` in a debug build, so everything runs on a thread with a larger one
+const STACK_SIZE: usize = 1024 * 1024 * 1024;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-
-    // Determine file and CLI arguments
-    if args.len() >= 2 {
-        // Get file and make buffer
-        let mut file = File::open(&args[1]).expect("Error reading file!");
-        let mut buffer = String::new();
-
-        // Read file as string into buffer
-        file.read_to_string(&mut buffer).expect("Error");
-
-        // Get lines content
-        let mut lines = Vec::<String>::new();
-        let lines_iter = buffer.chars();
-
-        // Iterate over each character, making new strings when \n found
-        let mut buf = String::new();
-        for c in lines_iter {
-            if c == '\n' {
-                lines.push(buf);
-                buf = String::new();
-
-            } else {
-                buf.push(c);
-            }
-        }
+    let darcy = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(start)
+        .expect("the interpreter's thread should start");
 
-        println!("{:#?}", lines);
+    // `start` exits the process itself, so the thread only finishes early by panicking
+    if darcy.join().is_err() {
+        process::exit(101);
+    }
+}
 
-        // Create the global environment
-        let mut glbl_env = GlobalEnvironment {
-            values: HashMap::new(),
-            children: Vec::new(),
-        };
+fn start() {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-        // Create lexer and iterator
-        let iterator = buffer.chars().peekable();
-        let mut lexer = Lexer::new(iterator, lines, glbl_env);
-
-        // Scan iterator for tokens
-        let (tokens, errors) = lexer.scan();
-
-        // Match CLI args for flags
-        if args.len() == 3 {
-            match args[2].as_str() {
-                "--debug" => {
-                    println!("debugging...");
-                    for token in tokens {
-                        println!("{:#?}", token);
-                    }
-                },
-                "--source" => {
-                    println!("{:?}", buffer);
-                },
-                _ => {},
-            }
+    // Only color diagnostics when they're going to a terminal
+    if !std::io::stderr().is_terminal() {
+        colored::control::set_override(false);
+    }
+
+    let command = match parse(&args) {
+        Ok(command) => command,
+        Err(message) => usage_error(&message),
+    };
+
+    let result = match command {
+        Command::Help(topic) => match help(topic.as_deref()) {
+            Ok(text) => {
+                print!("{text}");
+                Ok(())
+            },
+            Err(message) => usage_error(&message),
+        },
+        Command::Version => {
+            println!("darcy {}", env!("CARGO_PKG_VERSION"));
+            Ok(())
+        },
+        Command::Explain(code) => explain(code.as_ref()),
+        Command::Repl(format) => process::exit(Repl::new(format).start()),
+        Command::Lex(options) => print_tokens(&options),
+        Command::Parse(options) => print_tree(&options),
+        Command::Check(options) => check(&options),
+        Command::Run(options) if options.watch => watch(options.input.name(), |sources| run(&options, sources)),
+        Command::Run(options) => run(&options, &mut Sources::new()),
+        Command::Fmt(options) => format_file(&options),
+    };
+
+    process::exit(match result {
+        Ok(()) => SUCCESS,
+        Err(status) => status,
+    });
+}
+
+// Prints what was wrong with the command line and exits
+fn usage_error(message: &str) -> ! {
+    eprintln!("{message}");
+    eprintln!("Run `darcy help` to see the commands and options.");
+    process::exit(USAGE_ERROR);
+}
+
+/*
+    Each step below reports what went wrong itself and gives back the status to
+    exit with, so a program can be run again (by `--watch`) without exiting.
+*/
+
+// Reads the program (a file, stdin or the code after `-e`) into the source database
+// Every position reported from here on is worked out from the database
+fn read(options: &Options, sources: &mut Sources) -> Result<FileId, i32> {
+    let (buffer, path) = match &options.input {
+        Input::File(path) => (fs::read_to_string(path), fs::canonicalize(path).ok()),
+        Input::Stdin => (io::read_to_string(io::stdin()), None),
+        Input::Inline(code) => (Ok(code.clone()), None),
+    };
+
+    match buffer {
+        Ok(buffer) => Ok(sources.add(options.input.name(), path, buffer, None)),
+        Err(error) => Err(fail(&[Loader::unreadable_input(&options.input, error.kind())], sources, options.format, COMPILE_ERROR)),
+    }
+}
+
+
+// Lexes and parses the file along with everything it includes
+fn load(sources: &mut Sources, root: FileId, format: ErrorFormat) -> Result<(Vec<Node>, Vec<Annotation>), i32> {
+    Loader::new(sources).load(root).map_err(|errors| fail(&errors, sources, format, COMPILE_ERROR))
+}
+
+// Reports the warnings in the program, denied lints stop it from going any further
+fn lint(nodes: &[Node], annotations: &[Annotation], sources: &Sources, options: &Options) -> Result<(), i32> {
+    match Linter::new(options.levels.clone(), annotations, sources).check(nodes) {
+        Ok(warnings) if report(&warnings, sources, options.format) => Err(COMPILE_ERROR),
+        Ok(_) => Ok(()),
+        Err(error) => Err(fail(&[error], sources, options.format, COMPILE_ERROR)),
+    }
+}
+
+// `darcy lex`, only the program itself is lexed and not the files it includes
+fn print_tokens(options: &Options) -> Result<(), i32> {
+    let mut sources = Sources::new();
+    let root = read(options, &mut sources)?;
+    let tokens = Loader::tokens(&sources, root).map_err(|errors| fail(&errors, &sources, options.format, COMPILE_ERROR))?;
+    print!("{}", dump_tokens(&tokens, &sources, options.dump.unwrap_or(DumpFormat::Compact)));
+    Ok(())
+}
+
+// `darcy parse`
+fn print_tree(options: &Options) -> Result<(), i32> {
+    let mut sources = Sources::new();
+    let root = read(options, &mut sources)?;
+    let (nodes, _) = load(&mut sources, root, options.format)?;
+    print!("{}", dump_nodes(&nodes, &sources, options.dump.unwrap_or(DumpFormat::Sexpr)));
+    Ok(())
+}
+
+// `darcy check`, everything `run` does short of running the program
+fn check(options: &Options) -> Result<(), i32> {
+    let mut sources = Sources::new();
+    let root = read(options, &mut sources)?;
+    let (nodes, annotations) = load(&mut sources, root, options.format)?;
+    lint(&nodes, &annotations, &sources, options)
+}
+
+// `darcy fmt`, a file is rewritten in place while stdin and `-e` are printed formatted
+fn format_file(options: &Options) -> Result<(), i32> {
+    let mut sources = Sources::new();
+    let root = read(options, &mut sources)?;
+    let before = sources.file(root).map.text();
+    let after = format(&sources.file(root).map).map_err(|errors| fail(&errors, &sources, options.format, COMPILE_ERROR))?;
+
+    if options.check {
+        let Some((line, old, new)) = changes(before, &after) else {
+            return Ok(());
+        };
+        println!("`{}` needs formatting, from line {line}:", options.input.name());
+        for text in old {
+            println!("-{text}");
+        }
+        for text in new {
+            println!("+{text}");
         }
+        if let Input::File(path) = &options.input {
+            println!("Run `darcy fmt {path}` to format it.");
+        }
+        return Err(UNFORMATTED);
+    }
 
-        process::exit(0);
-    } else {
-        eprint!("\nSource code file path not specified!");
-        eprint!("\nUsage: cargo run <path> <--flags>\n");
-        
-        process::exit(1);
+    match &options.input {
+        Input::File(_) if before == after => Ok(()),
+        Input::File(path) => fs::write(path, &after).map_err(|error| {
+            eprintln!("cannot write the formatted file to `{path}`: {error}");
+            RUNTIME_ERROR
+        }),
+        _ => {
+            print!("{after}");
+            Ok(())
+        },
     }
 }
+
+// Reads, checks and runs a program, leaving every file it read in `sources`
+fn run(options: &Options, sources: &mut Sources) -> Result<(), i32> {
+    let root = read(options, sources)?;
+    let (nodes, annotations) = load(sources, root, options.format)?;
+    lint(&nodes, &annotations, sources, options)?;
+
+    // Create the global environment
+    let mut glbl_env = GlobalEnvironment {
+        values: HashMap::new(),
+        children: Vec::new(),
+    };
+
+    // Walk the AST
+    let mut interpreter = Interpreter::new(&mut glbl_env, sources).with_args(options.args.clone());
+    match interpreter.run(&nodes) {
+        Ok(()) => Ok(()),
+        Err(Errors::Exit(SUCCESS)) => Ok(()),
+        Err(Errors::Exit(code)) => Err(code),
+        Err(error) => Err(fail(&[error], sources, options.format, RUNTIME_ERROR)),
+    }
+}
+
+// Reports the errors, giving back the status given
+fn fail(errors: &[Errors], sources: &Sources, format: ErrorFormat, status: i32) -> i32 {
+    let diagnostics: Vec<Diagnostic> = errors.iter().map(Errors::diagnostic).collect();
+    report(&diagnostics, sources, format);
+    status
+}
+
+// Prints every diagnostic, then where to find out more about them
+// Returns whether any of them is an error (rather than a warning)
+fn report(diagnostics: &[Diagnostic], sources: &Sources, format: ErrorFormat) -> bool {
+    emit(diagnostics, sources, format);
+
+    let Some(error) = diagnostics.iter().find(|d| d.severity == Severity::Error) else {
+        return false;
+    };
+
+    if format == ErrorFormat::Human {
+        let code = error.code.as_deref().unwrap_or("D0201");
+        eprintln!("For more about an error, run `darcy explain <code>`, e.g. `darcy explain {code}`.");
+    }
+    true
+}
+
+// Prints the long-form explanation of an error code
+fn explain(code: Option<&String>) -> ! {
+    let explanation = match code.and_then(|c| explanation_of(c)) {
+        Some(explanation) => explanation,
+        None => {
+            match code {
+                Some(code) => eprintln!("`{code}` is not an error code. The codes are:"),
+                None => eprintln!("Usage: darcy explain <code>, e.g. `darcy explain D0101`. The codes are:"),
+            }
+            for e in CATALOGUE {
+                eprintln!("  {}  {}", e.code, e.title);
+            }
+            process::exit(USAGE_ERROR);
+        },
+    };
+
+    println!("{}: {}\n", explanation.code, explanation.title);
+    print!("{}", explanation.text);
+    process::exit(SUCCESS);
+}
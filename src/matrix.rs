@@ -0,0 +1,297 @@
+pub mod matrix {
+    use std::fmt;
+
+    // Pivots smaller than this are treated as zero during elimination
+    const EPSILON: f64 = 1e-10;
+
+    // Results are shown to this many significant digits, which hides the rounding errors of elimination
+    const SIGNIFICANT: usize = 12;
+
+    // The most rows (or columns) `identity` makes, so a typo can't ask for gigabytes
+    pub const MAX_SIZE: usize = 2048;
+
+    // Matrix struct stores its shape and the entries in row-major order
+    // Indices here are 0-based, the interpreter converts from DarcyLang's 1-based indices
+    #[derive(Debug, PartialEq, Clone)]
+    pub struct Matrix {
+        pub rows: usize,
+        pub cols: usize,
+        data: Vec<f64>,
+    }
+
+    impl Matrix {
+        // Creates a matrix from a list of rows
+        // Returns an error message if the rows are empty or not all the same length
+        pub fn new(rows: Vec<Vec<f64>>) -> Result<Self, String> {
+            let cols = match rows.first() {
+                Some(first) if !first.is_empty() => first.len(),
+                _ => return Err("a matrix must have at least one row and one column".to_string()),
+            };
+
+            for (i, row) in rows.iter().enumerate() {
+                if row.len() != cols {
+                    return Err(format!(
+                        "row {} has {} entries but row 1 has {}; every row of a matrix must be the same length",
+                        i + 1, row.len(), cols,
+                    ));
+                }
+            }
+
+            Ok(Self {
+                rows: rows.len(),
+                cols,
+                data: rows.into_iter().flatten().collect(),
+            })
+        }
+
+        // Creates the n x n identity matrix
+        // Returns an error message if n is over MAX_SIZE
+        pub fn identity(n: usize) -> Result<Self, String> {
+            if n > MAX_SIZE {
+                return Err(format!("a {n}x{n} identity matrix is too large, it can have at most {MAX_SIZE} rows"));
+            }
+
+            let mut data = vec![0.0; n * n];
+            for i in 0..n {
+                data[i * n + i] = 1.0;
+            }
+
+            Ok(Self { rows: n, cols: n, data })
+        }
+
+        // Returns the shape formatted as it appears in error messages, e.g. `2x3`
+        pub fn shape(&self) -> String {
+            format!("{}x{}", self.rows, self.cols)
+        }
+
+        // Returns the entry at (row, col)
+        pub fn get(&self, row: usize, col: usize) -> f64 {
+            self.data[row * self.cols + col]
+        }
+
+        // Returns a copy of a single row
+        pub fn row(&self, row: usize) -> Vec<f64> {
+            self.data[row * self.cols..(row + 1) * self.cols].to_vec()
+        }
+
+        pub fn transpose(&self) -> Self {
+            let mut data = Vec::with_capacity(self.data.len());
+            for c in 0..self.cols {
+                for r in 0..self.rows {
+                    data.push(self.get(r, c));
+                }
+            }
+
+            Self { rows: self.cols, cols: self.rows, data }
+        }
+
+        // Multiplies every entry by a scalar
+        pub fn scale(&self, k: f64) -> Self {
+            Self {
+                rows: self.rows,
+                cols: self.cols,
+                data: self.data.iter().map(|x| x * k).collect(),
+            }
+        }
+
+        // Adds (or subtracts) two matrices of the same shape entry by entry
+        pub fn add(&self, other: &Matrix, sign: f64) -> Result<Self, String> {
+            if self.rows != other.rows || self.cols != other.cols {
+                return Err(format!(
+                    "cannot {} a {} matrix and a {} matrix; both must have the same shape",
+                    if sign < 0.0 { "subtract" } else { "add" }, self.shape(), other.shape(),
+                ));
+            }
+
+            Ok(Self {
+                rows: self.rows,
+                cols: self.cols,
+                data: self.data.iter().zip(&other.data).map(|(a, b)| a + sign * b).collect(),
+            })
+        }
+
+        pub fn multiply(&self, other: &Matrix) -> Result<Self, String> {
+            if self.cols != other.rows {
+                return Err(format!(
+                    "cannot multiply a {} matrix by a {} matrix; the left has {} columns but the right has {} rows",
+                    self.shape(), other.shape(), self.cols, other.rows,
+                ));
+            }
+
+            let mut data = vec![0.0; self.rows * other.cols];
+            for r in 0..self.rows {
+                for c in 0..other.cols {
+                    data[r * other.cols + c] = (0..self.cols)
+                        .map(|k| self.get(r, k) * other.get(k, c))
+                        .sum();
+                }
+            }
+
+            Ok(Self { rows: self.rows, cols: other.cols, data })
+        }
+
+        // Returns an error naming the operation if the matrix is not square
+        fn require_square(&self, operation: &str) -> Result<(), String> {
+            if self.rows != self.cols {
+                return Err(format!("the {operation} is only defined for square matrices, but this matrix is {}", self.shape()));
+            }
+            Ok(())
+        }
+
+        /*
+            The following operations all run Gaussian elimination with partial pivoting
+            on a copy of the entries, eliminate() returns the reduced rows along with
+            the pivot columns found and the number of row swaps made.
+        */
+        fn eliminate(&self) -> (Vec<Vec<f64>>, Vec<usize>, usize) {
+            let mut rows: Vec<Vec<f64>> = (0..self.rows).map(|r| self.row(r)).collect();
+            let mut pivots = Vec::new();
+            let mut swaps = 0;
+            let mut pivot_row = 0;
+
+            for col in 0..self.cols {
+                if pivot_row == self.rows {
+                    break;
+                }
+
+                // Pick the largest entry in the column as the pivot
+                let best = (pivot_row..self.rows)
+                    .max_by(|a, b| rows[*a][col].abs().total_cmp(&rows[*b][col].abs()))
+                    .unwrap();
+                if rows[best][col].abs() < EPSILON {
+                    continue;
+                }
+
+                if best != pivot_row {
+                    rows.swap(best, pivot_row);
+                    swaps += 1;
+                }
+
+                // Clear out the column below the pivot
+                let pivot = rows[pivot_row].clone();
+                for row in rows.iter_mut().skip(pivot_row + 1) {
+                    let factor = row[col] / pivot[col];
+                    for c in col..self.cols {
+                        row[c] -= factor * pivot[c];
+                    }
+                }
+
+                pivots.push(col);
+                pivot_row += 1;
+            }
+
+            (rows, pivots, swaps)
+        }
+
+        pub fn determinant(&self) -> Result<f64, String> {
+            self.require_square("determinant")?;
+
+            let (rows, pivots, swaps) = self.eliminate();
+            if pivots.len() < self.rows {
+                return Ok(0.0);
+            }
+
+            let product: f64 = (0..self.rows).map(|i| rows[i][i]).product();
+            Ok(tidy(if swaps % 2 == 0 { product } else { -product }))
+        }
+
+        pub fn rank(&self) -> usize {
+            self.eliminate().1.len()
+        }
+
+        // Solves self * x = b for x, where b has one column per right-hand side
+        pub fn solve(&self, b: &Matrix) -> Result<Self, String> {
+            self.require_square("solve")?;
+            if b.rows != self.rows {
+                return Err(format!(
+                    "cannot solve a {} system with a {} right-hand side; both must have {} rows",
+                    self.shape(), b.shape(), self.rows,
+                ));
+            }
+
+            // Augment the matrix with the right-hand side and eliminate
+            let mut augmented = Vec::with_capacity(self.rows);
+            for r in 0..self.rows {
+                let mut row = self.row(r);
+                row.extend(b.row(r));
+                augmented.push(row);
+            }
+            let augmented = Matrix::new(augmented)?;
+            let (mut rows, pivots, _) = augmented.eliminate();
+
+            if pivots.len() < self.rows || pivots[self.rows - 1] >= self.cols {
+                return Err("the matrix is singular (its determinant is 0), so the system has no unique solution".to_string());
+            }
+
+            // Back substitute from the last row upwards
+            let n = self.rows;
+            for r in (0..n).rev() {
+                for c in n..n + b.cols {
+                    let sum: f64 = (r + 1..n).map(|k| rows[r][k] * rows[k][c]).sum();
+                    rows[r][c] = (rows[r][c] - sum) / rows[r][r];
+                }
+            }
+
+            let solution = rows.into_iter().map(|row| row[n..].to_vec()).collect();
+            Matrix::new(solution)
+        }
+
+        pub fn inverse(&self) -> Result<Self, String> {
+            self.require_square("inverse")?;
+            self.solve(&Matrix::identity(self.rows)?).map_err(|e| {
+                if e.starts_with("the matrix is singular") {
+                    "the matrix is singular (its determinant is 0), so it has no inverse".to_string()
+                } else {
+                    e
+                }
+            })
+        }
+    }
+
+    /*
+        Cleans up the rounding errors left behind by elimination, so the inverse of a matrix
+        of whole numbers shows 0.333333333333 rather than 0.33333333330000003.
+        Anything closer to 0 than EPSILON (-0 included) is 0, everything else keeps SIGNIFICANT
+        digits, which works the same for 1e-9 and 1e300.
+    */
+    fn tidy(x: f64) -> f64 {
+        if x.abs() < EPSILON {
+            return 0.0;
+        }
+        if !x.is_finite() {
+            return x;
+        }
+        format!("{:.*e}", SIGNIFICANT - 1, x).parse().unwrap_or(x)
+    }
+
+    // Prints each row on its own line, the same way matrix literals are written
+    impl fmt::Display for Matrix {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for r in 0..self.rows {
+                let entries: Vec<String> = self.row(r).into_iter().map(|x| tidy(x).to_string()).collect();
+
+                if r > 0 {
+                    writeln!(f)?;
+                }
+                write!(f, "[{}]", entries.join(", "))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn entries_keep_their_significant_digits() {
+            assert_eq!(tidy(1.0 / 3.0 - 3e-11), 0.333333333303);
+            assert_eq!(tidy(120.00000000000003), 120.0);
+            assert_eq!(tidy(1e300), 1e300);
+            assert_eq!(tidy(-1e-12), 0.0);
+            let m = Matrix { rows: 1, cols: 2, data: vec![1e300, 0.1 + 0.2] };
+            let shown = m.to_string();
+            assert!(shown.starts_with("[1000") && shown.ends_with(", 0.3]"), "{shown}");
+        }
+    }
+}
@@ -0,0 +1,250 @@
+pub mod repl {
+    use std::{collections::HashMap, fs, io::{self, BufRead, Write}};
+
+    use crate::{
+        ast::{ast::Ast, nodes::Node},
+        cli::cli::{emit, ErrorFormat, Input, SUCCESS},
+        error::errors::Errors,
+        interpreter::interpreter::{Interpreter, PRELUDE},
+        lexer::lexer::Lexer,
+        loader::loader::Loader,
+        scope::{scope::GlobalEnvironment, values::Type},
+        source::source::{FileId, SourceMap, Sources},
+    };
+
+    const HELP: &str = "\
+Type DarcyLang to run it, the value of an expression is printed back.
+A line that opens a block (`if`, `func`, `matrix`, ...) keeps reading until its `end`.
+
+    :type <expr>     Print the type of an expression
+    :env             List the names that are defined, with their values
+    :load <file>     Run a file, keeping what it defines
+    :history         List what has been entered so far
+    :help            Print this
+    :quit            Leave, as do Ctrl-D and `sys.exit(code)`
+";
+
+    /*
+        An interactive session. Every entry is a file of its own in the source database
+        (`<repl:3>`), so errors show the entry they're in, and every entry runs in the same
+        global environment, so names defined in one can be used in the next.
+
+        >>> func double <- x
+        ...     return x * 2
+        ... end
+        >>> double(21)
+        42
+    */
+    pub struct Repl {
+        sources: Sources,
+        glbl_env: GlobalEnvironment,
+        format: ErrorFormat,
+        history: Vec<String>,
+    }
+
+    impl Repl {
+        pub fn new(format: ErrorFormat) -> Self {
+            // An empty file stands in for the program, so every entry is shown with its name
+            let mut sources = Sources::new();
+            sources.add("<repl>", None, String::new(), None);
+
+            let glbl_env = GlobalEnvironment { values: HashMap::new(), children: Vec::new() };
+            Self { sources, glbl_env, format, history: Vec::new() }
+        }
+
+        // Reads and runs entries until the input ends, `:quit` or `sys.exit(code)`
+        // Returns the code the process should exit with
+        pub fn start(&mut self) -> i32 {
+            println!("darcy {}, `:help` for help, `:quit` or Ctrl-D to leave", env!("CARGO_PKG_VERSION"));
+
+            let stdin = io::stdin();
+            let mut entry = String::new();
+            loop {
+                print!("{}", if entry.is_empty() { ">>> " } else { "... " });
+                io::stdout().flush().ok();
+
+                let mut line = String::new();
+                match stdin.lock().read_line(&mut line) {
+                    Ok(0) | Err(_) => {
+                        println!();
+                        return SUCCESS;
+                    },
+                    Ok(_) => {},
+                }
+
+                if entry.is_empty() {
+                    let command = line.trim();
+                    if command.is_empty() {
+                        continue;
+                    }
+                    if let Some(command) = command.strip_prefix(':') {
+                        if let Some(code) = self.command(command) {
+                            return code;
+                        }
+                        continue;
+                    }
+                }
+
+                entry.push_str(&line);
+                if Self::unfinished(&entry) {
+                    continue;
+                }
+
+                let text = std::mem::take(&mut entry);
+                self.history.push(text.trim_end().to_string());
+                match self.evaluate(&format!("<repl:{}>", self.history.len()), text) {
+                    Ok(Some(value)) => {
+                        // Nothing is echoed for nil, such as a call to a function that doesn't return
+                        if !matches!(value, Type::Nil(_)) {
+                            println!("{}", value.repr());
+                        }
+                    },
+                    Ok(None) => {},
+                    Err(code) => return code,
+                }
+            }
+        }
+
+        // Runs a `:command`, returning the exit code when it leaves the REPL
+        fn command(&mut self, command: &str) -> Option<i32> {
+            let (name, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+            let argument = argument.trim();
+
+            match name {
+                "q" | "quit" | "exit" => return Some(SUCCESS),
+                "help" | "h" => print!("{HELP}"),
+                "type" | "t" if argument.is_empty() => println!("`:type` takes an expression, e.g. `:type [1, 2.5]`"),
+                "type" | "t" => {
+                    match self.evaluate("<repl:type>", format!("{argument}\n")) {
+                        Ok(Some(value)) => println!("{}", value.name()),
+                        Ok(None) => {},
+                        Err(code) => return Some(code),
+                    }
+                },
+                "env" => self.environment(),
+                "load" | "l" => return self.load(argument).err(),
+                "history" => {
+                    for (i, entry) in self.history.iter().enumerate() {
+                        let mut lines = entry.lines();
+                        println!("{:>4}  {}", i + 1, lines.next().unwrap_or(""));
+                        for line in lines {
+                            println!("      {line}");
+                        }
+                    }
+                },
+                other => println!("unknown command `:{other}`, `:help` lists them"),
+            }
+            None
+        }
+
+        // Returns whether an entry opens a block (or bracket) it hasn't closed yet
+        // An entry the lexer can't read is finished, so its errors get reported
+        fn unfinished(entry: &str) -> bool {
+            let source = SourceMap::new(entry.to_string());
+            let mut lexer = Lexer::new(&source);
+            let (tokens, errors) = lexer.scan();
+            errors.is_empty() && Ast::new(tokens.clone()).unclosed()
+        }
+
+        // Runs an entry, returning the value it ends with when it is an expression
+        // An entry that calls `sys.exit(code)` gives back the code instead
+        fn evaluate(&mut self, name: &str, text: String) -> Result<Option<Type>, i32> {
+            let file = self.sources.add(name, None, text, None);
+            match self.parse(file) {
+                Some(nodes) => self.run(&nodes),
+                None => Ok(None),
+            }
+        }
+
+        // Runs a file as though it had been entered, without echoing anything
+        fn load(&mut self, path: &str) -> Result<(), i32> {
+            if path.is_empty() {
+                println!("`:load` takes a file, e.g. `:load examples/plotting.dy`");
+                return Ok(());
+            }
+
+            let text = match fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(error) => {
+                    self.report(&[Loader::unreadable_input(&Input::File(path.to_string()), error.kind())]);
+                    return Ok(());
+                },
+            };
+            let file = self.sources.add(path, fs::canonicalize(path).ok(), text, None);
+            if let Some(nodes) = self.parse(file) {
+                self.run(&nodes)?;
+            }
+            Ok(())
+        }
+
+        fn parse(&mut self, file: FileId) -> Option<Vec<Node>> {
+            match Loader::new(&mut self.sources).load(file) {
+                Ok((nodes, _)) => Some(nodes),
+                Err(errors) => {
+                    self.report(&errors);
+                    None
+                },
+            }
+        }
+
+        fn run(&mut self, nodes: &[Node]) -> Result<Option<Type>, i32> {
+            let mut interpreter = Interpreter::new(&mut self.glbl_env, &self.sources);
+            match interpreter.run_value(nodes) {
+                Ok(value) => Ok(value),
+                Err(Errors::Exit(code)) => Err(code),
+                Err(error) => {
+                    self.report(&[error]);
+                    Ok(None)
+                },
+            }
+        }
+
+        // Errors are printed and the session carries on
+        fn report(&self, errors: &[Errors]) {
+            let diagnostics: Vec<_> = errors.iter().map(Errors::diagnostic).collect();
+            emit(&diagnostics, &self.sources, self.format);
+        }
+
+        // Prints every name in the global environment, apart from the prelude
+        fn environment(&self) {
+            let mut names: Vec<&String> = self.glbl_env.values.keys().filter(|name| !PRELUDE.contains(&name.as_str())).collect();
+            if names.is_empty() {
+                println!("nothing is defined yet");
+                return;
+            }
+
+            names.sort();
+            for name in names {
+                let value = &self.glbl_env.values[name].typ;
+                println!("{name}: {} = {}", value.name(), value.repr());
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::Repl;
+        use crate::{cli::cli::ErrorFormat, scope::values::Type};
+
+        #[test]
+        fn blocks_keep_reading_until_their_end() {
+            assert!(Repl::unfinished("func double <- x\n"));
+            assert!(Repl::unfinished("func double <- x\nif x > 0\nreturn x * 2\nend\n"));
+            assert!(Repl::unfinished("xs = [1,\n"));
+            assert!(!Repl::unfinished("func double <- x\nreturn x * 2\nend\n"));
+            assert!(!Repl::unfinished("out 1 + 2\n"));
+            // An unterminated string can't be finished by more lines, so its error is shown
+            assert!(!Repl::unfinished("out \"open\n"));
+        }
+
+        #[test]
+        fn entries_share_names_and_echo_expressions() {
+            let mut repl = Repl::new(ErrorFormat::Human);
+            assert_eq!(repl.evaluate("<repl:1>", "func double <- x\nreturn x * 2\nend\n".to_string()), Ok(None));
+            assert_eq!(repl.evaluate("<repl:2>", "double(21)\n".to_string()), Ok(Some(Type::Integer(42))));
+            // An error is reported and the session carries on
+            assert_eq!(repl.evaluate("<repl:3>", "missing(1)\n".to_string()), Ok(None));
+            assert_eq!(repl.evaluate("<repl:4>", "sys.exit(4)\n".to_string()), Err(4));
+        }
+    }
+}
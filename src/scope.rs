@@ -1,119 +1,257 @@
 pub mod values {
-    use super::scope::Environment;
+    use std::{fmt, rc::Rc};
+    use crate::{ast::nodes::FuncNode, diagnostic::diagnostic::Span, matrix::matrix::Matrix};
+
+    // A function along with the environment it was defined in
+    // Calls run in a new child of that environment
+    #[derive(Debug, Clone)]
+    pub struct Function {
+        pub node: Rc<FuncNode>,
+        pub env: usize,
+    }
+
+    // Two functions are only equal if they are the same definition
+    impl PartialEq for Function {
+        fn eq(&self, other: &Self) -> bool {
+            Rc::ptr_eq(&self.node, &other.node) && self.env == other.env
+        }
+    }
+
+    /*
+        Where a nil value came from, e.g. "`scores.max()` on line 4 (the list is empty)".
+        It only exists for error messages, so every nil compares equal no matter the origin.
+    */
+    #[derive(Debug, Clone, Default)]
+    pub struct NilOrigin(pub Option<Rc<str>>);
+
+    impl PartialEq for NilOrigin {
+        fn eq(&self, _: &Self) -> bool {
+            true
+        }
+    }
+
+    impl NilOrigin {
+        pub fn new(origin: String) -> Self {
+            Self(Some(origin.into()))
+        }
+    }
+
+    // An enum declaration, each variant may have a single field
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Enum {
+        pub name: String,
+        pub variants: Vec<(String, bool)>,
+    }
+
+    // A value of an enum, e.g. `Option:Some(5)`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Variant {
+        pub enum_name: String,
+        pub name: String,
+        pub field: Option<Box<Type>>,
+    }
+
+    /*
+        An error that was caught by `try ... catch err`, or is about to be raised.
+        `kind` is what it's reported as (`IndexError`, or `Error` for a raised Str)
+        and `span` is where it first happened, which stays the same if it's raised again.
+    */
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ErrorValue {
+        pub kind: String,
+        pub message: String,
+        pub span: Span,
+        pub code: &'static str,
+    }
+
+    // Map stores its entries in the order they were first inserted
+    // Lookups are linear, which is fine for the size of maps DarcyLang programs build
+    #[derive(Debug, Clone, Default)]
+    pub struct Map {
+        entries: Vec<(Type, Type)>,
+    }
+
+    impl Map {
+        // Returns an error message if the value can't be used as a key
+        // Keys have to compare reliably, so Num (floating point) and containers that can change are out
+        pub fn check_key(key: &Type) -> Result<(), String> {
+            match key {
+                Type::Integer(_) | Type::Bool(_) | Type::Str(_) => Ok(()),
+                Type::Tuple(items) => items.iter().try_for_each(Self::check_key),
+                other => Err(format!("a value of type {} can't be used as a map key, use a Str, Int, Bool or Tuple", other.name())),
+            }
+        }
+
+        pub fn get(&self, key: &Type) -> Option<&Type> {
+            self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+        }
+
+        // Inserts a value, replacing (but keeping the position of) any existing entry
+        pub fn insert(&mut self, key: Type, value: Type) {
+            match self.entries.iter_mut().find(|(k, _)| *k == key) {
+                Some(entry) => entry.1 = value,
+                None => self.entries.push((key, value)),
+            }
+        }
+
+        pub fn remove(&mut self, key: &Type) -> Option<Type> {
+            let position = self.entries.iter().position(|(k, _)| k == key)?;
+            Some(self.entries.remove(position).1)
+        }
+
+        pub fn len(&self) -> usize {
+            self.entries.len()
+        }
+
+        pub fn entries(&self) -> &[(Type, Type)] {
+            &self.entries
+        }
+    }
+
+    // Maps are equal if they have the same entries in any order
+    impl PartialEq for Map {
+        fn eq(&self, other: &Self) -> bool {
+            self.len() == other.len() && self.entries.iter().all(|(k, v)| other.get(k) == Some(v))
+        }
+    }
 
-<<<<<<< HEAD
     #[derive(Debug, PartialEq, Clone)]
-    pub enum ValueKind {
-=======
-    #[derive(Debug, PartialEq)]
     pub enum Type {
->>>>>>> bd1a31444c128380d505fb63bcef4d74f948947a
         // Integers
         Integer(i32),
 
         // Numbers
-        Number(f32),
-        
+        Number(f64),
+
         // Other
+        Nil(NilOrigin),
+        Bool(bool),
         Str(String),
+        List(Vec<Type>),
+        Tuple(Vec<Type>),
+        Map(Map),
+        Matrix(Matrix),
+        Function(Function),
+        Enum(Enum),
+        Variant(Variant),
+        Error(ErrorValue),
+        // A built-in module such as `sys`, its functions are called as methods, e.g. `sys.args()`
+        Module(&'static str),
     }
 
-    // Value struct stores the kind, data, and environment a value belongs to
-    // Values can be linked to identifiers via HashMap
-    #[derive(Debug)]
-    pub struct Value {
-        typ: Type,
-        env: u8,
-    }
+    impl Type {
+        // Returns the name of the type as it is written in DarcyLang
+        pub fn name(&self) -> &'static str {
+            match self {
+                Type::Integer(_) => "Int",
+                Type::Number(_) => "Num",
+                Type::Nil(_) => "Nil",
+                Type::Bool(_) => "Bool",
+                Type::Str(_) => "Str",
+                Type::List(_) => "List",
+                Type::Tuple(_) => "Tuple",
+                Type::Map(_) => "Map",
+                Type::Matrix(_) => "Matrix",
+                Type::Function(_) => "Func",
+                Type::Enum(_) | Type::Variant(_) => "Enum",
+                Type::Error(_) => "Error",
+                Type::Module(_) => "Module",
+            }
+        }
 
-    impl Value {
-        // Constructs a new value with the kind specified
-        pub fn num_or_int(val: String, env: u8) -> Self {
-            // Determine value type
-            if val.contains(".") {
-                // Parse floating point literals
-                let num: f32 = match val.parse() {
-                    Ok(v) => v,
-                    Err(_) => 0.0 // TODO: Error handle here
-                };
-
-                // Return value with Number kind
-                Self {
-                    typ: Type::Number(num),
-                    env,
-                }
-            } else {
-                // Parse integer literals
-                let num: i32 = match val.parse() {
-                    Ok(v) => v,
-                    Err(_) => 0 // TODO: Error handle here
-                };
-
-                // Return self with Integer kind
-                Self {
-                    typ: Type::Integer(num),
-                    env,
-                }
+        // Returns the value the way it is written inside a list or tuple
+        // Strings are quoted so `["a, b"]` and `["a", "b"]` print differently
+        pub fn repr(&self) -> String {
+            match self {
+                Type::Str(s) => format!("{s:?}"),
+                other => other.to_string(),
             }
         }
 
-        // Creates a new value with string type
-        // Returns the value instance
-        pub fn str(val: String, env: u8) -> Self {
-            Self {
-                typ: Type::Str(val),
-                env,
+        // The value of expressions that have nothing to give back, such as loops
+        pub fn nil() -> Self {
+            Type::Nil(NilOrigin::default())
+        }
+
+        // `Option:Some(value)` and `Option:None` from the prelude
+        pub fn some(value: Type) -> Self {
+            Type::Variant(Variant { enum_name: "Option".to_string(), name: "Some".to_string(), field: Some(Box::new(value)) })
+        }
+
+        pub fn none() -> Self {
+            Type::Variant(Variant { enum_name: "Option".to_string(), name: "None".to_string(), field: None })
+        }
+
+        // Returns the value as a float if it is numeric
+        pub fn as_number(&self) -> Option<f64> {
+            match self {
+                Type::Integer(i) => Some(*i as f64),
+                Type::Number(n) => Some(*n),
+                _ => None,
             }
         }
-        
-        // Creates a new value with the type specified in parameteres
-        // Returns the value instance
-        pub fn from(typ: &str, val: String, env: u8) -> Self {
-            match typ {
-                "int" => {
-                    Self {
-                        typ: Type::Integer(Self::parse_int(val)),
-                        env,
-                    }
+    }
+
+    impl fmt::Display for Type {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Type::Integer(i) => write!(f, "{i}"),
+                Type::Number(n) => write!(f, "{n}"),
+                Type::Nil(_) => write!(f, "nil"),
+                Type::Bool(b) => write!(f, "{b}"),
+                Type::Str(s) => write!(f, "{s}"),
+                Type::List(items) => {
+                    let items: Vec<String> = items.iter().map(|i| i.repr()).collect();
+                    write!(f, "[{}]", items.join(", "))
                 },
-                "num" => {
-                    Self {
-                        typ: Type::Number(Self::parse_float(val)),
-                        env,
+                Type::Tuple(items) => {
+                    let items: Vec<String> = items.iter().map(|i| i.repr()).collect();
+                    // A tuple of one keeps its comma so it doesn't read as a plain value
+                    if items.len() == 1 {
+                        write!(f, "({},)", items[0])
+                    } else {
+                        write!(f, "({})", items.join(", "))
                     }
                 },
-                _ => panic!("KIND SPECIFIER `{}` NOT VALID!", typ)
+                Type::Map(map) => {
+                    let entries: Vec<String> = map.entries().iter()
+                        .map(|(k, v)| format!("{}: {}", k.repr(), v.repr()))
+                        .collect();
+                    write!(f, "{{{}}}", entries.join(", "))
+                },
+                Type::Matrix(m) => write!(f, "{m}"),
+                Type::Function(func) => write!(f, "<func {}>", func.node.name),
+                Type::Enum(e) => write!(f, "<enum {}>", e.name),
+                Type::Variant(v) => match &v.field {
+                    Some(field) => write!(f, "{}:{}({})", v.enum_name, v.name, field.repr()),
+                    None => write!(f, "{}:{}", v.enum_name, v.name),
+                },
+                Type::Error(e) => write!(f, "{}: {}", e.kind, e.message),
+                Type::Module(name) => write!(f, "<module {name}>"),
             }
         }
+    }
 
-        // Helper function that parses integers
-        fn parse_int(val: String) -> i32 {
-            // Parse integer literals
-            let num: i32 = match val.parse() {
-                Ok(v) => v,
-                Err(_) => 0 // TODO: Error handle here
-            };
-
-            num
-        }
-
-        // Helper function that parses floats
-        fn parse_float(val: String) -> f32 {
-            // Parse floating point literals
-            let num: f32 = match val.parse() {
-                Ok(v) => v,
-                Err(_) => 0.0 // TODO: Error handle here
-            };
+    // Value struct stores the type, data, and environment a value belongs to
+    // Values can be linked to identifiers via HashMap
+    #[derive(Debug, Clone)]
+    pub struct Value {
+        pub typ: Type,
+        pub env: usize,
+    }
 
-            num
+    impl Value {
+        // Constructs a new value of the type given, owned by the environment given
+        pub fn new(typ: Type, env: usize) -> Self {
+            Self { typ, env }
         }
     }
 }
 
 pub mod scope {
     use std::collections::HashMap;
-    use super::values::Value;
-    
+    use super::values::{Type, Value};
+
     pub struct GlobalEnvironment {
         pub values: HashMap<String, Value>,
         pub children: Vec<Environment>,
@@ -126,28 +264,122 @@ pub mod scope {
         pub parent: usize,
         pub children: Vec<usize>,
         pub id: usize,
+        // A function was made in it, so it has to outlive the scope that created it
+        pub captured: bool,
     }
 
+    impl GlobalEnvironment {
+        // Returns the values of an environment by ID (0 for GE)
+        fn values_mut(&mut self, env: usize) -> &mut HashMap<String, Value> {
+            if env == 0 {
+                &mut self.values
+            } else {
+                &mut self.children[env - 1].values
+            }
+        }
+
+        // Looks up a name in an environment, then each of its parents
+        // Returns None if the name is not defined anywhere in the chain
+        pub fn get(&self, env: usize, name: &str) -> Option<&Value> {
+            let mut current = env;
+
+            loop {
+                if current == 0 {
+                    return self.values.get(name);
+                }
+
+                let environment = &self.children[current - 1];
+                if let Some(value) = environment.values.get(name) {
+                    return Some(value);
+                }
+                current = environment.parent;
+            }
+        }
+
+        // Returns every name visible from an environment, innermost first
+        pub fn names(&self, env: usize) -> Vec<&str> {
+            let mut names = Vec::new();
+            let mut current = env;
+
+            loop {
+                if current == 0 {
+                    names.extend(self.values.keys().map(String::as_str));
+                    return names;
+                }
+
+                let environment = &self.children[current - 1];
+                names.extend(environment.values.keys().map(String::as_str));
+                current = environment.parent;
+            }
+        }
+
+        // Defines (or overwrites) a name in the environment the value belongs to
+        pub fn set(&mut self, name: &str, value: Value) {
+            self.values_mut(value.env).insert(name.to_string(), value);
+        }
+
+        // Assigns to a name from inside the given environment
+        // Updates the environment the name is already defined in, otherwise defines it in `env`
+        pub fn assign(&mut self, env: usize, name: &str, typ: Type) {
+            let owner = match self.get(env, name) {
+                Some(existing) => existing.env,
+                None => env,
+            };
+
+            self.set(name, Value::new(typ, owner));
+        }
+
+        // Keeps an environment around for a function made in it to call back into later
+        pub fn capture(&mut self, env: usize) {
+            if env != 0 {
+                self.children[env - 1].captured = true;
+            }
+        }
+
+        /*
+            Frees an environment once the scope it was made for has finished, so a long loop
+            or deep recursion doesn't keep every pass around. Environments are only ever freed
+            from the end, which keeps every other ID pointing at the same environment.
+            One that was captured (or that has a captured one after it) stays for good.
+        */
+        pub fn release(&mut self, env: usize) {
+            if env == 0 || env != self.children.len() || self.children[env - 1].captured {
+                return;
+            }
+            let environment = self.children.pop().unwrap();
+            if environment.parent != 0 {
+                self.children[environment.parent - 1].children.retain(|&child| child != env);
+            }
+        }
+    }
+
+    #[allow(clippy::new_ret_no_self)]
     impl Environment {
         // Takes an instance of GlobalEnvironment and any parent Environments (0 for GE)
         // Returns the ID of the environment to be used as a pointer
         pub fn new(g: &mut GlobalEnvironment, parent: usize) -> usize {
             // Construct a new environment
-            let environment = Self { 
+            let environment = Self {
                 values: HashMap::new(),
                 parent,
                 children: Vec::new(),
                 id: g.children.len() + 1,
+                captured: false,
             };
 
             // Get the ID of the environment
             let return_id = environment.id;
 
+            // Register the environment with its parent
+            if parent != 0 {
+                g.children[parent - 1].children.push(return_id);
+            }
+
             // Push the environment to the GlobalEnvironment
             g.children.push(environment);
-            
+
             // Return the ID
             return_id
         }
     }
-}
\ No newline at end of file
+}
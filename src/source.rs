@@ -0,0 +1,348 @@
+pub mod source {
+    use std::path::{Path, PathBuf};
+    use crate::diagnostic::diagnostic::Span;
+
+    /*
+        The text of a file, along with the byte offset each of its lines starts at.
+        Everything that reports a position (lexer, parser, diagnostics) goes through
+        it, so they all agree on what line and column an offset is.
+
+        Lines end at "\n" or "\r\n", and the last one doesn't need to end at all.
+        Lines are numbered from `first`, which is 1 unless the map is one file of many.
+        Columns count characters from 1, so a tab is a single column here and is
+        only widened when a line is printed.
+
+        "a = 1\r\nout a"
+         line 1: "a = 1"    starts at 0
+         line 2: "out a"    starts at 7
+    */
+    pub struct SourceMap {
+        text: String,
+        starts: Vec<usize>,
+        first: usize,
+    }
+
+    impl SourceMap {
+        pub fn new(text: String) -> Self {
+            Self::starting_at(text, 1)
+        }
+
+        // A map whose first line is numbered `first`
+        pub fn starting_at(text: String, first: usize) -> Self {
+            let mut starts = vec![0];
+            starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+            Self { text, starts, first }
+        }
+
+        pub fn text(&self) -> &str {
+            &self.text
+        }
+
+        // A trailing newline ends the last line rather than starting an empty one
+        pub fn line_count(&self) -> usize {
+            match self.starts.last() {
+                Some(&last) if last == self.text.len() => self.starts.len() - 1,
+                _ => self.starts.len(),
+            }
+        }
+
+        // Returns a line without its line ending
+        pub fn line(&self, line: usize) -> Option<&str> {
+            if line < self.first || line - self.first >= self.line_count() {
+                return None;
+            }
+            let line = line - self.first + 1;
+
+            let start = self.starts[line - 1];
+            let end = self.starts.get(line).map_or(self.text.len(), |next| next - 1);
+            let content = &self.text[start..end];
+            Some(content.strip_suffix('\r').unwrap_or(content))
+        }
+
+        /*
+            Returns the line and column of a byte offset, the column counting from 1.
+            An offset in a line ending (either half of "\r\n") is the column just
+            after the last character, and offsets past the end are the end of the text.
+        */
+        pub fn location(&self, offset: usize) -> (usize, usize) {
+            let offset = offset.min(self.text.len());
+            let index = self.starts.partition_point(|&start| start <= offset) - 1;
+            let start = self.starts[index];
+
+            let line = self.first + index;
+            let content = self.line(line).unwrap_or("");
+            let before = &self.text[start..offset];
+            let col = before.chars().count().min(content.chars().count()) + 1;
+
+            (line, col)
+        }
+
+        // Returns the span covering the bytes from `start` up to (not including) `end`
+        pub fn span(&self, start: usize, end: usize) -> Span {
+            let (line, col) = self.location(start);
+            let (end_line, end_col) = self.location(end);
+            Span::new(line, col, end_line, end_col)
+        }
+    }
+
+    // Which file of the database a line belongs to, the file being run is always 0
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct FileId(pub usize);
+
+    pub struct SourceFile {
+        // What the file is called in messages, e.g. `examples/plotting.dy` or `<repl>`
+        pub name: String,
+        // Where it was read from, virtual files (REPL lines, code given on the command line) have none
+        pub path: Option<PathBuf>,
+        pub map: SourceMap,
+        // The span of the `include` that loaded it
+        pub included_from: Option<Span>,
+    }
+
+    /*
+        Every file loaded while running a program. Each file gets its own range of line numbers,
+        so a line (and so any span or node) says which file it came from without carrying a FileId.
+
+        main.dy         lines 1..=40
+        plotting.dy     lines 42..=90, shown as lines 1..=49
+    */
+    #[derive(Default)]
+    pub struct Sources {
+        files: Vec<SourceFile>,
+        // Included files that couldn't be read, so `--watch` can notice when they appear
+        missing: Vec<PathBuf>,
+    }
+
+    impl Sources {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn add(&mut self, name: &str, path: Option<PathBuf>, text: String, included_from: Option<Span>) -> FileId {
+            // One spare line after each file, for the end of a file with no lines
+            let first = self.files.last().map_or(1, |f| f.map.first + f.map.line_count() + 1);
+            self.files.push(SourceFile {
+                name: name.to_string(),
+                path,
+                map: SourceMap::starting_at(text, first),
+                included_from,
+            });
+            FileId(self.files.len() - 1)
+        }
+
+        pub fn file(&self, id: FileId) -> &SourceFile {
+            &self.files[id.0]
+        }
+
+        // Returns where every file that was read from disk came from
+        pub fn paths(&self) -> impl Iterator<Item = &Path> {
+            self.files.iter().filter_map(|f| f.path.as_deref())
+        }
+
+        // Records the path of an included file that couldn't be read
+        pub fn add_missing(&mut self, path: PathBuf) {
+            if !self.missing.contains(&path) {
+                self.missing.push(path);
+            }
+        }
+
+        // Returns where every included file that couldn't be read was looked for
+        pub fn missing(&self) -> impl Iterator<Item = &Path> {
+            self.missing.iter().map(PathBuf::as_path)
+        }
+
+        // Returns the file read from a path, if it has been loaded already
+        pub fn find(&self, path: &Path) -> Option<FileId> {
+            self.files.iter().position(|f| f.path.as_deref() == Some(path)).map(FileId)
+        }
+
+        // Returns the file a line belongs to
+        pub fn file_of(&self, line: usize) -> FileId {
+            FileId(self.files.iter().rposition(|f| f.map.first <= line).unwrap_or(0))
+        }
+
+        pub fn line(&self, line: usize) -> Option<&str> {
+            self.files.get(self.file_of(line).0)?.map.line(line)
+        }
+
+        // Returns the number a line has in its own file
+        pub fn local(&self, line: usize) -> usize {
+            match self.files.get(self.file_of(line).0) {
+                Some(file) => line + 1 - file.map.first,
+                None => line,
+            }
+        }
+
+        // Returns where a line is for a message, e.g. "line 4" or "plotting.dy, line 4"
+        pub fn describe(&self, line: usize) -> String {
+            match self.file_of(line) {
+                FileId(0) => format!("line {}", self.local(line)),
+                id => format!("{}, line {}", self.file(id).name, self.local(line)),
+            }
+        }
+
+        // Like `describe`, with the column when the span has one
+        pub fn locate(&self, span: Span) -> String {
+            match self.file_of(span.line) {
+                FileId(0) => self.position(span),
+                id => format!("{}, {}", self.file(id).name, self.position(span)),
+            }
+        }
+
+        // Returns where a span is within its own file, e.g. "line 4, column 9"
+        fn position(&self, span: Span) -> String {
+            match span.has_column() {
+                true => format!("line {}, column {}", self.local(span.line), span.col),
+                false => format!("line {}", self.local(span.line)),
+            }
+        }
+
+        // Returns a note for every `include` that led to the line's file, innermost first
+        pub fn included_from(&self, line: usize) -> Vec<String> {
+            let mut notes = Vec::new();
+            let mut id = self.file_of(line);
+            while let Some(file) = self.files.get(id.0) {
+                let Some(span) = file.included_from else {
+                    break;
+                };
+                id = self.file_of(span.line);
+                notes.push(format!("`{}` is included from {}, {}", file.name, self.file(id).name, self.position(span)));
+            }
+            notes
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{FileId, SourceMap, Sources};
+
+        fn map(text: &str) -> SourceMap {
+            SourceMap::new(text.to_string())
+        }
+
+        #[test]
+        fn splits_lines_on_newlines() {
+            let source = map("a = 1\nout a\n");
+            assert_eq!(source.line_count(), 2);
+            assert_eq!(source.line(1), Some("a = 1"));
+            assert_eq!(source.line(2), Some("out a"));
+            assert_eq!(source.line(3), None);
+            assert_eq!(source.line(0), None);
+        }
+
+        #[test]
+        fn keeps_last_line_without_trailing_newline() {
+            let source = map("a = 1\nout a");
+            assert_eq!(source.line_count(), 2);
+            assert_eq!(source.line(2), Some("out a"));
+            assert_eq!(source.location(10), (2, 5));
+        }
+
+        #[test]
+        fn empty_text_has_no_lines() {
+            let source = map("");
+            assert_eq!(source.line_count(), 0);
+            assert_eq!(source.line(1), None);
+            assert_eq!(source.location(0), (1, 1));
+        }
+
+        #[test]
+        fn maps_offsets_to_lines_and_columns() {
+            let source = map("a = 1\nout a\n");
+            assert_eq!(source.location(0), (1, 1));
+            assert_eq!(source.location(4), (1, 5));
+            assert_eq!(source.location(6), (2, 1));
+            assert_eq!(source.location(10), (2, 5));
+        }
+
+        #[test]
+        fn newline_belongs_to_the_line_it_ends() {
+            let source = map("a = 1\nout a\n");
+            assert_eq!(source.location(5), (1, 6));
+            assert_eq!(source.location(11), (2, 6));
+        }
+
+        #[test]
+        fn strips_carriage_returns() {
+            let source = map("a = 1\r\nout a\r\n");
+            assert_eq!(source.line_count(), 2);
+            assert_eq!(source.line(1), Some("a = 1"));
+            assert_eq!(source.line(2), Some("out a"));
+
+            // Both halves of "\r\n" sit just after the last character
+            assert_eq!(source.location(5), (1, 6));
+            assert_eq!(source.location(6), (1, 6));
+            assert_eq!(source.location(7), (2, 1));
+        }
+
+        #[test]
+        fn counts_a_tab_as_one_column() {
+            let source = map("if x\n\tout x\n");
+            assert_eq!(source.line(2), Some("\tout x"));
+            assert_eq!(source.location(5), (2, 1));
+            assert_eq!(source.location(6), (2, 2));
+            assert_eq!(source.location(10), (2, 6));
+        }
+
+        #[test]
+        fn counts_characters_not_bytes() {
+            // `é` is two bytes and `→` is three
+            let source = map("s = \"é→\" x");
+            assert_eq!(source.location(5), (1, 6));
+            assert_eq!(source.location(7), (1, 7));
+            assert_eq!(source.location(10), (1, 8));
+            assert_eq!(source.location(12), (1, 10));
+        }
+
+        #[test]
+        fn offsets_past_the_end_clamp() {
+            let source = map("out 1");
+            assert_eq!(source.location(100), (1, 6));
+        }
+
+        #[test]
+        fn spans_cover_a_byte_range() {
+            let source = map("a = 1\nout a\n");
+            let span = source.span(6, 9);
+            assert_eq!((span.line, span.col, span.end_line, span.end_col), (2, 1, 2, 4));
+
+            let span = source.span(4, 9);
+            assert_eq!((span.line, span.col, span.end_line, span.end_col), (1, 5, 2, 4));
+        }
+
+        #[test]
+        fn files_get_their_own_lines() {
+            let mut sources = Sources::new();
+            let main = sources.add("main.dy", None, "include lib end\nout f()\n".to_string(), None);
+            let span = sources.file(main).map.span(8, 11);
+            let lib = sources.add("lib.dy", None, "func f\n    return 1\nend".to_string(), Some(span));
+
+            // main.dy has lines 1 and 2, and a spare line 3
+            let (line, col) = sources.file(lib).map.location(11);
+            assert_eq!((line, col), (5, 5));
+            assert_eq!(sources.file_of(line), lib);
+            assert_eq!(sources.file_of(2), main);
+            assert_eq!(sources.local(line), 2);
+            assert_eq!(sources.line(line), Some("    return 1"));
+            assert_eq!(sources.describe(line), "lib.dy, line 2");
+            assert_eq!(sources.describe(2), "line 2");
+        }
+
+        #[test]
+        fn notes_follow_includes_outwards() {
+            let mut sources = Sources::new();
+            sources.add("main.dy", None, "include a end\n".to_string(), None);
+            let from_main = sources.file(FileId(0)).map.span(8, 9);
+            let a = sources.add("a.dy", None, "\ninclude b end\n".to_string(), Some(from_main));
+            let from_a = sources.file(a).map.span(9, 10);
+            let b = sources.add("b.dy", None, "out 1".to_string(), Some(from_a));
+
+            let line = sources.file(b).map.location(0).0;
+            assert_eq!(sources.included_from(line), [
+                "`b.dy` is included from a.dy, line 2, column 9",
+                "`a.dy` is included from main.dy, line 1, column 9",
+            ]);
+            assert!(sources.included_from(1).is_empty());
+        }
+    }
+}
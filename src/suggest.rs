@@ -0,0 +1,84 @@
+pub mod suggest {
+    /*
+        Returns how many single-character edits turn one word into the other,
+        counting an insertion, deletion, substitution or swap of two neighbours as one.
+
+        retrun -> return    1 (swap)
+        elsif  -> elif      1 (deletion)
+        lenght -> length    1 (swap)
+    */
+    pub fn distance(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+
+        // rows[i][j] is the distance between the first i characters of a and the first j of b
+        let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for (i, row) in rows.iter_mut().enumerate() {
+            row[0] = i;
+        }
+        for (j, cell) in rows[0].iter_mut().enumerate() {
+            *cell = j;
+        }
+
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                let cost = usize::from(a[i - 1] != b[j - 1]);
+                let mut best = (rows[i - 1][j] + 1)
+                    .min(rows[i][j - 1] + 1)
+                    .min(rows[i - 1][j - 1] + cost);
+
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    best = best.min(rows[i - 2][j - 2] + 1);
+                }
+                rows[i][j] = best;
+            }
+        }
+
+        rows[a.len()][b.len()]
+    }
+
+    // Returns the candidate closest to the name, if any is close enough to be a likely typo
+    // Longer names are allowed more edits, about one for every three characters
+    pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+        let limit = (name.chars().count() / 3).max(1);
+        let mut best: Option<(usize, &str)> = None;
+
+        for candidate in candidates {
+            if candidate == name {
+                continue;
+            }
+
+            // Only the case differs, nothing is closer
+            if candidate.eq_ignore_ascii_case(name) {
+                return Some(candidate);
+            }
+
+            let distance = distance(name, candidate);
+            if distance <= limit && best.is_none_or(|(d, _)| distance < d) {
+                best = Some((distance, candidate));
+            }
+        }
+
+        best.map(|(_, candidate)| candidate)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{closest, distance};
+
+        #[test]
+        fn swaps_count_as_one_edit() {
+            assert_eq!(distance("retrun", "return"), 1);
+            assert_eq!(distance("elsif", "elif"), 1);
+            assert_eq!(distance("", "out"), 3);
+        }
+
+        #[test]
+        fn only_close_names_are_suggested() {
+            assert_eq!(closest("lenght", ["length", "left"]), Some("length"));
+            assert_eq!(closest("Total", ["total", "Tota"]), Some("total"));
+            assert_eq!(closest("x", ["x"]), None);
+            assert_eq!(closest("width", ["height"]), None);
+        }
+    }
+}
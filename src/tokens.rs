@@ -1,20 +1,7 @@
 pub mod tokens {
+    use crate::diagnostic::diagnostic::Span;
 
-<<<<<<< HEAD
     #[derive(Debug, PartialEq, Eq, Clone)]
-=======
-    #[derive(Debug, PartialEq, Eq)]
->>>>>>> bd1a31444c128380d505fb63bcef4d74f948947a
-    pub enum MatchResult {
-        Match(Token),
-        None,
-    }
-
-<<<<<<< HEAD
-    #[derive(Debug, PartialEq, Eq, Clone)]
-=======
-    #[derive(Debug, PartialEq, Eq)]
->>>>>>> bd1a31444c128380d505fb63bcef4d74f948947a
     pub enum TokenKind {
         // Grouping Operators
         RPar,
@@ -30,12 +17,14 @@ pub mod tokens {
         Comma,
         Semicolon,
         Colon,
+        Hash,
+        At,
         //Tilde,
         //SlashSlash,
         Bar,
 
         // Operators
-        //Slash,
+        Slash,
         Plus,
         Minus,
         PlusEqual,
@@ -60,56 +49,75 @@ pub mod tokens {
 
         // Reserved Words
         Func,
-<<<<<<< HEAD
-=======
+        Return,
+        As,
         Matrix,
->>>>>>> bd1a31444c128380d505fb63bcef4d74f948947a
         Out,
         If,
         Elif,
         Else,
         For,
+        In,
+        True,
+        False,
+        Nil,
+        Match,
+        Case,
+        Enum,
         Const,
+        Raise,
+        Try,
+        Catch,
+        Include,
         End,
 
-        Type(String),
-
         // Other
-        EndOfFile, 
-        Empty, 
-        Newline, 
-        StringLiteral(String), 
+        EndOfFile,
+        Empty,
+        Newline,
+        // Only read when the lexer is asked to keep comments, the text starts with `//`
+        Comment(String),
+        Identifier(String),
+        StringLiteral(String),
         NumberLiteral(String),
     }
 
-<<<<<<< HEAD
     #[derive(Debug, PartialEq, Eq, Clone)]
-=======
-    #[derive(Debug, PartialEq, Eq)]
->>>>>>> bd1a31444c128380d505fb63bcef4d74f948947a
     pub struct Token {
         pub lex: String,
         pub kind: TokenKind,
+        // Filled in by the lexer once it knows where the token ends
         pub line: usize,
+        pub col: usize,
+        pub len: usize,
     }
 
     impl Token {
         // Create a new token from arguments
-        pub fn new(kind: TokenKind, lex: &str, line: &usize) -> Self {
+        pub fn new(kind: TokenKind, lex: &str) -> Self {
             Self {
                 kind,
                 lex: lex.to_string(),
-                line: *line,
+                line: 0,
+                col: 0,
+                len: 0,
             }
         }
 
+        // Returns the part of the source the token was read from
+        pub fn span(&self) -> Span {
+            Span::new(self.line, self.col, self.line, self.col + self.len.max(1))
+        }
+
         // Return end of file token
-        pub fn end(line: &usize) -> Self {
+        pub fn end() -> Self {
             Self {
                 kind: TokenKind::EndOfFile,
                 lex: "<END OF FILE>".to_string(),
-                line: *line,
+                line: 0,
+                col: 0,
+                len: 0,
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,64 @@
+pub mod watch {
+    use std::{fs, io::{self, IsTerminal, Write}, path::{Path, PathBuf}, thread, time::{Duration, SystemTime}};
+
+    use colored::Colorize;
+    use crate::source::source::Sources;
+
+    // How long to wait between looking at the files again
+    const INTERVAL: Duration = Duration::from_millis(300);
+
+    /*
+        `darcy run --watch main.dy`: runs the program, then looks at the modification time of
+        every file it read (the program and everything it included) until one changes, and runs
+        it again. Errors are reported as usual without stopping the watch, only Ctrl-C does.
+
+        Files are polled rather than watched with the OS, which is plenty for a handful of files
+        and works the same everywhere. A file that is deleted (or saved by replacing it) changes
+        too, so the run after it reports the missing file and the next save runs it again.
+        An include that couldn't be read is watched as well, so creating it runs the program again.
+    */
+    pub fn watch(root: &str, mut run: impl FnMut(&mut Sources) -> Result<(), i32>) -> ! {
+        loop {
+            clear();
+
+            let mut sources = Sources::new();
+            let result = run(&mut sources);
+
+            // The program itself is watched by the path it was given, as it may not exist yet
+            let mut files: Vec<PathBuf> = vec![PathBuf::from(root)];
+            files.extend(sources.paths().map(Path::to_path_buf));
+            files.extend(sources.missing().map(Path::to_path_buf));
+            let stamps: Vec<Option<SystemTime>> = files.iter().map(|path| modified(path)).collect();
+
+            let status = match result {
+                Ok(()) => "finished".to_string(),
+                Err(status) => format!("stopped with exit code {status}"),
+            };
+            // The program is in the database too, once it could be read
+            let count = sources.paths().count().saturating_sub(1);
+            let watching = match count {
+                0 => format!("`{root}`"),
+                _ => format!("`{root}` and {count} included file{}", if count == 1 { "" } else { "s" }),
+            };
+            eprintln!("{}", format!("[{status}, watching {watching} for changes, Ctrl-C to stop]").dimmed());
+
+            while files.iter().map(|path| modified(path)).eq(stamps.iter().copied()) {
+                thread::sleep(INTERVAL);
+            }
+        }
+    }
+
+    // When the file was last written to, or None if it can't be read
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    // Clears the terminal and moves to the top, so each run starts on a clean screen
+    // Nothing is written when the output is going to a file or pipe
+    fn clear() {
+        if io::stdout().is_terminal() {
+            print!("\x1b[2J\x1b[3J\x1b[H");
+            io::stdout().flush().ok();
+        }
+    }
+}